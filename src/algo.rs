@@ -0,0 +1,816 @@
+//! Execution algorithms that work a large parent order as a series of child
+//! orders: TWAP, VWAP, iceberg and percentage-of-volume.
+//!
+//! Each runs on its own task, fed market data through `on_book` / `on_trade`
+//! and child order updates through the account stream of the `TradingApi`.
+
+use crate::client::{ClientError, EdgeXClient};
+use crate::model::{CancelOrderRequest, CreateOrderRequest, Fill, Kline, KlineInterval, MetaData, OrderSide, OrderStatus, PublicTrade, TimeInForce};
+use crate::order_book::OrderBook;
+use crate::order_manager::new_client_order_id;
+use crate::trading::{check_response, response_order_id, TradingApi};
+use crate::websocket::AccountEvent;
+use futures_util::stream::BoxStream;
+use futures_util::{stream, Stream, StreamExt};
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Error, Debug)]
+pub enum AlgoError {
+    #[error("Invalid algo parameters: {0}")]
+    InvalidParameters(String),
+    #[error("Client error: {0}")]
+    Client(#[from] ClientError),
+}
+
+/// How expected volume is spread over an execution window, as weights per
+/// equal-length bucket summing to one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeProfile {
+    weights: Vec<Decimal>,
+}
+
+impl VolumeProfile {
+    pub fn uniform(buckets: usize) -> Self {
+        let buckets = buckets.max(1);
+        Self { weights: vec![Decimal::ONE / Decimal::from(buckets); buckets] }
+    }
+
+    /// Normalizes `weights`; they must not be negative or all zero.
+    pub fn from_weights(weights: Vec<Decimal>) -> Result<Self, AlgoError> {
+        let total: Decimal = weights.iter().sum();
+        if weights.iter().any(|w| w.is_sign_negative()) || total.is_zero() {
+            return Err(AlgoError::InvalidParameters("volume weights must be non-negative with a positive sum".to_string()));
+        }
+        Ok(Self { weights: weights.into_iter().map(|w| w / total).collect() })
+    }
+
+    /// Averages historical klines by time of day over the window
+    /// `[start_ms, start_ms + duration)`, which must not exceed a day.
+    /// Falls back to a uniform profile when the klines hold no volume.
+    pub fn from_klines(klines: &[Kline], start_ms: u64, duration: Duration, buckets: usize) -> Result<Self, AlgoError> {
+        let duration_ms = duration.as_millis() as u64;
+        if duration_ms == 0 || duration_ms > DAY_MS || buckets == 0 {
+            return Err(AlgoError::InvalidParameters("VWAP window must be between 1ms and a day".to_string()));
+        }
+        let mut weights = vec![Decimal::ZERO; buckets];
+        for kline in klines {
+            // Where this kline falls in the same window on its own day.
+            let offset = (kline.kline_time as i128 - start_ms as i128).rem_euclid(DAY_MS as i128) as u64;
+            if offset < duration_ms {
+                let bucket = (offset as u128 * buckets as u128 / duration_ms as u128) as usize;
+                weights[bucket] += Decimal::from_str(&kline.size).unwrap_or_default();
+            }
+        }
+        Self::from_weights(weights).or_else(|_| Ok(Self::uniform(buckets)))
+    }
+
+    /// Fetches `lookback_days` of klines before `start_ms` and builds the profile.
+    pub async fn load(
+        client: &EdgeXClient,
+        contract_id: u64,
+        start_ms: u64,
+        duration: Duration,
+        buckets: usize,
+        lookback_days: u32,
+    ) -> Result<Self, AlgoError> {
+        let bucket_ms = duration.as_millis() as u64 / buckets.max(1) as u64;
+        let interval = KlineInterval::ALL.iter().rev()
+            .find(|i| i.as_millis() <= bucket_ms)
+            .copied()
+            .unwrap_or(KlineInterval::Minute1);
+        let from = start_ms.saturating_sub(lookback_days as u64 * DAY_MS);
+        let klines = client.get_klines(contract_id, interval, from, start_ms).await?;
+        Self::from_klines(&klines, start_ms, duration, buckets)
+    }
+
+    pub fn buckets(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Share of the volume expected in the first `buckets` buckets.
+    fn cumulative(&self, buckets: usize) -> Decimal {
+        self.weights.iter().take(buckets).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlgoKind {
+    /// Equal slices at equal intervals over `duration`.
+    Twap { duration: Duration, slices: u32 },
+    /// One slice per profile bucket over `duration`, sized by the profile.
+    Vwap { duration: Duration, profile: VolumeProfile },
+    /// Rests at most `display_size` at the limit price at a time, refilling
+    /// as it fills. Needs a limit price.
+    Iceberg { display_size: Decimal },
+    /// Keeps executed size at `participation` (0..1) of the public volume
+    /// traded since the start, ours included.
+    Pov { participation: Decimal },
+}
+
+/// The full order to be worked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParentOrder {
+    pub contract_id: u64,
+    pub side: OrderSide,
+    pub size: Decimal,
+    /// No child is ever priced beyond this. Without it, children take the
+    /// best opposite level of the book passed to `on_book`.
+    pub limit_price: Option<Decimal>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlgoConfig {
+    /// How often the schedule is re-evaluated.
+    pub tick: Duration,
+    /// Caps any single child order.
+    pub max_child_size: Option<Decimal>,
+    /// How long a child whose placement outcome is unknown (transport error,
+    /// or no order id in the response) keeps its size reserved while waiting
+    /// for an order event.
+    pub child_ack_timeout: Duration,
+}
+
+impl Default for AlgoConfig {
+    fn default() -> Self {
+        Self { tick: Duration::from_millis(250), max_child_size: None, child_ack_timeout: Duration::from_secs(10) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlgoState {
+    Running,
+    Paused,
+    /// The full size was executed.
+    Completed,
+    /// The schedule ended with size left, e.g. because of the limit price.
+    Expired,
+    Cancelled,
+    Failed(String),
+}
+
+impl AlgoState {
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, AlgoState::Running | AlgoState::Paused)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlgoProgress {
+    pub state: AlgoState,
+    pub filled: Decimal,
+    pub remaining: Decimal,
+    /// Volume-weighted fill price so far.
+    pub avg_price: Option<Decimal>,
+    pub child_orders: u64,
+    pub last_error: Option<String>,
+}
+
+enum Command {
+    Pause,
+    Resume,
+    Cancel,
+    Book { best_bid: Option<Decimal>, best_ask: Option<Decimal> },
+    Trade(PublicTrade),
+}
+
+/// A running execution algorithm. Dropping the handle cancels it, the same
+/// as `cancel`. Open child orders are pulled whenever it finishes.
+pub struct ExecutionAlgo {
+    commands: mpsc::UnboundedSender<Command>,
+    progress: watch::Receiver<AlgoProgress>,
+    contract_id: u64,
+    task: JoinHandle<()>,
+}
+
+impl ExecutionAlgo {
+    pub async fn start(
+        api: Arc<dyn TradingApi>,
+        meta: MetaData,
+        account_id: u64,
+        parent: ParentOrder,
+        kind: AlgoKind,
+        config: AlgoConfig,
+    ) -> Result<Self, AlgoError> {
+        let contract = meta.contract(parent.contract_id)
+            .ok_or_else(|| AlgoError::InvalidParameters(format!("unknown contract {}", parent.contract_id)))?;
+        let step = Decimal::from_str(&contract.step_size)
+            .map_err(|_| AlgoError::InvalidParameters(format!("bad step size {:?}", contract.step_size)))?;
+        validate(&parent, &kind, step)?;
+
+        // Subscribe before the first child goes out so none of its fills are missed.
+        let account_events = api.account_events(account_id).await?;
+        let (commands, rx) = mpsc::unbounded_channel();
+        let (progress_tx, progress) = watch::channel(AlgoProgress {
+            state: AlgoState::Running,
+            filled: Decimal::ZERO,
+            remaining: parent.size,
+            avg_price: None,
+            child_orders: 0,
+            last_error: None,
+        });
+        let contract_id = parent.contract_id;
+        let runner = Runner {
+            api,
+            meta,
+            account_id,
+            parent,
+            kind,
+            config,
+            step,
+            started: Instant::now(),
+            paused: false,
+            filled: Decimal::ZERO,
+            notional: Decimal::ZERO,
+            children: HashSet::new(),
+            client_ids: HashSet::new(),
+            open_children: HashMap::new(),
+            unacked: HashMap::new(),
+            early_fills: HashMap::new(),
+            seen_fills: HashSet::new(),
+            child_orders: 0,
+            best_bid: None,
+            best_ask: None,
+            market_volume: Decimal::ZERO,
+            last_error: None,
+            progress: progress_tx,
+        };
+        let task = tokio::spawn(runner.run(rx, account_events));
+        Ok(Self { commands, progress, contract_id, task })
+    }
+
+    /// Stops sending children and pulls resting ones until `resume`.
+    pub fn pause(&self) {
+        let _ = self.commands.send(Command::Pause);
+    }
+
+    pub fn resume(&self) {
+        let _ = self.commands.send(Command::Resume);
+    }
+
+    /// Stops the algo and cancels its resting children.
+    pub fn cancel(&self) {
+        let _ = self.commands.send(Command::Cancel);
+    }
+
+    /// Feeds the latest book; other contracts are ignored.
+    pub fn on_book(&self, book: &OrderBook) {
+        if book.contract_id == self.contract_id {
+            let best_bid = book.best_bid().map(|(price, _)| price);
+            let best_ask = book.best_ask().map(|(price, _)| price);
+            let _ = self.commands.send(Command::Book { best_bid, best_ask });
+        }
+    }
+
+    /// Feeds a public trade (needed for POV); other contracts are ignored.
+    pub fn on_trade(&self, trade: &PublicTrade) {
+        if trade.contract_id == self.contract_id {
+            let _ = self.commands.send(Command::Trade(trade.clone()));
+        }
+    }
+
+    pub fn progress(&self) -> AlgoProgress {
+        self.progress.borrow().clone()
+    }
+
+    /// The current progress, then every change, ending with the terminal state.
+    pub fn progress_stream(&self) -> impl Stream<Item = AlgoProgress> + use<> {
+        stream::unfold((self.progress.clone(), true, false), |(mut rx, first, done)| async move {
+            if done || (!first && rx.changed().await.is_err()) {
+                return None;
+            }
+            let progress = rx.borrow_and_update().clone();
+            let done = progress.state.is_terminal();
+            Some((progress, (rx, false, done)))
+        })
+    }
+
+    /// Waits for the algo to finish and returns its final progress.
+    pub async fn wait(self) -> AlgoProgress {
+        let _ = self.task.await;
+        self.progress.borrow().clone()
+    }
+}
+
+fn validate(parent: &ParentOrder, kind: &AlgoKind, step: Decimal) -> Result<(), AlgoError> {
+    let invalid = |msg: &str| Err(AlgoError::InvalidParameters(msg.to_string()));
+    if parent.size <= Decimal::ZERO {
+        return invalid("size must be positive");
+    }
+    if parent.limit_price.is_some_and(|p| p <= Decimal::ZERO) {
+        return invalid("limit price must be positive");
+    }
+    match kind {
+        AlgoKind::Twap { duration, slices } if duration.is_zero() || *slices == 0 => invalid("TWAP needs a duration and at least one slice"),
+        AlgoKind::Vwap { duration, .. } if duration.is_zero() => invalid("VWAP needs a duration"),
+        AlgoKind::Iceberg { .. } if parent.limit_price.is_none() => invalid("iceberg orders need a limit price"),
+        AlgoKind::Iceberg { display_size } if *display_size < step => invalid("display size is below the step size"),
+        AlgoKind::Pov { participation } if *participation <= Decimal::ZERO || *participation > Decimal::ONE => {
+            invalid("participation must be in (0, 1]")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A child order known by its exchange id.
+struct Child {
+    size: Decimal,
+    /// Filled size from the fills seen so far.
+    seen: Decimal,
+    /// Filled size reported by the terminal order event, once there is one.
+    final_fill: Option<Decimal>,
+}
+
+impl Child {
+    /// Size that may still show up as fills.
+    fn reserved(&self) -> Decimal {
+        (self.final_fill.unwrap_or(self.size) - self.seen).max(Decimal::ZERO)
+    }
+}
+
+struct Runner {
+    api: Arc<dyn TradingApi>,
+    meta: MetaData,
+    account_id: u64,
+    parent: ParentOrder,
+    kind: AlgoKind,
+    config: AlgoConfig,
+    step: Decimal,
+    started: Instant,
+    paused: bool,
+    filled: Decimal,
+    notional: Decimal,
+    /// Exchange ids of our child orders.
+    children: HashSet<u64>,
+    client_ids: HashSet<String>,
+    /// Children that may still fill, or whose reported fills haven't all arrived.
+    open_children: HashMap<u64, Child>,
+    /// Children that may have reached the exchange but have no order id yet,
+    /// by client order id, with their size and when they were sent.
+    unacked: HashMap<String, (Decimal, Instant)>,
+    /// Fills for unknown order ids while children are unacked, in case one
+    /// of them turns out to be ours.
+    early_fills: HashMap<u64, Vec<Fill>>,
+    seen_fills: HashSet<u64>,
+    child_orders: u64,
+    best_bid: Option<Decimal>,
+    best_ask: Option<Decimal>,
+    market_volume: Decimal,
+    last_error: Option<String>,
+    progress: watch::Sender<AlgoProgress>,
+}
+
+impl Runner {
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<Command>, mut events: BoxStream<'static, AccountEvent>) {
+        let mut tick = tokio::time::interval(self.config.tick);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let state = loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(Command::Pause) => {
+                        self.paused = true;
+                        self.cancel_open_children().await;
+                    }
+                    Some(Command::Resume) => self.paused = false,
+                    Some(Command::Cancel) | None => break AlgoState::Cancelled,
+                    Some(Command::Book { best_bid, best_ask }) => {
+                        self.best_bid = best_bid;
+                        self.best_ask = best_ask;
+                    }
+                    Some(Command::Trade(trade)) => {
+                        self.market_volume += Decimal::from_str(&trade.size).unwrap_or_default();
+                    }
+                },
+                event = events.next() => match event {
+                    Some(event) => self.apply_event(event),
+                    None => break AlgoState::Failed("account stream ended".to_string()),
+                },
+                _ = tick.tick() => {}
+            }
+            if let Some(state) = self.step().await {
+                break state;
+            }
+            self.publish(if self.paused { AlgoState::Paused } else { AlgoState::Running });
+        };
+        // Leave nothing resting, whatever ended the run.
+        self.cancel_open_children().await;
+        self.publish(state);
+    }
+
+    fn remaining(&self) -> Decimal {
+        self.parent.size - self.filled
+    }
+
+    /// Sends whatever the schedule calls for; returns the final state once done.
+    async fn step(&mut self) -> Option<AlgoState> {
+        if self.remaining() <= Decimal::ZERO {
+            return Some(AlgoState::Completed);
+        }
+        self.expire_unacked();
+        let in_flight: Decimal = self.open_children.values().map(Child::reserved).sum::<Decimal>()
+            + self.unacked.values().map(|(size, _)| *size).sum::<Decimal>();
+        let elapsed = self.started.elapsed();
+        let target = match &self.kind {
+            AlgoKind::Twap { duration, slices } => {
+                let slices = *slices as u64;
+                let slice_ms = (duration.as_millis() as u64 / slices).max(1);
+                let due = (elapsed.as_millis() as u64 / slice_ms + 1).min(slices);
+                if elapsed >= *duration && in_flight.is_zero() {
+                    return Some(AlgoState::Expired);
+                }
+                self.parent.size * Decimal::from(due) / Decimal::from(slices)
+            }
+            AlgoKind::Vwap { duration, profile } => {
+                let bucket_ms = (duration.as_millis() as u64 / profile.buckets() as u64).max(1);
+                let due = (elapsed.as_millis() as u64 / bucket_ms + 1) as usize;
+                if elapsed >= *duration && in_flight.is_zero() {
+                    return Some(AlgoState::Expired);
+                }
+                self.parent.size * profile.cumulative(due)
+            }
+            AlgoKind::Iceberg { display_size } => {
+                // One slice rests at a time.
+                if !in_flight.is_zero() {
+                    return None;
+                }
+                self.filled + *display_size
+            }
+            AlgoKind::Pov { participation } => self.market_volume * *participation,
+        };
+        if self.paused {
+            return None;
+        }
+
+        let mut size = (target.min(self.parent.size) - self.filled - in_flight).max(Decimal::ZERO);
+        if let Some(max) = self.config.max_child_size {
+            size = size.min(max);
+        }
+        // Round down to the step; whatever is left over rolls into later slices.
+        size = (size / self.step).floor() * self.step;
+        if size.is_zero() {
+            return None;
+        }
+        let resting = matches!(self.kind, AlgoKind::Iceberg { .. });
+        if let Some(price) = self.child_price(resting) {
+            self.send_child(price, size, resting).await;
+        }
+        None
+    }
+
+    /// Aggressive children cross to the limit, or to the best opposite level
+    /// without one; either way never beyond the limit. `None` means wait.
+    fn child_price(&self, resting: bool) -> Option<Decimal> {
+        let limit = self.parent.limit_price;
+        if resting {
+            return limit;
+        }
+        let touch = match self.parent.side {
+            OrderSide::Buy => self.best_ask,
+            OrderSide::Sell => self.best_bid,
+        };
+        match (touch, limit) {
+            (Some(touch), Some(limit)) => {
+                let tradable = match self.parent.side {
+                    OrderSide::Buy => touch <= limit,
+                    OrderSide::Sell => touch >= limit,
+                };
+                tradable.then_some(limit)
+            }
+            (None, Some(limit)) => Some(limit),
+            (touch, None) => touch,
+        }
+    }
+
+    async fn send_child(&mut self, price: Decimal, size: Decimal, resting: bool) {
+        let mut req = match CreateOrderRequest::limit(&self.meta, self.account_id, self.parent.contract_id, self.parent.side, price, size) {
+            Ok(req) => req,
+            Err(e) => {
+                self.last_error = Some(e.to_string());
+                return;
+            }
+        };
+        if !resting {
+            req.time_in_force = TimeInForce::Ioc;
+        }
+        let client_order_id = new_client_order_id();
+        req.client_order_id = Some(client_order_id.clone());
+        self.client_ids.insert(client_order_id.clone());
+        self.child_orders += 1;
+        // Reserved until the exchange tells us what became of it, so a lost
+        // response can't lead to the size being sent twice.
+        self.unacked.insert(client_order_id.clone(), (size, Instant::now()));
+        match self.api.place_order(req, &self.meta).await {
+            Ok(res) => match check_response(&res) {
+                Ok(()) => {
+                    if let Some(order_id) = response_order_id(&res)
+                        && self.unacked.remove(&client_order_id).is_some()
+                    {
+                        self.open_children.entry(order_id).or_insert(Child { size, seen: Decimal::ZERO, final_fill: None });
+                        self.bind_child(order_id);
+                    }
+                }
+                Err(e) => {
+                    // Rejected: it never rested.
+                    self.unacked.remove(&client_order_id);
+                    self.last_error = Some(e.to_string());
+                }
+            },
+            Err(e) => self.last_error = Some(e.to_string()),
+        }
+    }
+
+    /// Records `order_id` as ours and applies any fills that arrived first.
+    fn bind_child(&mut self, order_id: u64) {
+        self.children.insert(order_id);
+        for fill in self.early_fills.remove(&order_id).unwrap_or_default() {
+            self.apply_fill(fill);
+        }
+        if self.unacked.is_empty() {
+            self.early_fills.clear();
+        }
+    }
+
+    /// Gives up on children that stayed unacked past `child_ack_timeout`.
+    fn expire_unacked(&mut self) {
+        let timeout = self.config.child_ack_timeout;
+        let before = self.unacked.len();
+        self.unacked.retain(|_, (_, sent)| sent.elapsed() < timeout);
+        if self.unacked.len() < before {
+            self.last_error = Some("child order unacknowledged, released its size".to_string());
+        }
+        if self.unacked.is_empty() {
+            self.early_fills.clear();
+        }
+    }
+
+    fn apply_event(&mut self, event: AccountEvent) {
+        match event {
+            AccountEvent::Order(order) => {
+                let ours = self.children.contains(&order.order_id)
+                    || order.client_order_id.as_ref().is_some_and(|id| self.client_ids.contains(id));
+                if !ours {
+                    return;
+                }
+                let unacked = order.client_order_id.as_ref().and_then(|id| self.unacked.remove(id));
+                let size = Decimal::from_str(&order.size).ok().or(unacked.map(|(size, _)| size));
+                if let Some(size) = size
+                    && !self.children.contains(&order.order_id)
+                {
+                    self.open_children.insert(order.order_id, Child { size, seen: Decimal::ZERO, final_fill: None });
+                }
+                let terminal = OrderStatus::from_exchange(&order.status).is_some_and(OrderStatus::is_terminal);
+                if terminal && let Some(child) = self.open_children.get_mut(&order.order_id) {
+                    // Fills can arrive after the order event that reports them.
+                    child.final_fill = Some(Decimal::from_str(&order.filled_size).unwrap_or(child.seen));
+                }
+                self.bind_child(order.order_id);
+                self.settle(order.order_id);
+            }
+            AccountEvent::Fill(fill) => {
+                if self.children.contains(&fill.order_id) {
+                    self.apply_fill(fill);
+                } else if !self.unacked.is_empty() {
+                    self.early_fills.entry(fill.order_id).or_default().push(fill);
+                }
+            }
+        }
+    }
+
+    fn apply_fill(&mut self, fill: Fill) {
+        if !self.seen_fills.insert(fill.id) {
+            return;
+        }
+        let (Ok(price), Ok(size)) = (Decimal::from_str(&fill.price), Decimal::from_str(&fill.size)) else {
+            return;
+        };
+        self.filled += size;
+        self.notional += price * size;
+        if let Some(child) = self.open_children.get_mut(&fill.order_id) {
+            child.seen += size;
+        }
+        self.settle(fill.order_id);
+    }
+
+    /// Forgets a child once it is done and all its fills are in.
+    fn settle(&mut self, order_id: u64) {
+        if self.open_children.get(&order_id).is_some_and(|child| child.final_fill.is_some() && child.reserved().is_zero()) {
+            self.open_children.remove(&order_id);
+        }
+    }
+
+    async fn cancel_open_children(&mut self) {
+        let order_ids: Vec<u64> = self.open_children.iter()
+            .filter(|(_, child)| child.final_fill.is_none())
+            .map(|(order_id, _)| *order_id)
+            .collect();
+        for order_id in order_ids {
+            let req = CancelOrderRequest::by_order_id(self.account_id, self.parent.contract_id, order_id);
+            match self.api.cancel_order(&req).await.and_then(|res| check_response(&res)) {
+                Ok(()) => {
+                    self.open_children.remove(&order_id);
+                }
+                Err(e) => self.last_error = Some(e.to_string()),
+            }
+        }
+        // These may be resting without us knowing their id. Their reservation
+        // stays until an order event or the ack timeout settles them.
+        let client_order_ids: Vec<String> = self.unacked.keys().cloned().collect();
+        for client_order_id in client_order_ids {
+            let req = CancelOrderRequest::by_client_order_id(self.account_id, self.parent.contract_id, &client_order_id);
+            if let Err(e) = self.api.cancel_order(&req).await.and_then(|res| check_response(&res)) {
+                self.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn publish(&self, state: AlgoState) {
+        let progress = AlgoProgress {
+            state,
+            filled: self.filled,
+            remaining: self.remaining().max(Decimal::ZERO),
+            avg_price: (!self.filled.is_zero()).then(|| self.notional / self.filled),
+            child_orders: self.child_orders,
+            last_error: self.last_error.clone(),
+        };
+        self.progress.send_if_modified(|current| {
+            let changed = *current != progress;
+            *current = progress;
+            changed
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::default_metadata;
+    use crate::model::{DepthUpdate, OpenOrder, Position, PriceLevel};
+    use crate::paper::PaperExchange;
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Loses the response to the first `lost` placements, after passing them
+    /// on to the paper exchange or not.
+    struct LossyApi {
+        paper: Arc<PaperExchange>,
+        lost: AtomicU32,
+        reaches_exchange: bool,
+    }
+
+    #[async_trait]
+    impl TradingApi for LossyApi {
+        async fn place_order(&self, req: CreateOrderRequest, meta: &MetaData) -> Result<Value, ClientError> {
+            if self.lost.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_err() {
+                return self.paper.place_order(req, meta).await;
+            }
+            if self.reaches_exchange {
+                self.paper.place_order(req, meta).await?;
+            }
+            Err(ClientError::ApiError("connection reset".to_string()))
+        }
+
+        async fn cancel_order(&self, req: &CancelOrderRequest) -> Result<Value, ClientError> {
+            self.paper.cancel_order(req).await
+        }
+
+        async fn get_open_orders(&self, account_id: u64) -> Result<Vec<OpenOrder>, ClientError> {
+            self.paper.get_open_orders(account_id).await
+        }
+
+        async fn get_fills(&self, account_id: u64) -> Result<Vec<Fill>, ClientError> {
+            self.paper.get_fills(account_id).await
+        }
+
+        async fn get_positions(&self, account_id: u64) -> Result<Vec<Position>, ClientError> {
+            self.paper.get_positions(account_id).await
+        }
+
+        async fn account_events(&self, account_id: u64) -> Result<BoxStream<'static, AccountEvent>, ClientError> {
+            self.paper.account_events(account_id).await
+        }
+    }
+
+    const CONTRACT: u64 = 10000001;
+
+    fn book(paper: &PaperExchange, ask: &str, ask_size: &str) -> OrderBook {
+        paper.apply_depth(&DepthUpdate {
+            contract_id: CONTRACT,
+            depth_type: "SNAPSHOT".to_string(),
+            bids: vec![PriceLevel { price: "49990".to_string(), size: "10".to_string() }],
+            asks: vec![PriceLevel { price: ask.to_string(), size: ask_size.to_string() }],
+        });
+        paper.book(CONTRACT).unwrap()
+    }
+
+    fn parent(size: i64, limit: Option<i64>) -> ParentOrder {
+        ParentOrder { contract_id: CONTRACT, side: OrderSide::Buy, size: Decimal::from(size), limit_price: limit.map(Decimal::from) }
+    }
+
+    #[tokio::test]
+    async fn test_twap_slices_and_limit_protection() {
+        let paper = Arc::new(PaperExchange::new(1, default_metadata()));
+        let config = AlgoConfig { tick: Duration::from_millis(10), ..Default::default() };
+        let twap = AlgoKind::Twap { duration: Duration::from_millis(400), slices: 4 };
+        let algo = ExecutionAlgo::start(paper.clone(), default_metadata(), 1, parent(4, Some(50_100)), twap.clone(), config.clone())
+            .await
+            .unwrap();
+        algo.on_book(&book(&paper, "50010", "100"));
+        let updates: Vec<_> = algo.progress_stream().collect().await;
+        let last = updates.last().unwrap();
+        assert_eq!(last.state, AlgoState::Completed);
+        assert_eq!((last.filled, last.child_orders), (Decimal::from(4), 4));
+        assert_eq!(last.avg_price, Some(Decimal::from(50_010)));
+        // Progress went up one slice at a time.
+        assert!(updates.iter().any(|p| p.filled == Decimal::TWO && p.state == AlgoState::Running));
+
+        // The book is above the limit: nothing trades and the schedule runs out.
+        let paper = Arc::new(PaperExchange::new(1, default_metadata()));
+        let algo = ExecutionAlgo::start(paper.clone(), default_metadata(), 1, parent(4, Some(50_000)), twap, config.clone())
+            .await
+            .unwrap();
+        algo.on_book(&book(&paper, "50010", "100"));
+        let last = algo.wait().await;
+        assert_eq!((last.state, last.filled, last.child_orders), (AlgoState::Expired, Decimal::ZERO, 0));
+
+        // An iceberg shows one slice at a time and pulls it on cancel.
+        let paper = Arc::new(PaperExchange::new(1, default_metadata()));
+        let iceberg = AlgoKind::Iceberg { display_size: Decimal::ONE };
+        let algo = ExecutionAlgo::start(paper.clone(), default_metadata(), 1, parent(5, Some(49_000)), iceberg, config.clone())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let open = paper.get_open_orders(1).await.unwrap();
+        assert_eq!((open.len(), open[0].size.as_str()), (1, "1"));
+        algo.cancel();
+        assert_eq!(algo.wait().await.state, AlgoState::Cancelled);
+        assert!(paper.get_open_orders(1).await.unwrap().is_empty());
+
+        // So does dropping the handle.
+        let iceberg = AlgoKind::Iceberg { display_size: Decimal::ONE };
+        let algo = ExecutionAlgo::start(paper.clone(), default_metadata(), 1, parent(5, Some(49_000)), iceberg, config)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(paper.get_open_orders(1).await.unwrap().len(), 1);
+        drop(algo);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(paper.get_open_orders(1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lost_child_responses_do_not_overfill() {
+        let config = AlgoConfig { tick: Duration::from_millis(10), max_child_size: None, child_ack_timeout: Duration::from_millis(50) };
+        let twap = AlgoKind::Twap { duration: Duration::from_millis(300), slices: 1 };
+        for reaches_exchange in [true, false] {
+            let paper = Arc::new(PaperExchange::new(1, default_metadata()));
+            let api = Arc::new(LossyApi { paper: paper.clone(), lost: AtomicU32::new(1), reaches_exchange });
+            let algo = ExecutionAlgo::start(api, default_metadata(), 1, parent(2, None), twap.clone(), config.clone())
+                .await
+                .unwrap();
+            algo.on_book(&book(&paper, "50010", "100"));
+            let last = algo.wait().await;
+            assert_eq!(last.state, AlgoState::Completed);
+            // Filled by the lost child itself, or by a retry once its reservation expired.
+            assert_eq!(last.child_orders, if reaches_exchange { 1 } else { 2 });
+            let executed: Decimal = paper.get_fills(1).await.unwrap().iter().map(|f| Decimal::from_str(&f.size).unwrap()).sum();
+            assert_eq!((last.filled, executed), (Decimal::TWO, Decimal::TWO));
+        }
+    }
+
+    #[test]
+    fn test_volume_profile_from_klines() {
+        let kline = |time: u64, size: &str| Kline {
+            kline_time: time,
+            open: "1".to_string(),
+            high: "1".to_string(),
+            low: "1".to_string(),
+            close: "1".to_string(),
+            size: size.to_string(),
+            value: String::new(),
+            trades: 1,
+        };
+        let start = 10 * DAY_MS + 3_600_000;
+        // Two days back: 3 in the first half hour of the window, 1 in the second,
+        // and one kline outside the window.
+        let klines = [
+            kline(start - DAY_MS, "2"),
+            kline(start - 2 * DAY_MS + 60_000, "1"),
+            kline(start - DAY_MS + 1_800_000, "1"),
+            kline(start - DAY_MS + 7_200_000, "50"),
+        ];
+        let profile = VolumeProfile::from_klines(&klines, start, Duration::from_secs(3600), 2).unwrap();
+        assert_eq!(profile.cumulative(1), Decimal::new(75, 2));
+        assert_eq!(profile.cumulative(2), Decimal::ONE);
+        assert_eq!(VolumeProfile::from_klines(&[], start, Duration::from_secs(3600), 4).unwrap(), VolumeProfile::uniform(4));
+    }
+}
@@ -0,0 +1,642 @@
+use crate::model::{
+    CreateOrderRequest, DepthUpdate, Fill, FundingRate, Kline, LiquidityRole, MetaData, OpenOrder, OrderSide,
+    OrderType, PublicTrade, TimeInForce,
+};
+use crate::order_book::OrderBook;
+use crate::position::{ContractPosition, PositionTracker};
+use crate::websocket::{parse_depth_updates, parse_public_trades, WsMessage};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum BacktestError {
+    #[error("Invalid order: {0}")]
+    InvalidOrder(String),
+}
+
+/// Market data the backtester replays, with the same types the live SDK parses.
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    Trade(PublicTrade),
+    Depth(DepthUpdate),
+    /// A kline, delivered when it closed.
+    Kline { contract_id: u64, kline: Kline },
+    Funding(FundingRate),
+}
+
+impl MarketEvent {
+    pub fn contract_id(&self) -> u64 {
+        match self {
+            MarketEvent::Trade(t) => t.contract_id,
+            MarketEvent::Depth(d) => d.contract_id,
+            MarketEvent::Kline { contract_id, .. } => *contract_id,
+            MarketEvent::Funding(f) => f.contract_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimedEvent {
+    pub time_ms: u64,
+    pub event: MarketEvent,
+}
+
+/// Turns recorded `(receive time, message)` pairs into replayable events.
+pub fn events_from_messages(messages: &[(u64, WsMessage)]) -> Vec<TimedEvent> {
+    let mut events = Vec::new();
+    for (time_ms, msg) in messages {
+        events.extend(parse_depth_updates(msg).into_iter()
+            .map(|d| TimedEvent { time_ms: *time_ms, event: MarketEvent::Depth(d) }));
+        events.extend(parse_public_trades(msg).into_iter()
+            .map(|t| TimedEvent { time_ms: *time_ms, event: MarketEvent::Trade(t) }));
+    }
+    events
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    /// Delay between the strategy sending an order or cancel and it reaching the matcher.
+    pub latency: Duration,
+    /// Where a new resting order joins the queue at its level: 0 is the front,
+    /// 1 the back (everything displayed at arrival trades first).
+    pub queue_fraction: Decimal,
+    /// Override the contracts' default fee rates.
+    pub maker_fee_rate: Option<Decimal>,
+    pub taker_fee_rate: Option<Decimal>,
+    /// Minimum spacing of points on the PnL curve.
+    pub sample_interval: Duration,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_millis(50),
+            queue_fraction: Decimal::ONE,
+            maker_fee_rate: None,
+            taker_fee_rate: None,
+            sample_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestReport {
+    /// `(time, total PnL)` after fees and funding, marked to the last price.
+    pub pnl_curve: Vec<(u64, Decimal)>,
+    pub final_pnl: Decimal,
+    /// Largest peak-to-trough drop of the PnL curve.
+    pub max_drawdown: Decimal,
+    /// Traded notional.
+    pub turnover: Decimal,
+    pub fees_paid: Decimal,
+    pub funding_paid: Decimal,
+    pub orders_submitted: u64,
+    pub fills: u64,
+    /// Filled size over submitted size.
+    pub fill_ratio: Decimal,
+    pub positions: Vec<ContractPosition>,
+}
+
+/// Callbacks driven by `Backtester::run`. All have empty defaults.
+pub trait BacktestStrategy {
+    fn on_trade(&mut self, _ctx: &mut BacktestContext, _trade: &PublicTrade) {}
+    /// Called after the update has been applied to `ctx.book(...)`.
+    fn on_depth(&mut self, _ctx: &mut BacktestContext, _update: &DepthUpdate) {}
+    fn on_kline(&mut self, _ctx: &mut BacktestContext, _contract_id: u64, _kline: &Kline) {}
+    fn on_funding(&mut self, _ctx: &mut BacktestContext, _funding: &FundingRate) {}
+    fn on_order_update(&mut self, _ctx: &mut BacktestContext, _order: &OpenOrder) {}
+    fn on_fill(&mut self, _ctx: &mut BacktestContext, _fill: &Fill) {}
+}
+
+struct SimOrder {
+    order: OpenOrder,
+    r#type: OrderType,
+    time_in_force: TimeInForce,
+    price: Decimal,
+    size: Decimal,
+    filled: Decimal,
+    /// Displayed size ahead of us at our level.
+    queue_ahead: Decimal,
+    /// Still travelling to the matcher.
+    in_flight: bool,
+}
+
+impl SimOrder {
+    fn remaining(&self) -> Decimal {
+        self.size - self.filled
+    }
+
+    fn is_live(&self) -> bool {
+        matches!(self.order.status.as_str(), "PENDING" | "OPEN" | "PARTIALLY_FILLED")
+    }
+}
+
+enum Action {
+    Place(u64),
+    Cancel(u64),
+}
+
+enum Notice {
+    Order(OpenOrder),
+    Fill(Fill),
+}
+
+/// The simulated exchange as seen by a strategy during a backtest.
+pub struct BacktestContext {
+    meta: MetaData,
+    config: BacktestConfig,
+    now_ms: u64,
+    next_id: u64,
+    books: HashMap<u64, OrderBook>,
+    orders: Vec<SimOrder>,
+    /// `(arrival time, action)`, in submission order.
+    pending: Vec<(u64, Action)>,
+    notices: Vec<Notice>,
+    positions: PositionTracker,
+    turnover: Decimal,
+    filled_size: Decimal,
+    submitted_size: Decimal,
+    fills: u64,
+}
+
+impl BacktestContext {
+    pub fn now_ms(&self) -> u64 {
+        self.now_ms
+    }
+
+    pub fn book(&self, contract_id: u64) -> Option<&OrderBook> {
+        self.books.get(&contract_id)
+    }
+
+    pub fn position(&self, contract_id: u64) -> Option<ContractPosition> {
+        self.positions.position(contract_id)
+    }
+
+    /// Live orders, including ones still in flight (status `PENDING`).
+    pub fn open_orders(&self) -> Vec<OpenOrder> {
+        self.orders.iter().filter(|o| o.is_live()).map(|o| o.order.clone()).collect()
+    }
+
+    /// Sends an order; it reaches the matcher after `latency`. L2 fields are
+    /// ignored. Returns the simulated order id.
+    ///
+    /// Price and size must parse, size must be positive and limit orders need a
+    /// positive price; a market order priced at zero has no worst price.
+    pub fn place_order(&mut self, req: CreateOrderRequest) -> Result<u64, BacktestError> {
+        let price = parse_decimal("price", &req.price)?;
+        let size = parse_decimal("size", &req.size)?;
+        if size <= Decimal::ZERO {
+            return Err(BacktestError::InvalidOrder(format!("size {} is not positive", size)));
+        }
+        if price.is_sign_negative() || (price.is_zero() && req.r#type != OrderType::Market) {
+            return Err(BacktestError::InvalidOrder(format!("price {} is not valid for a {:?} order", price, req.r#type)));
+        }
+        let order_id = self.next_id();
+        self.submitted_size += size;
+        self.orders.push(SimOrder {
+            order: OpenOrder {
+                order_id,
+                client_order_id: req.client_order_id.clone(),
+                contract_id: req.contract_id,
+                price: req.price.clone(),
+                size: req.size.clone(),
+                side: req.side,
+                status: "PENDING".to_string(),
+                filled_size: "0".to_string(),
+                remaining_size: req.size.clone(),
+            },
+            r#type: req.r#type,
+            time_in_force: req.time_in_force,
+            price,
+            size,
+            filled: Decimal::ZERO,
+            queue_ahead: Decimal::ZERO,
+            in_flight: true,
+        });
+        self.pending.push((self.now_ms + self.latency_ms(), Action::Place(order_id)));
+        Ok(order_id)
+    }
+
+    /// Requests a cancel; it takes effect after `latency`, so fills can still happen.
+    pub fn cancel_order(&mut self, order_id: u64) {
+        self.pending.push((self.now_ms + self.latency_ms(), Action::Cancel(order_id)));
+    }
+
+    fn latency_ms(&self) -> u64 {
+        self.config.latency.as_millis() as u64
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn fee_rate(&self, contract_id: u64, role: LiquidityRole) -> Decimal {
+        let contract = self.meta.contract(contract_id);
+        let (configured, default) = match role {
+            LiquidityRole::Maker => (self.config.maker_fee_rate, contract.map(|c| &c.default_maker_fee_rate)),
+            LiquidityRole::Taker => (self.config.taker_fee_rate, contract.map(|c| &c.default_taker_fee_rate)),
+        };
+        configured.or_else(|| default.and_then(|r| Decimal::from_str(r).ok())).unwrap_or_default()
+    }
+
+    /// Delivers every action that has arrived by `until_ms`.
+    fn process_pending(&mut self, until_ms: u64) {
+        while let Some(index) = self.pending.iter().position(|(arrival, _)| *arrival <= until_ms) {
+            let (arrival, action) = self.pending.remove(index);
+            self.now_ms = self.now_ms.max(arrival);
+            match action {
+                Action::Place(order_id) => self.arrive(order_id),
+                Action::Cancel(order_id) => {
+                    if let Some(index) = self.orders.iter().position(|o| o.order.order_id == order_id && o.is_live()) {
+                        self.orders[index].order.status = "CANCELED".to_string();
+                        self.notices.push(Notice::Order(self.orders[index].order.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// An order reaches the matcher: take what crosses, rest or cancel the rest.
+    fn arrive(&mut self, order_id: u64) {
+        let Some(index) = self.orders.iter().position(|o| o.order.order_id == order_id) else {
+            return;
+        };
+        let order = &mut self.orders[index];
+        order.in_flight = false;
+        if order.order.status != "PENDING" {
+            return;
+        }
+        order.order.status = "OPEN".to_string();
+        let (contract_id, side, size) = (order.order.contract_id, order.order.side, order.size);
+        let limit = (order.price > Decimal::ZERO).then_some(order.price);
+        let (tif, kind, price) = (order.time_in_force, order.r#type, order.price);
+
+        let book = self.books.entry(contract_id).or_insert_with(|| OrderBook::new(contract_id));
+        let executions = if tif == TimeInForce::Fok && book.available(side, limit) < size {
+            Vec::new()
+        } else {
+            book.take(side, limit, size)
+        };
+        let queue_ahead = book.size_at(side, price) * self.config.queue_fraction;
+        for (exec_price, exec_size) in executions {
+            self.execute(index, exec_price, exec_size, LiquidityRole::Taker);
+        }
+
+        let order = &mut self.orders[index];
+        order.queue_ahead = queue_ahead;
+        let rests = kind == OrderType::Limit && tif == TimeInForce::Gtc;
+        if !rests && !order.remaining().is_zero() {
+            order.order.status = "CANCELED".to_string();
+        }
+        if order.order.status == "OPEN" || order.order.status == "CANCELED" {
+            self.notices.push(Notice::Order(order.order.clone()));
+        }
+    }
+
+    fn execute(&mut self, index: usize, price: Decimal, size: Decimal, role: LiquidityRole) {
+        let fill_id = self.next_id();
+        let now = self.now_ms;
+        let order = &mut self.orders[index];
+        order.filled += size;
+        order.order.filled_size = order.filled.normalize().to_string();
+        order.order.remaining_size = order.remaining().normalize().to_string();
+        order.order.status = if order.remaining().is_zero() { "FILLED" } else { "PARTIALLY_FILLED" }.to_string();
+        let order_update = order.order.clone();
+
+        let fee = size * price * self.fee_rate(order_update.contract_id, role);
+        let fill = Fill {
+            id: fill_id,
+            order_id: order_update.order_id,
+            contract_id: order_update.contract_id,
+            price: price.normalize().to_string(),
+            size: size.normalize().to_string(),
+            side: order_update.side,
+            time: now,
+            fee: fee.normalize().to_string(),
+            fee_asset_id: self.meta.collateral().coin_id,
+            direction: Some(role),
+        };
+        // Fills are generated here with valid decimals, so this can't fail.
+        let _ = self.positions.apply_fill(&fill);
+        self.turnover += size * price;
+        self.filled_size += size;
+        self.fills += 1;
+        self.notices.push(Notice::Order(order_update));
+        self.notices.push(Notice::Fill(fill));
+    }
+
+    fn on_trade(&mut self, trade: &PublicTrade) {
+        let (Ok(price), Ok(mut available)) = (Decimal::from_str(&trade.price), Decimal::from_str(&trade.size)) else {
+            return;
+        };
+        self.positions.set_mark_price(trade.contract_id, price);
+        // A buyer-maker trade was a sell hitting bids, so it can fill our buys.
+        let hits = if trade.is_buyer_maker { OrderSide::Buy } else { OrderSide::Sell };
+
+        for index in 0..self.orders.len() {
+            let order = &mut self.orders[index];
+            if available.is_zero() {
+                break;
+            }
+            if order.in_flight || !order.is_live() || order.order.contract_id != trade.contract_id {
+                continue;
+            }
+            let through = match order.order.side {
+                OrderSide::Buy => price < order.price,
+                OrderSide::Sell => price > order.price,
+            };
+            let size = if through {
+                order.remaining().min(available)
+            } else if price == order.price && order.order.side == hits {
+                // At our level the queue ahead of us trades first.
+                let past_queue = (available - order.queue_ahead).max(Decimal::ZERO);
+                order.queue_ahead = (order.queue_ahead - available).max(Decimal::ZERO);
+                order.remaining().min(past_queue)
+            } else {
+                Decimal::ZERO
+            };
+            if !size.is_zero() {
+                available -= size;
+                let order_price = order.price;
+                self.execute(index, order_price, size, LiquidityRole::Maker);
+            }
+        }
+    }
+
+    fn on_depth(&mut self, update: &DepthUpdate) {
+        let book = self.books.entry(update.contract_id).or_insert_with(|| OrderBook::new(update.contract_id));
+        if book.apply(update).is_err() {
+            return;
+        }
+        if let Some(mid) = book.mid() {
+            self.positions.set_mark_price(update.contract_id, mid);
+        }
+
+        for index in 0..self.orders.len() {
+            let order = &mut self.orders[index];
+            if order.in_flight || !order.is_live() || order.order.contract_id != update.contract_id {
+                continue;
+            }
+            let book = self.books.get_mut(&update.contract_id).unwrap();
+            // Size leaving our level is assumed to have been ahead of us.
+            order.queue_ahead = order.queue_ahead.min(book.size_at(order.order.side, order.price));
+            // The book crossed us: we'd have traded as maker at our price.
+            let taken: Decimal = book.take(order.order.side, Some(order.price), order.remaining())
+                .iter()
+                .map(|(_, size)| *size)
+                .sum();
+            if !taken.is_zero() {
+                let order_price = order.price;
+                self.execute(index, order_price, taken, LiquidityRole::Maker);
+            }
+        }
+    }
+
+    /// Funding on open positions, at the oracle price: positive rates charge longs.
+    fn on_funding(&mut self, funding: &FundingRate) {
+        let (Ok(rate), Ok(price)) = (Decimal::from_str(&funding.funding_rate), Decimal::from_str(&funding.oracle_price)) else {
+            return;
+        };
+        if let Some(position) = self.positions.position(funding.contract_id) {
+            self.positions.apply_funding(funding.contract_id, -position.net_size * price * rate);
+        }
+    }
+
+    fn total_pnl(&self) -> Decimal {
+        let positions = self.positions.positions();
+        positions.iter().map(|p| p.net_realized_pnl()).sum::<Decimal>() + self.positions.total_unrealized_pnl()
+    }
+}
+
+/// Replays market events in timestamp order through a `BacktestStrategy`,
+/// simulating order latency, queue position and fees.
+pub struct Backtester {
+    meta: MetaData,
+    config: BacktestConfig,
+    events: Vec<TimedEvent>,
+}
+
+impl Backtester {
+    pub fn new(meta: MetaData) -> Self {
+        Self { meta, config: BacktestConfig::default(), events: Vec::new() }
+    }
+
+    pub fn with_config(mut self, config: BacktestConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Adds events from any source; they are merged by time when the run starts.
+    pub fn add_events(&mut self, events: impl IntoIterator<Item = TimedEvent>) {
+        self.events.extend(events);
+    }
+
+    pub fn run<S: BacktestStrategy>(&self, strategy: &mut S) -> BacktestReport {
+        let mut events: Vec<_> = self.events.iter().collect();
+        // Stable, so same-time events keep their recorded order.
+        events.sort_by_key(|e| e.time_ms);
+
+        let mut ctx = BacktestContext {
+            meta: self.meta.clone(),
+            config: self.config.clone(),
+            now_ms: events.first().map(|e| e.time_ms).unwrap_or_default(),
+            next_id: 0,
+            books: HashMap::new(),
+            orders: Vec::new(),
+            pending: Vec::new(),
+            notices: Vec::new(),
+            positions: PositionTracker::new(),
+            turnover: Decimal::ZERO,
+            filled_size: Decimal::ZERO,
+            submitted_size: Decimal::ZERO,
+            fills: 0,
+        };
+        let sample_ms = self.config.sample_interval.as_millis() as u64;
+        let mut pnl_curve: Vec<(u64, Decimal)> = Vec::new();
+
+        for timed in events {
+            ctx.process_pending(timed.time_ms);
+            deliver_notices(&mut ctx, strategy);
+            ctx.now_ms = ctx.now_ms.max(timed.time_ms);
+
+            match &timed.event {
+                MarketEvent::Trade(trade) => ctx.on_trade(trade),
+                MarketEvent::Depth(update) => ctx.on_depth(update),
+                MarketEvent::Kline { contract_id, kline } => {
+                    if let Ok(close) = Decimal::from_str(&kline.close) {
+                        ctx.positions.set_mark_price(*contract_id, close);
+                    }
+                }
+                MarketEvent::Funding(funding) => ctx.on_funding(funding),
+            }
+            deliver_notices(&mut ctx, strategy);
+
+            match &timed.event {
+                MarketEvent::Trade(trade) => strategy.on_trade(&mut ctx, trade),
+                MarketEvent::Depth(update) => strategy.on_depth(&mut ctx, update),
+                MarketEvent::Kline { contract_id, kline } => strategy.on_kline(&mut ctx, *contract_id, kline),
+                MarketEvent::Funding(funding) => strategy.on_funding(&mut ctx, funding),
+            }
+            // Zero latency means orders sent now trade against this same state.
+            ctx.process_pending(ctx.now_ms);
+            deliver_notices(&mut ctx, strategy);
+
+            if pnl_curve.last().is_none_or(|(t, _)| ctx.now_ms >= t + sample_ms) {
+                pnl_curve.push((ctx.now_ms, ctx.total_pnl()));
+            }
+        }
+
+        let final_pnl = ctx.total_pnl();
+        if pnl_curve.last().is_none_or(|(t, _)| *t != ctx.now_ms) {
+            pnl_curve.push((ctx.now_ms, final_pnl));
+        }
+        let positions = ctx.positions.positions();
+        BacktestReport {
+            max_drawdown: max_drawdown(&pnl_curve),
+            pnl_curve,
+            final_pnl,
+            turnover: ctx.turnover,
+            fees_paid: positions.iter().map(|p| p.fees_paid).sum(),
+            funding_paid: positions.iter().map(|p| p.funding_paid).sum(),
+            orders_submitted: ctx.orders.len() as u64,
+            fills: ctx.fills,
+            fill_ratio: if ctx.submitted_size.is_zero() { Decimal::ZERO } else { ctx.filled_size / ctx.submitted_size },
+            positions,
+        }
+    }
+}
+
+/// Hands queued order updates and fills to the strategy, including any its
+/// callbacks cause.
+fn deliver_notices<S: BacktestStrategy>(ctx: &mut BacktestContext, strategy: &mut S) {
+    while !ctx.notices.is_empty() {
+        for notice in std::mem::take(&mut ctx.notices) {
+            match notice {
+                Notice::Order(order) => strategy.on_order_update(ctx, &order),
+                Notice::Fill(fill) => strategy.on_fill(ctx, &fill),
+            }
+        }
+    }
+}
+
+fn max_drawdown(curve: &[(u64, Decimal)]) -> Decimal {
+    let mut peak = Decimal::MIN;
+    let mut drawdown = Decimal::ZERO;
+    for (_, value) in curve {
+        peak = peak.max(*value);
+        drawdown = drawdown.max(peak - *value);
+    }
+    drawdown
+}
+
+fn parse_decimal(field: &str, value: &str) -> Result<Decimal, BacktestError> {
+    Decimal::from_str(value.trim()).map_err(|_| BacktestError::InvalidOrder(format!("{} is not a valid decimal: {:?}", field, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::default_metadata;
+    use crate::model::PriceLevel;
+
+    const CONTRACT: u64 = 10000001;
+
+    /// Bids one lot at the best bid on the first book, then sells it 100 higher.
+    #[derive(Default)]
+    struct BuyThenSell {
+        bought: bool,
+        updates: Vec<String>,
+    }
+
+    fn order(side: OrderSide, price: &str) -> CreateOrderRequest {
+        CreateOrderRequest::unsigned(1, CONTRACT, side, price, "1")
+    }
+
+    impl BacktestStrategy for BuyThenSell {
+        fn on_depth(&mut self, ctx: &mut BacktestContext, _update: &DepthUpdate) {
+            if ctx.open_orders().is_empty() && !self.bought {
+                let bid = ctx.book(CONTRACT).unwrap().best_bid().unwrap().0;
+                assert!(ctx.place_order(order(OrderSide::Buy, "")).is_err());
+                assert!(ctx.place_order(order(OrderSide::Buy, "0")).is_err());
+                ctx.place_order(order(OrderSide::Buy, &bid.to_string())).unwrap();
+            }
+        }
+
+        fn on_order_update(&mut self, _ctx: &mut BacktestContext, order: &OpenOrder) {
+            self.updates.push(order.status.clone());
+        }
+
+        fn on_fill(&mut self, ctx: &mut BacktestContext, fill: &Fill) {
+            if fill.side == OrderSide::Buy {
+                self.bought = true;
+                ctx.place_order(order(OrderSide::Sell, "50100")).unwrap();
+            }
+        }
+    }
+
+    fn depth(time_ms: u64, bid: &str, bid_size: &str, ask: &str) -> TimedEvent {
+        TimedEvent {
+            time_ms,
+            event: MarketEvent::Depth(DepthUpdate {
+                contract_id: CONTRACT,
+                depth_type: "SNAPSHOT".to_string(),
+                bids: vec![PriceLevel { price: bid.to_string(), size: bid_size.to_string() }],
+                asks: vec![PriceLevel { price: ask.to_string(), size: "5".to_string() }],
+            }),
+        }
+    }
+
+    fn trade(time_ms: u64, price: &str, size: &str, is_buyer_maker: bool) -> TimedEvent {
+        TimedEvent {
+            time_ms,
+            event: MarketEvent::Trade(PublicTrade {
+                ticket_id: time_ms,
+                contract_id: CONTRACT,
+                price: price.to_string(),
+                size: size.to_string(),
+                is_buyer_maker,
+                time: time_ms,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_backtest_queue_and_report() {
+        let mut backtester = Backtester::new(default_metadata()).with_config(BacktestConfig {
+            sample_interval: Duration::ZERO,
+            ..Default::default()
+        });
+        backtester.add_events([
+            trade(3_000, "50000", "1", true),
+            depth(1_000, "50000", "2", "50010"),
+            // 2 lots ahead of us; this trade only eats into the queue.
+            trade(2_000, "50000", "1.5", true),
+            trade(4_000, "50100", "0.5", false),
+            TimedEvent {
+                time_ms: 3_500,
+                event: MarketEvent::Funding(FundingRate {
+                    contract_id: CONTRACT,
+                    funding_time: 3_500,
+                    funding_rate: "0.0001".to_string(),
+                    oracle_price: "50100".to_string(),
+                }),
+            },
+        ]);
+        let mut strategy = BuyThenSell::default();
+        let report = backtester.run(&mut strategy);
+
+        assert_eq!(strategy.updates, ["OPEN", "PARTIALLY_FILLED", "OPEN", "PARTIALLY_FILLED"]);
+        assert_eq!(report.fills, 2);
+        assert_eq!(report.orders_submitted, 2);
+        assert_eq!(report.turnover, Decimal::from(50_000 + 50_100) / Decimal::TWO);
+        assert_eq!(report.fill_ratio, Decimal::new(5, 1));
+        // Long 0.5 when funding hit, flat after selling it at 50100.
+        assert_eq!(report.positions[0].net_size, Decimal::ZERO);
+        assert_eq!(report.funding_paid, Decimal::new(2505, 3));
+        assert!(report.max_drawdown >= Decimal::ZERO);
+    }
+}
@@ -0,0 +1,605 @@
+//! `edgex`: command-line access to common exchange operations.
+//!
+//! Connection details and the L2 key come from a profile in a TOML file
+//! (`$EDGEX_CONFIG`, or `~/.config/edgex/config.toml`):
+//!
+//! ```toml
+//! [profiles.default]
+//! account_id = 123456
+//! key_file = "/home/ops/.edgex/l2.key"     # or key_env = "EDGEX_L2_KEY",
+//!                                          # or keystore = "..." with EDGEX_KEYSTORE_PASSWORD set
+//! # base_url = "https://pro.edgex.exchange"
+//! # ws_url = "wss://quote.edgex.exchange"
+//! ```
+//!
+//! Without a config file the key is read from `EDGEX_L2_KEY` and the account
+//! id from `EDGEX_ACCOUNT_ID`.
+//!
+//! Exits 1 on any error, including requests the exchange refuses, and 2 when
+//! `verify` finds the signature invalid.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use edgex_rust_sdk::client::EdgeXClient;
+use edgex_rust_sdk::model::{
+    CancelOrderRequest, ContractMeta, CreateOrderRequest, MetaData, OrderSide, OrderType, TimeInForce, TransferRequest,
+    WithdrawRequest,
+};
+use edgex_rust_sdk::secret::StarkSecretKey;
+use edgex_rust_sdk::signature::{parse_signature, verify_signature, SignatureManager};
+use edgex_rust_sdk::trading::check_response;
+use edgex_rust_sdk::websocket::EdgeXWebSocket;
+use futures_util::StreamExt;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const KEY_ENV: &str = "EDGEX_L2_KEY";
+const ACCOUNT_ENV: &str = "EDGEX_ACCOUNT_ID";
+const KEYSTORE_PASSWORD_ENV: &str = "EDGEX_KEYSTORE_PASSWORD";
+
+#[derive(Parser)]
+#[command(name = "edgex", about = "EdgeX exchange command-line tool")]
+struct Cli {
+    /// Config file with connection profiles.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[arg(long, short, global = true, default_value = "default")]
+    profile: String,
+    #[arg(long, short, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Place, cancel or list orders.
+    #[command(subcommand)]
+    Orders(OrdersCommand),
+    /// Recent fills.
+    Fills {
+        /// Only this contract (id or name).
+        #[arg(long)]
+        contract: Option<String>,
+    },
+    /// Open positions.
+    Positions,
+    /// Collateral balances and margin.
+    Balances,
+    /// Print websocket messages on a channel as JSON lines until interrupted.
+    Stream {
+        /// E.g. `depth.10000001.15`; omit with `--private`.
+        channel: Option<String>,
+        /// The authenticated account stream instead of a public channel.
+        #[arg(long)]
+        private: bool,
+    },
+    /// Transfer collateral to another account.
+    Transfer {
+        #[arg(long)]
+        to_account: u64,
+        /// Stark public key of the receiving account.
+        #[arg(long)]
+        to_key: String,
+        #[arg(long, value_parser = positive_decimal)]
+        amount: Decimal,
+    },
+    /// Withdraw collateral to an L1 address.
+    Withdraw {
+        #[arg(long)]
+        address: String,
+        #[arg(long, value_parser = positive_decimal)]
+        amount: Decimal,
+    },
+    /// Sign an arbitrary L2 hash with the profile's key.
+    Sign { hash: String },
+    /// Verify a signature over an L2 hash.
+    Verify {
+        hash: String,
+        /// `r || s` as 128 hex digits.
+        signature: String,
+        /// Defaults to the profile's own key.
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+    /// Contract metadata.
+    Metadata {
+        /// Only this contract (id or name).
+        contract: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum OrdersCommand {
+    /// Open orders.
+    List,
+    Place {
+        /// Contract id or name, e.g. `BTCUSDT`.
+        contract: String,
+        #[arg(value_enum)]
+        side: Side,
+        size: Decimal,
+        /// Limit price; for market orders the worst acceptable price.
+        price: Decimal,
+        #[arg(long)]
+        market: bool,
+        #[arg(long, value_enum, default_value_t = Tif::Gtc)]
+        tif: Tif,
+        #[arg(long)]
+        client_order_id: Option<String>,
+    },
+    Cancel {
+        contract: String,
+        #[arg(long, required_unless_present = "client_order_id")]
+        order_id: Option<u64>,
+        #[arg(long)]
+        client_order_id: Option<String>,
+    },
+    /// Cancel every open order, optionally on one contract.
+    CancelAll { contract: Option<String> },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Tif {
+    Gtc,
+    Ioc,
+    Fok,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+struct Profile {
+    account_id: Option<u64>,
+    base_url: Option<String>,
+    ws_url: Option<String>,
+    key_file: Option<PathBuf>,
+    key_env: Option<String>,
+    keystore: Option<PathBuf>,
+}
+
+impl Profile {
+    fn load(path: Option<PathBuf>, name: &str) -> Result<Self> {
+        let explicit = path.is_some();
+        let path = path
+            .or_else(|| std::env::var_os("EDGEX_CONFIG").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/edgex/config.toml")));
+        let config = match path {
+            Some(path) if explicit || path.exists() => {
+                let text = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+                toml::from_str::<ConfigFile>(&text).with_context(|| format!("parsing {}", path.display()))?
+            }
+            _ => ConfigFile::default(),
+        };
+        match config.profiles.get(name) {
+            Some(profile) => Ok(profile.clone()),
+            None if name == "default" => Ok(Profile::default()),
+            None => bail!("no profile named {:?}", name),
+        }
+    }
+
+    fn secret_key(&self) -> Result<StarkSecretKey> {
+        let key = if let Some(path) = &self.keystore {
+            let password = std::env::var(KEYSTORE_PASSWORD_ENV)
+                .with_context(|| format!("{} must be set to unlock the keystore", KEYSTORE_PASSWORD_ENV))?;
+            StarkSecretKey::from_keystore(path, &password)?
+        } else if let Some(path) = &self.key_file {
+            StarkSecretKey::from_file(path)?
+        } else {
+            StarkSecretKey::from_env(self.key_env.as_deref().unwrap_or(KEY_ENV))?
+        };
+        Ok(key)
+    }
+
+    fn account_id(&self) -> Result<u64> {
+        if let Some(id) = self.account_id {
+            return Ok(id);
+        }
+        let id = std::env::var(ACCOUNT_ENV).map_err(|_| anyhow!("no account_id in profile and {} not set", ACCOUNT_ENV))?;
+        id.parse().with_context(|| format!("{} is not a number", ACCOUNT_ENV))
+    }
+
+    fn client(&self) -> Result<EdgeXClient> {
+        let signer = SignatureManager::from_secret(self.secret_key()?);
+        let mut client = EdgeXClient::with_signer(Arc::new(signer), self.base_url.clone())?;
+        if let Some(ws_url) = &self.ws_url {
+            client = client.with_ws_url(ws_url);
+        }
+        Ok(client)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let profile = Profile::load(cli.config, &cli.profile)?;
+    let out = cli.output;
+
+    match cli.command {
+        Command::Orders(OrdersCommand::List) => {
+            let client = profile.client()?;
+            let orders = client.get_open_orders(profile.account_id()?).await?;
+            let rows = orders.iter()
+                .map(|o| vec![
+                    o.order_id.to_string(),
+                    o.client_order_id.clone().unwrap_or_default(),
+                    o.contract_id.to_string(),
+                    format!("{:?}", o.side),
+                    o.price.clone(),
+                    o.size.clone(),
+                    o.filled_size.clone(),
+                    o.status.clone(),
+                ])
+                .collect();
+            print(out, &orders, &["ORDER ID", "CLIENT ID", "CONTRACT", "SIDE", "PRICE", "SIZE", "FILLED", "STATUS"], rows)
+        }
+        Command::Orders(OrdersCommand::Place { contract, side, size, price, market, tif, client_order_id }) => {
+            let client = profile.client()?;
+            client.time_sync().sync().await?;
+            let meta = client.get_metadata().await?;
+            let contract = find_contract(&meta, &contract)?;
+            let side = match side {
+                Side::Buy => OrderSide::Buy,
+                Side::Sell => OrderSide::Sell,
+            };
+            let tif = match tif {
+                Tif::Gtc => TimeInForce::Gtc,
+                Tif::Ioc => TimeInForce::Ioc,
+                Tif::Fok => TimeInForce::Fok,
+            };
+            let mut req = CreateOrderRequest::limit(&meta, profile.account_id()?, contract.contract_id, side, price, size)?;
+            req.client_order_id = client_order_id;
+            req.time_in_force = tif;
+            if market {
+                req.r#type = OrderType::Market;
+                // Market orders can't rest.
+                if tif == TimeInForce::Gtc {
+                    req.time_in_force = TimeInForce::Ioc;
+                }
+            }
+            print_response(&client.place_order(req, &meta).await?)
+        }
+        Command::Orders(OrdersCommand::Cancel { contract, order_id, client_order_id }) => {
+            let client = profile.client()?;
+            let meta = client.get_metadata().await?;
+            let account_id = profile.account_id()?;
+            let req = CancelOrderRequest {
+                account_id,
+                order_id,
+                client_order_id,
+                contract_id: find_contract(&meta, &contract)?.contract_id,
+                l2_nonce: 0,
+                l2_signature: String::new(),
+            };
+            print_response(&client.cancel_order(&req).await?)
+        }
+        Command::Orders(OrdersCommand::CancelAll { contract }) => {
+            let client = profile.client()?;
+            let contract_ids = match contract {
+                Some(contract) => vec![find_contract(&client.get_metadata().await?, &contract)?.contract_id],
+                None => Vec::new(),
+            };
+            let filter = (!contract_ids.is_empty()).then_some(contract_ids.as_slice());
+            print_response(&client.cancel_all_orders(profile.account_id()?, filter).await?)
+        }
+        Command::Fills { contract } => {
+            let client = profile.client()?;
+            let contract_id = match contract {
+                Some(contract) => Some(find_contract(&client.get_metadata().await?, &contract)?.contract_id),
+                None => None,
+            };
+            let mut fills = client.get_fills(profile.account_id()?).await?;
+            fills.retain(|f| contract_id.is_none_or(|id| f.contract_id == id));
+            let rows = fills.iter()
+                .map(|f| vec![
+                    f.id.to_string(),
+                    f.order_id.to_string(),
+                    f.contract_id.to_string(),
+                    format!("{:?}", f.side),
+                    f.price.clone(),
+                    f.size.clone(),
+                    f.fee.clone(),
+                    f.direction.map(|d| format!("{:?}", d)).unwrap_or_default(),
+                    f.time.to_string(),
+                ])
+                .collect();
+            print(out, &fills, &["FILL ID", "ORDER ID", "CONTRACT", "SIDE", "PRICE", "SIZE", "FEE", "ROLE", "TIME"], rows)
+        }
+        Command::Positions => {
+            let client = profile.client()?;
+            let positions = client.get_positions(profile.account_id()?).await?;
+            let rows = positions.iter()
+                .map(|p| vec![
+                    p.contract_id.to_string(),
+                    p.open_size.clone(),
+                    p.open_value.clone(),
+                    p.open_fee.clone(),
+                    p.funding_fee.clone(),
+                ])
+                .collect();
+            print(out, &positions, &["CONTRACT", "SIZE", "VALUE", "FEE", "FUNDING"], rows)
+        }
+        Command::Balances => {
+            let client = profile.client()?;
+            let asset = client.get_account_asset(profile.account_id()?).await?;
+            let rows = asset.collateral_list.iter()
+                .map(|c| vec![
+                    c.coin_id.to_string(),
+                    c.amount.clone(),
+                    c.total_equity.clone(),
+                    c.available_amount.clone(),
+                    c.initial_margin_requirement.clone(),
+                    c.maintenance_margin_requirement.clone(),
+                ])
+                .collect();
+            print(out, &asset, &["COIN", "AMOUNT", "EQUITY", "AVAILABLE", "INITIAL MARGIN", "MAINT MARGIN"], rows)
+        }
+        Command::Stream { channel, private } => {
+            let mut ws = if private {
+                let client = profile.client()?;
+                EdgeXWebSocket::connect_private(&client, profile.account_id()?, profile.ws_url.as_deref()).await?
+            } else {
+                match &profile.ws_url {
+                    Some(url) => EdgeXWebSocket::connect_url(url).await?,
+                    None => EdgeXWebSocket::connect().await?,
+                }
+            };
+            match (channel, private) {
+                (Some(channel), _) => EdgeXWebSocket::subscribe(&mut ws, &channel).await?,
+                (None, true) => {}
+                (None, false) => bail!("a channel is required for public streams"),
+            }
+            let mut frames = Box::pin(EdgeXWebSocket::text_messages(ws));
+            while let Some(frame) = frames.next().await {
+                println!("{}", frame?);
+            }
+            Ok(())
+        }
+        Command::Transfer { to_account, to_key, amount } => {
+            let client = profile.client()?;
+            client.time_sync().sync().await?;
+            let meta = client.get_metadata().await?;
+            let req = TransferRequest {
+                account_id: profile.account_id()?,
+                coin_id: meta.collateral().coin_id,
+                amount: amount.normalize().to_string(),
+                receiver_account_id: to_account,
+                receiver_l2_key: to_key,
+                client_transfer_id: None,
+                l2_nonce: 0,
+                l2_expire_time: 0,
+                l2_signature: String::new(),
+            };
+            print_response(&client.transfer(req, &meta).await?)
+        }
+        Command::Withdraw { address, amount } => {
+            let client = profile.client()?;
+            client.time_sync().sync().await?;
+            let meta = client.get_metadata().await?;
+            let req = WithdrawRequest {
+                account_id: profile.account_id()?,
+                coin_id: meta.collateral().coin_id,
+                amount: amount.normalize().to_string(),
+                eth_address: address,
+                client_withdraw_id: None,
+                l2_nonce: 0,
+                l2_expire_time: 0,
+                l2_signature: String::new(),
+            };
+            print_response(&client.withdraw(req, &meta).await?)
+        }
+        Command::Sign { hash } => {
+            let signer = SignatureManager::from_secret(profile.secret_key()?);
+            let signature = signer.sign_l2_action(parse_felt("hash", &hash)?)?;
+            let public_key = format!("{:#x}", signer.public_key());
+            match out {
+                Output::Json => print_value(&serde_json::json!({ "signature": signature, "publicKey": public_key })),
+                Output::Table => {
+                    println!("{}", signature);
+                    Ok(())
+                }
+            }
+        }
+        Command::Verify { hash, signature, public_key } => {
+            let public_key = match public_key {
+                Some(key) => parse_felt("public key", &key)?,
+                None => SignatureManager::from_secret(profile.secret_key()?).public_key(),
+            };
+            let valid = verify_signature(&public_key, &parse_felt("hash", &hash)?, &parse_signature(&signature)?)?;
+            match out {
+                Output::Json => print_value(&serde_json::json!({ "valid": valid }))?,
+                Output::Table => println!("{}", if valid { "valid" } else { "INVALID" }),
+            }
+            if !valid {
+                std::process::exit(2);
+            }
+            Ok(())
+        }
+        Command::Metadata { contract } => {
+            let client = profile.client()?;
+            let meta = client.get_metadata().await?;
+            let contracts: Vec<&ContractMeta> = match &contract {
+                Some(contract) => vec![find_contract(&meta, contract)?],
+                None => meta.contract_list.iter().collect(),
+            };
+            let rows = contracts.iter()
+                .map(|c| vec![
+                    c.contract_id.to_string(),
+                    c.contract_name.clone(),
+                    c.tick_size.clone(),
+                    c.step_size.clone(),
+                    c.default_maker_fee_rate.clone(),
+                    c.default_taker_fee_rate.clone(),
+                    c.risk_tier_list.first().map(|t| t.max_leverage.to_string()).unwrap_or_default(),
+                ])
+                .collect();
+            if contract.is_some() {
+                print(out, &contracts[0], &["ID", "NAME", "TICK", "STEP", "MAKER FEE", "TAKER FEE", "MAX LEVERAGE"], rows)
+            } else {
+                print(out, &meta, &["ID", "NAME", "TICK", "STEP", "MAKER FEE", "TAKER FEE", "MAX LEVERAGE"], rows)
+            }
+        }
+    }
+}
+
+fn positive_decimal(value: &str) -> Result<Decimal, String> {
+    match value.trim().parse::<Decimal>() {
+        Ok(amount) if amount > Decimal::ZERO => Ok(amount),
+        Ok(_) => Err("must be positive".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Accepts a contract id or a (case-insensitive) name.
+fn find_contract<'a>(meta: &'a MetaData, contract: &str) -> Result<&'a ContractMeta> {
+    let found = match contract.parse::<u64>() {
+        Ok(id) => meta.contract(id),
+        Err(_) => meta.contract_list.iter().find(|c| c.contract_name.eq_ignore_ascii_case(contract)),
+    };
+    found.ok_or_else(|| anyhow!("unknown contract {:?}", contract))
+}
+
+fn parse_felt(what: &str, value: &str) -> Result<Felt> {
+    Felt::from_hex(value.trim()).map_err(|_| anyhow!("{} is not a valid hex field element: {:?}", what, value))
+}
+
+fn print<T: Serialize + ?Sized>(out: Output, value: &T, headers: &[&str], rows: Vec<Vec<String>>) -> Result<()> {
+    match out {
+        Output::Json => print_value(value),
+        Output::Table => {
+            print_table(headers, &rows);
+            Ok(())
+        }
+    }
+}
+
+/// Raw API responses print as JSON either way.
+fn print_value<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Prints an exchange reply, failing (and so exiting non-zero) if it was refused.
+fn print_response(response: &serde_json::Value) -> Result<()> {
+    check_response(response)?;
+    print_value(response)
+}
+
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(c, w)| format!("{:<w$}", c, w = *w)).collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amounts_must_be_positive() {
+        let transfer = |amount: &str| Cli::try_parse_from(["edgex", "transfer", "--to-account", "2", "--to-key", "0x1", "--amount", amount]);
+        let withdraw = |amount: &str| Cli::try_parse_from(["edgex", "withdraw", "--address", "0xabc", "--amount", amount]);
+        for amount in ["0", "-5", "0.000", "ten"] {
+            assert!(transfer(amount).is_err(), "{}", amount);
+            assert!(withdraw(amount).is_err(), "{}", amount);
+        }
+        let Command::Transfer { amount, .. } = transfer("1.5").unwrap().command else { panic!("not a transfer") };
+        assert_eq!(amount, Decimal::new(15, 1));
+        let Command::Withdraw { amount, .. } = withdraw("20").unwrap().command else { panic!("not a withdrawal") };
+        assert_eq!(amount, Decimal::from(20));
+    }
+
+    #[test]
+    fn test_profile_loading() {
+        let dir = std::env::temp_dir().join(format!("edgex-cli-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, r#"
+            [profiles.default]
+            account_id = 123456
+            key_env = "MY_KEY"
+
+            [profiles.testnet]
+            account_id = 7
+            base_url = "https://testnet.example"
+            ws_url = "wss://quote.testnet.example"
+            keystore = "/keys/testnet.json"
+        "#).unwrap();
+
+        let default = Profile::load(Some(path.clone()), "default").unwrap();
+        assert_eq!((default.account_id, default.key_env.as_deref()), (Some(123456), Some("MY_KEY")));
+        let testnet = Profile::load(Some(path.clone()), "testnet").unwrap();
+        assert_eq!(testnet.account_id().unwrap(), 7);
+        assert_eq!(testnet.base_url.as_deref(), Some("https://testnet.example"));
+        assert_eq!(testnet.ws_url.as_deref(), Some("wss://quote.testnet.example"));
+        assert_eq!(testnet.keystore, Some(PathBuf::from("/keys/testnet.json")));
+        assert!(Profile::load(Some(path.clone()), "prod").is_err());
+
+        // A config without the default profile falls back to the environment.
+        std::fs::write(&path, "[profiles.other]\naccount_id = 1\n").unwrap();
+        assert!(Profile::load(Some(path.clone()), "default").unwrap().account_id.is_none());
+
+        std::fs::write(&path, "[profiles.default]\naccount_id = \"abc\"\n").unwrap();
+        assert!(Profile::load(Some(path.clone()), "default").is_err());
+        // An explicit path has to exist.
+        assert!(Profile::load(Some(dir.join("missing.toml")), "default").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_contract() {
+        let meta: MetaData = serde_json::from_value(serde_json::json!({
+            "global": { "starkExCollateralCoin": {
+                "coinId": "1000", "coinName": "USDT", "starkExAssetId": "0x1", "starkExResolution": "0xf4240",
+            } },
+            "contractList": [{
+                "contractId": "10000001", "contractName": "BTCUSDT", "tickSize": "0.1", "stepSize": "0.001",
+                "starkExSyntheticAssetId": "0x2", "starkExResolution": "0x2540be400",
+                "defaultMakerFeeRate": "0.0002", "defaultTakerFeeRate": "0.0005",
+            }],
+        })).unwrap();
+        assert_eq!(find_contract(&meta, "10000001").unwrap().contract_name, "BTCUSDT");
+        assert_eq!(find_contract(&meta, "btcusdt").unwrap().contract_id, 10000001);
+        assert!(find_contract(&meta, "10000002").is_err());
+        assert!(find_contract(&meta, "ETHUSDT").is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use crate::client::{ClientError, EdgeXClient};
+use crate::model::{Kline, KlineInterval, PublicTrade};
+use crate::time_sync::TimeSync;
+use futures_util::{stream, Stream, StreamExt};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CandleError {
+    #[error("{field} is not a valid decimal: {value:?}")]
+    InvalidDecimal { field: &'static str, value: String },
+    #[error("Interval must be at least 1ms")]
+    ZeroInterval,
+    #[error("No exchange kline interval divides {0}ms")]
+    NotBackfillable(u64),
+}
+
+/// An OHLCV bar covering `[open_time_ms, open_time_ms + interval_ms)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candle {
+    pub contract_id: u64,
+    pub open_time_ms: u64,
+    pub interval_ms: u64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    /// Traded size.
+    pub volume: Decimal,
+    /// Traded notional.
+    pub turnover: Decimal,
+    pub trades: u64,
+}
+
+impl Candle {
+    pub fn close_time_ms(&self) -> u64 {
+        self.open_time_ms + self.interval_ms
+    }
+
+    fn flat(contract_id: u64, open_time_ms: u64, interval_ms: u64, price: Decimal) -> Self {
+        Self {
+            contract_id,
+            open_time_ms,
+            interval_ms,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: Decimal::ZERO,
+            turnover: Decimal::ZERO,
+            trades: 0,
+        }
+    }
+
+    /// Adds a trade. Open/close follow arrival order, which for late trades
+    /// may differ slightly from trade time order.
+    fn add_trade(&mut self, price: Decimal, size: Decimal) {
+        if self.trades == 0 && self.volume.is_zero() {
+            self.open = price;
+            self.high = price;
+            self.low = price;
+        }
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += size;
+        self.turnover += size * price;
+        self.trades += 1;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CandleUpdate {
+    /// The current bar changed.
+    InProgress(Candle),
+    /// A bar was finalized. Bars without trades are emitted flat at the previous close.
+    Closed(Candle),
+    /// A late trade changed a bar that was already closed.
+    Revised(Candle),
+}
+
+/// Builds candles of any interval from public trades.
+///
+/// A bar is closed once the latest trade (or clock tick) is past its end by
+/// `allowed_lateness`. Trades arriving after that amend the closed bar and are
+/// reported as `Revised`, as long as it is still within `max_history` bars.
+pub struct CandleBuilder {
+    contract_id: u64,
+    interval_ms: u64,
+    allowed_lateness_ms: u64,
+    max_history: usize,
+    open: BTreeMap<u64, Candle>,
+    closed: VecDeque<Candle>,
+    /// Open time of the first bar that isn't closed yet.
+    frontier_ms: Option<u64>,
+    watermark_ms: u64,
+    /// Trades before this are already counted by the backfill.
+    backfilled_until_ms: u64,
+    last_close: Option<Decimal>,
+    late_dropped: u64,
+}
+
+impl CandleBuilder {
+    pub fn new(contract_id: u64, interval: Duration) -> Result<Self, CandleError> {
+        let interval_ms = interval.as_millis() as u64;
+        if interval_ms == 0 {
+            return Err(CandleError::ZeroInterval);
+        }
+        Ok(Self {
+            contract_id,
+            interval_ms,
+            allowed_lateness_ms: 2_000,
+            max_history: 1_000,
+            open: BTreeMap::new(),
+            closed: VecDeque::new(),
+            frontier_ms: None,
+            watermark_ms: 0,
+            backfilled_until_ms: 0,
+            last_close: None,
+            late_dropped: 0,
+        })
+    }
+
+    /// How long past a bar's end trades are still folded in before it closes (default 2s).
+    pub fn with_allowed_lateness(mut self, lateness: Duration) -> Self {
+        self.allowed_lateness_ms = lateness.as_millis() as u64;
+        self
+    }
+
+    /// Closed bars kept for revisions and `history` (default 1000).
+    pub fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history;
+        self
+    }
+
+    pub fn interval_ms(&self) -> u64 {
+        self.interval_ms
+    }
+
+    /// Closed bars, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Candle> {
+        self.closed.iter()
+    }
+
+    /// The latest bar that isn't closed yet.
+    pub fn current(&self) -> Option<&Candle> {
+        self.open.values().next_back()
+    }
+
+    /// Trades too old to be placed in any kept bar.
+    pub fn late_dropped(&self) -> u64 {
+        self.late_dropped
+    }
+
+    pub fn apply_trade(&mut self, trade: &PublicTrade) -> Result<Vec<CandleUpdate>, CandleError> {
+        let price = parse_decimal("price", &trade.price)?;
+        let size = parse_decimal("size", &trade.size)?;
+        if trade.time < self.backfilled_until_ms {
+            return Ok(Vec::new());
+        }
+
+        let open_time = trade.time - trade.time % self.interval_ms;
+        let mut updates = Vec::new();
+        if self.frontier_ms.is_none_or(|frontier| open_time >= frontier) {
+            let (contract_id, interval_ms) = (self.contract_id, self.interval_ms);
+            let candle = self.open.entry(open_time)
+                .or_insert_with(|| Candle::flat(contract_id, open_time, interval_ms, price));
+            candle.add_trade(price, size);
+            updates.push(CandleUpdate::InProgress(candle.clone()));
+        } else if let Some(candle) = self.closed.iter_mut().find(|c| c.open_time_ms == open_time) {
+            candle.add_trade(price, size);
+            updates.push(CandleUpdate::Revised(candle.clone()));
+        } else {
+            self.late_dropped += 1;
+        }
+
+        updates.extend(self.advance_to(trade.time));
+        Ok(updates)
+    }
+
+    /// Closes every bar that ended more than `allowed_lateness` before `now_ms`.
+    /// Call on a timer so bars close even when no trades arrive.
+    pub fn advance_to(&mut self, now_ms: u64) -> Vec<CandleUpdate> {
+        self.watermark_ms = self.watermark_ms.max(now_ms);
+        let mut updates = Vec::new();
+        let Some(mut open_time) = self.frontier_ms.or_else(|| self.open.keys().next().copied()) else {
+            return updates;
+        };
+        while open_time + self.interval_ms + self.allowed_lateness_ms <= self.watermark_ms {
+            let candle = match self.open.remove(&open_time) {
+                Some(candle) => Some(candle),
+                None => self.last_close
+                    .map(|close| Candle::flat(self.contract_id, open_time, self.interval_ms, close)),
+            };
+            if let Some(candle) = candle {
+                self.last_close = Some(candle.close);
+                self.push_closed(candle.clone());
+                updates.push(CandleUpdate::Closed(candle));
+            }
+            open_time += self.interval_ms;
+        }
+        self.frontier_ms = Some(open_time);
+        updates
+    }
+
+    /// Seeds the builder with exchange klines whose interval divides ours.
+    /// Klines still in progress at `now_ms` are skipped; live trades take over
+    /// from the end of the last complete one.
+    pub fn backfill(&mut self, klines: &[Kline], kline_interval: KlineInterval, now_ms: u64) -> Result<Vec<CandleUpdate>, CandleError> {
+        let kline_ms = kline_interval.as_millis();
+        if !self.interval_ms.is_multiple_of(kline_ms) {
+            return Err(CandleError::NotBackfillable(self.interval_ms));
+        }
+
+        let mut klines: Vec<_> = klines.iter().filter(|k| k.kline_time + kline_ms <= now_ms).collect();
+        klines.sort_by_key(|k| k.kline_time);
+        for kline in klines {
+            let open_time = kline.kline_time - kline.kline_time % self.interval_ms;
+            if self.frontier_ms.is_some_and(|frontier| open_time < frontier) {
+                continue;
+            }
+            let (open, high, low, close) = (
+                parse_decimal("open", &kline.open)?,
+                parse_decimal("high", &kline.high)?,
+                parse_decimal("low", &kline.low)?,
+                parse_decimal("close", &kline.close)?,
+            );
+            let volume = parse_decimal("size", &kline.size)?;
+            let turnover = if kline.value.is_empty() { volume * close } else { parse_decimal("value", &kline.value)? };
+
+            let (contract_id, interval_ms) = (self.contract_id, self.interval_ms);
+            let candle = self.open.entry(open_time)
+                .or_insert_with(|| Candle::flat(contract_id, open_time, interval_ms, open));
+            candle.high = candle.high.max(high);
+            candle.low = candle.low.min(low);
+            candle.close = close;
+            candle.volume += volume;
+            candle.turnover += turnover;
+            candle.trades += kline.trades.max(1);
+            self.backfilled_until_ms = self.backfilled_until_ms.max(kline.kline_time + kline_ms);
+        }
+        Ok(self.advance_to(now_ms))
+    }
+
+    /// Fetches `lookback` worth of history over REST and feeds it to `backfill`,
+    /// using the coarsest exchange interval that divides ours.
+    pub async fn backfill_from(&mut self, client: &EdgeXClient, lookback: Duration) -> Result<Vec<CandleUpdate>, ClientError> {
+        let kline_interval = kline_interval_for(self.interval_ms)
+            .ok_or(CandleError::NotBackfillable(self.interval_ms))?;
+        let now_ms = client.time_sync().now_millis();
+        let start_ms = now_ms.saturating_sub(lookback.as_millis() as u64);
+        let start_ms = start_ms - start_ms % self.interval_ms;
+        let klines = client.get_klines(self.contract_id, kline_interval, start_ms, now_ms).await?;
+        Ok(self.backfill(&klines, kline_interval, now_ms)?)
+    }
+
+    /// Drives the builder from a trade stream, ticking every `tick` so bars close
+    /// on time during quiet periods. Ticks read the exchange clock from
+    /// `time_sync`, the same clock trades are stamped with. Trades for other
+    /// contracts or with unparsable numbers are skipped. Ends when `trades` ends.
+    pub fn stream<S>(self, trades: S, time_sync: Arc<TimeSync>, tick: Duration) -> impl Stream<Item = CandleUpdate>
+    where
+        S: Stream<Item = PublicTrade> + Send + Unpin + 'static,
+    {
+        enum Input {
+            Trade(PublicTrade),
+            Tick,
+            End,
+        }
+        let ticks = stream::unfold(tokio::time::interval(tick), |mut interval| async move {
+            interval.tick().await;
+            Some((Input::Tick, interval))
+        });
+        // `End` marks the trade stream finishing, since `select` would otherwise
+        // keep going on ticks alone.
+        let trades = trades.map(Input::Trade).chain(stream::once(async { Input::End }));
+        let inputs = stream::select(trades.boxed(), ticks.boxed());
+
+        stream::unfold((self, inputs, VecDeque::new(), time_sync), |(mut builder, mut inputs, mut pending, time_sync)| async move {
+            loop {
+                if let Some(update) = pending.pop_front() {
+                    return Some((update, (builder, inputs, pending, time_sync)));
+                }
+                match inputs.next().await? {
+                    Input::Trade(trade) if trade.contract_id == builder.contract_id => {
+                        pending.extend(builder.apply_trade(&trade).unwrap_or_default());
+                    }
+                    Input::Trade(_) => {}
+                    Input::Tick => pending.extend(builder.advance_to(time_sync.now_millis())),
+                    Input::End => return None,
+                }
+            }
+        })
+    }
+
+    fn push_closed(&mut self, candle: Candle) {
+        self.closed.push_back(candle);
+        while self.closed.len() > self.max_history {
+            self.closed.pop_front();
+        }
+    }
+}
+
+/// The coarsest exchange kline interval that evenly divides `interval_ms`.
+pub fn kline_interval_for(interval_ms: u64) -> Option<KlineInterval> {
+    KlineInterval::ALL.iter().rev().copied().find(|k| interval_ms.is_multiple_of(k.as_millis()))
+}
+
+fn parse_decimal(field: &'static str, value: &str) -> Result<Decimal, CandleError> {
+    Decimal::from_str(value.trim()).map_err(|_| CandleError::InvalidDecimal { field, value: value.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockExchange, MockExchangeConfig};
+    use crate::time_sync::TimeSyncConfig;
+
+    fn trade(time: u64, price: &str, size: &str) -> PublicTrade {
+        PublicTrade {
+            ticket_id: time,
+            contract_id: 1,
+            price: price.to_string(),
+            size: size.to_string(),
+            is_buyer_maker: false,
+            time,
+        }
+    }
+
+    fn closed(updates: &[CandleUpdate]) -> Vec<&Candle> {
+        updates.iter().filter_map(|u| match u {
+            CandleUpdate::Closed(c) => Some(c),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_candles_from_trades() {
+        assert_eq!(kline_interval_for(90 * 60 * 1000), Some(KlineInterval::Minute30));
+        assert_eq!(kline_interval_for(10_000), None);
+
+        let mut builder = CandleBuilder::new(1, Duration::from_secs(10)).unwrap()
+            .with_allowed_lateness(Duration::from_secs(1));
+        builder.apply_trade(&trade(1_000, "100", "1")).unwrap();
+        builder.apply_trade(&trade(5_000, "105", "1")).unwrap();
+        builder.apply_trade(&trade(9_000, "95", "2")).unwrap();
+        // Still within the lateness window of the first bar.
+        builder.apply_trade(&trade(10_500, "101", "1")).unwrap();
+        let updates = builder.apply_trade(&trade(9_900, "99", "1")).unwrap();
+        assert!(closed(&updates).is_empty());
+
+        // Jumping ahead closes bars 0 and 1, and gap-fills bar 2 flat.
+        let updates = builder.apply_trade(&trade(31_500, "110", "1")).unwrap();
+        let bars = closed(&updates);
+        assert_eq!(bars.len(), 3);
+        assert_eq!((bars[0].open, bars[0].high, bars[0].low, bars[0].close), (
+            Decimal::from(100), Decimal::from(105), Decimal::from(95), Decimal::from(99),
+        ));
+        assert_eq!(bars[0].volume, Decimal::from(5));
+        assert_eq!(bars[1].volume, Decimal::ONE);
+        assert_eq!(bars[2].open_time_ms, 20_000);
+        assert_eq!(bars[2].volume, Decimal::ZERO);
+        assert_eq!(bars[2].close, Decimal::from(101));
+
+        // Late trade into a closed bar is a revision.
+        let updates = builder.apply_trade(&trade(8_000, "120", "1")).unwrap();
+        assert!(matches!(&updates[0], CandleUpdate::Revised(c) if c.high == Decimal::from(120)));
+        assert_eq!(builder.current().unwrap().open_time_ms, 30_000);
+    }
+
+    #[tokio::test]
+    async fn test_stream_closes_bars_on_exchange_clock() {
+        // The exchange runs a minute ahead; trades carry its timestamps.
+        let mock = MockExchange::start_with(MockExchangeConfig { server_time_offset_ms: 60_000, ..Default::default() }).await.unwrap();
+        let time_sync = Arc::new(TimeSync::new(reqwest::Client::new(), &mock.base_url(), TimeSyncConfig::default()));
+        time_sync.sync().await.unwrap();
+
+        let trades = stream::iter([trade(time_sync.now_millis(), "100", "1")]).chain(stream::pending());
+        let builder = CandleBuilder::new(1, Duration::from_secs(1)).unwrap()
+            .with_allowed_lateness(Duration::ZERO);
+        let mut updates = Box::pin(builder.stream(trades, time_sync.clone(), Duration::from_millis(50)));
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(update) = updates.next().await {
+                if let CandleUpdate::Closed(candle) = update {
+                    return candle;
+                }
+            }
+            unreachable!("trade stream never ends")
+        }).await.unwrap();
+        assert_eq!(closed.close, Decimal::from(100));
+        assert!(closed.close_time_ms() <= time_sync.now_millis());
+    }
+}
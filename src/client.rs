@@ -1,12 +1,31 @@
-use crate::model::CreateOrderRequest;
-use crate::signature::SignatureManager;
+use crate::model::{
+    AccountAsset, AccountSettings, CancelOrderRequest, ContractMeta, CreateOrderRequest, Fill, LeverageError,
+    FundingPayment, Kline, KlineInterval, MarginMode, MetaData, OpenOrder, Page, Position, TransferRequest,
+    WithdrawRequest,
+};
+use crate::nonce::{NonceError, NonceManager};
+use crate::order_manager::{new_client_order_id, tagged_client_order_id, validate_strategy_tag, InvalidStrategyTag, OrderManager};
+use crate::risk::{RiskManager, RiskViolation};
+use crate::self_trade::{RestingOrder, SelfTradePrevention, SelfTradeViolation};
+use crate::signature::{format_signature, message_hash, SignatureManager};
+use crate::signer::StarkSigner;
+use crate::stark_order::{OrderHashError, StarkLimitOrder, StarkTransfer, StarkWithdrawal};
+use crate::time_sync::{TimeSync, TimeSyncConfig};
+use crate::trading::check_response;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use reqwest::Client;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json::Value;
-use std::time::{SystemTime, UNIX_EPOCH};
+use starknet_types_core::felt::Felt;
+use std::sync::Arc;
 use thiserror::Error;
+use tokio::task::JoinHandle;
 
-const BASE_URL: &str = "https://pro.edgex.exchange";
+pub(crate) const BASE_URL: &str = "https://pro.edgex.exchange";
+const SHUTDOWN_CANCEL_ATTEMPTS: usize = 3;
+const HISTORY_PAGE_SIZE: u32 = 100;
 
 #[derive(Error, Debug)]
 pub enum ClientError {
@@ -16,181 +35,551 @@ pub enum ClientError {
     SignatureError(#[from] crate::signature::SignatureError),
     #[error("API error: {0}")]
     ApiError(String),
+    #[error("Order hash error: {0}")]
+    OrderHashError(#[from] OrderHashError),
+    #[error("Nonce error: {0}")]
+    NonceError(#[from] NonceError),
+    #[error("Position error: {0}")]
+    PositionError(#[from] crate::position::PositionError),
+    #[error("Rejected by pre-trade risk check: {0}")]
+    RiskRejected(#[from] RiskViolation),
+    #[error("Leverage error: {0}")]
+    LeverageError(#[from] LeverageError),
+    #[error("Candle error: {0}")]
+    CandleError(#[from] crate::candle::CandleError),
+    #[error("Recorder error: {0}")]
+    RecorderError(#[from] crate::recorder::RecorderError),
+    #[error("Rejected by self-trade prevention: {0}")]
+    SelfTradeRejected(#[from] SelfTradeViolation),
+    #[error("Order dropped by self-trade prevention; cancelled resting orders {cancelled:?}")]
+    SelfTradePrevented { cancelled: Vec<u64> },
+    #[error("{0}")]
+    InvalidStrategyTag(#[from] InvalidStrategyTag),
 }
 
 pub struct EdgeXClient {
     client: Client,
-    signature_manager: SignatureManager,
+    signer: Arc<dyn StarkSigner>,
     base_url: String,
+    account_id: Option<u64>,
+    nonce_manager: Arc<NonceManager>,
+    time_sync: Arc<TimeSync>,
+    order_manager: Option<Arc<OrderManager>>,
+    risk_manager: Option<Arc<RiskManager>>,
+    self_trade_prevention: Option<Arc<SelfTradePrevention>>,
+    strategy_tag: Option<String>,
+    ws_url: Option<String>,
 }
 
 impl EdgeXClient {
     pub fn new(private_key: &str, base_url: Option<String>) -> Result<Self, ClientError> {
         let signature_manager = SignatureManager::new(private_key)?;
+        Self::with_signer(Arc::new(signature_manager), base_url)
+    }
+
+    /// Creates a client whose L2 key is held by `signer` (e.g. a `RemoteSigner`)
+    /// instead of in this process.
+    pub fn with_signer(signer: Arc<dyn StarkSigner>, base_url: Option<String>) -> Result<Self, ClientError> {
         let client = Client::builder().build()?;
         let base_url = base_url.unwrap_or_else(|| BASE_URL.to_string());
 
+        let time_sync = Arc::new(TimeSync::new(client.clone(), &base_url, TimeSyncConfig::default()));
+
         Ok(Self {
             client,
-            signature_manager,
+            signer,
             base_url,
+            account_id: None,
+            nonce_manager: Arc::new(NonceManager::default()),
+            time_sync,
+            order_manager: None,
+            risk_manager: None,
+            self_trade_prevention: None,
+            strategy_tag: None,
+            ws_url: None,
         })
     }
 
-    pub async fn create_order(&self, req: &CreateOrderRequest) -> Result<Value, ClientError> {
-        let url = format!("{}/api/v1/private/order/createOrder", self.base_url);
-        
-        // TODO: The request object 'req' should already have l2Signature populated, 
-        // OR we should sign it here.
-        // For now, assuming caller or a builder helper handles signing before passing here, 
-        // or we clone and sign here.
-        
-        // Let's assume we implement a helper to sign and create the request.
-        // But for this raw method, we take the request as is.
-        
-        let body = serde_json::to_string(req).map_err(|e| ClientError::ApiError(e.to_string()))?;
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis().to_string();
-        
-        let path = "/api/v1/private/order/createOrder";
-        // Header signature content usually: timestamp + method + path + body
-        let _sign_payload = format!("{}{}{}{}", timestamp, "POST", path, body);
-        
-        // TODO: header signature implementation is uncertain.
-        // If it requires L2 Key signing of this payload:
-        // let header_signature = self.signature_manager.sign_message(&_sign_payload).await?;
-        // But sign_message is not implemented for Stark key yet (needs definition of hash algo).
-        // For now, use a placeholder or fail.
-        // To proceed with SDK dev, we assume we can add this later.
-        let header_signature = "0x0000000000000000000000000000000000000000".to_string(); // Temporary
+    /// Binds the client to an account so callers don't have to pass it around.
+    pub fn with_account_id(mut self, account_id: u64) -> Self {
+        self.account_id = Some(account_id);
+        self
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert("X-edgeX-Api-Timestamp", HeaderValue::from_str(&timestamp).unwrap());
-        headers.insert("X-edgeX-Api-Signature", HeaderValue::from_str(&header_signature).unwrap());
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    /// Replaces the default nonce allocation, which is random and not persisted
+    /// across restarts.
+    pub fn with_nonce_manager(mut self, nonce_manager: NonceManager) -> Self {
+        self.nonce_manager = Arc::new(nonce_manager);
+        self
+    }
 
-        let res = self.client.post(&url)
-            .headers(headers)
-            .body(body)
-            .send()
-            .await?;
+    pub fn nonce_manager(&self) -> &Arc<NonceManager> {
+        &self.nonce_manager
+    }
+
+    /// Allocates a fresh L2 nonce for `account_id`, unique across every task sharing this client.
+    pub fn next_nonce(&self, account_id: u64) -> Result<u64, ClientError> {
+        Ok(self.nonce_manager.next(account_id)? as u64)
+    }
+
+    pub fn with_time_sync_config(mut self, config: TimeSyncConfig) -> Self {
+        self.time_sync = Arc::new(TimeSync::new(self.client.clone(), &self.base_url, config));
+        self
+    }
+
+    /// Clock used for request timestamps and default order expiry.
+    pub fn time_sync(&self) -> &Arc<TimeSync> {
+        &self.time_sync
+    }
+
+    /// Syncs with the server clock once, then keeps refreshing in the background.
+    /// Abort the returned handle to stop refreshing.
+    pub async fn start_time_sync(&self) -> Result<JoinHandle<()>, ClientError> {
+        self.time_sync.sync().await?;
+        Ok(self.time_sync.clone().spawn_refresh())
+    }
+
+    /// Records every order sent through `create_order` / `place_order` in `order_manager`.
+    pub fn with_order_manager(mut self, order_manager: Arc<OrderManager>) -> Self {
+        self.order_manager = Some(order_manager);
+        self
+    }
+
+    pub fn order_manager(&self) -> Option<&Arc<OrderManager>> {
+        self.order_manager.as_ref()
+    }
+
+    /// Runs `risk_manager` checks in `place_order` before signing; its kill
+    /// switch also blocks raw `create_order` calls.
+    pub fn with_risk_manager(mut self, risk_manager: Arc<RiskManager>) -> Self {
+        self.risk_manager = Some(risk_manager);
+        self
+    }
+
+    pub fn risk_manager(&self) -> Option<&Arc<RiskManager>> {
+        self.risk_manager.as_ref()
+    }
+
+    /// Checks every order in `place_order` against the account's open orders
+    /// and resolves crosses as `self_trade_prevention`'s mode says.
+    pub fn with_self_trade_prevention(mut self, self_trade_prevention: Arc<SelfTradePrevention>) -> Self {
+        self.self_trade_prevention = Some(self_trade_prevention);
+        self
+    }
 
-        let status = res.status();
-        if !status.is_success() {
-            let text = res.text().await?;
-            return Err(ClientError::ApiError(format!("Status: {}, Body: {}", status, text)));
+    pub fn self_trade_prevention(&self) -> Option<&Arc<SelfTradePrevention>> {
+        self.self_trade_prevention.as_ref()
+    }
+
+    /// Tags the client order ids generated by `place_order` with `tag`; see
+    /// `order_manager::strategy_tag`. Fails unless the tag passes
+    /// `order_manager::validate_strategy_tag`.
+    pub fn with_strategy_tag(mut self, tag: &str) -> Result<Self, ClientError> {
+        validate_strategy_tag(tag)?;
+        self.strategy_tag = Some(tag.to_string());
+        Ok(self)
+    }
+
+    pub fn strategy_tag(&self) -> Option<&str> {
+        self.strategy_tag.as_deref()
+    }
+
+    pub fn account_id(&self) -> Option<u64> {
+        self.account_id
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Websocket host used by `TradingApi::account_events`; defaults to the public quote host.
+    pub fn with_ws_url(mut self, ws_url: &str) -> Self {
+        self.ws_url = Some(ws_url.to_string());
+        self
+    }
+
+    pub fn ws_url(&self) -> Option<&str> {
+        self.ws_url.as_deref()
+    }
+
+    pub fn signer(&self) -> &Arc<dyn StarkSigner> {
+        &self.signer
+    }
+
+    /// Signs an L2 action hash (order, transfer, withdrawal) into the `l2Signature` format.
+    pub async fn sign_l2_action(&self, hash: &Felt) -> Result<String, ClientError> {
+        let signature = self.signer.sign_hash(hash).await?;
+        Ok(format_signature(&signature))
+    }
+
+    /// Fills in `l2Signature` from the request's L2 fields and the contract metadata.
+    pub async fn sign_order(&self, req: &mut CreateOrderRequest, meta: &MetaData) -> Result<(), ClientError> {
+        let hash = StarkLimitOrder::from_request(req, meta)?.hash()?;
+        req.l2_signature = self.sign_l2_action(&hash).await?;
+        Ok(())
+    }
+
+    /// Allocates the L2 nonce, signs and submits the order.
+    /// An `l2_expire_time` of 0 is replaced by the server-time based default,
+    /// and a missing client order id is generated (tagged with the strategy tag, if set).
+    ///
+    /// With self-trade prevention configured, the order is checked once signed.
+    /// One it drops fails with `ClientError::SelfTradePrevented`, after any
+    /// resting orders the mode cancels, without reaching the exchange.
+    pub async fn place_order(&self, mut req: CreateOrderRequest, meta: &MetaData) -> Result<Value, ClientError> {
+        if let Some(risk) = &self.risk_manager {
+            risk.check(&req, meta.contract(req.contract_id))?;
+        }
+        if req.client_order_id.is_none() {
+            req.client_order_id = Some(match &self.strategy_tag {
+                Some(tag) => tagged_client_order_id(tag),
+                None => new_client_order_id(),
+            });
+        }
+        req.l2_nonce = self.next_nonce(req.account_id)?;
+        if req.l2_expire_time == 0 {
+            req.l2_expire_time = self.time_sync.default_l2_expire_time();
+        }
+        // Sign first, so nothing is cancelled for an order that can't be sent.
+        self.sign_order(&mut req, meta).await?;
+        if let Some(stp) = &self.self_trade_prevention {
+            self.prevent_self_trade(stp, &req).await?;
         }
+        self.create_order(&req).await
+    }
 
-        let json: Value = res.json().await?;
-        Ok(json)
+    /// Cancels the resting orders `req` would cross, as the mode requires, and
+    /// fails if `req` should not be sent.
+    async fn prevent_self_trade(&self, stp: &SelfTradePrevention, req: &CreateOrderRequest) -> Result<(), ClientError> {
+        let open_orders: Vec<RestingOrder> = match stp.order_manager() {
+            Some(orders) => orders.open_orders(req.account_id, Some(req.contract_id)).iter().filter_map(RestingOrder::from_tracked).collect(),
+            None => self.get_open_orders(req.account_id).await?.iter().filter_map(RestingOrder::from_open_order).collect(),
+        };
+        let resolution = stp.check(req, &open_orders)?;
+        for &order_id in &resolution.cancel {
+            let cancel = CancelOrderRequest::by_order_id(req.account_id, req.contract_id, order_id);
+            check_response(&self.cancel_order(&cancel).await?)?;
+        }
+        if !resolution.send {
+            return Err(ClientError::SelfTradePrevented { cancelled: resolution.cancel });
+        }
+        Ok(())
     }
 
-    pub async fn cancel_order(&self, req: &crate::model::CancelOrderRequest) -> Result<Value, ClientError> {
-        let url = format!("{}/api/v1/private/order/cancelOrderById", self.base_url);
-        // Uses same Header auth mechanism
-        
-        let body = serde_json::to_string(req).map_err(|e| ClientError::ApiError(e.to_string()))?;
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis().to_string();
-        let path = "/api/v1/private/order/cancelOrderById";
-        
-        let header_signature = "0x0000000000000000000000000000000000000000".to_string(); // Temporary
+    pub async fn create_order(&self, req: &CreateOrderRequest) -> Result<Value, ClientError> {
+        // The request is expected to carry its l2Signature already; see `sign_order`.
+        if let Some(risk) = &self.risk_manager {
+            risk.check_kill_switch()?;
+        }
+        let handle = self.order_manager.as_ref().map(|m| m.record_submitted(req));
+        let result = self.post_private("/api/v1/private/order/createOrder", req).await;
+        if let (Some(manager), Some(handle)) = (&self.order_manager, handle) {
+            match &result {
+                Ok(json) => manager.record_ack(handle, json),
+                // The order may still have reached the exchange; leave it
+                // pending for reconciliation to settle.
+                Err(ClientError::RequestError(_)) => {}
+                Err(e) => manager.record_rejected(handle, &e.to_string()),
+            }
+        }
+        result
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert("X-edgeX-Api-Timestamp", HeaderValue::from_str(&timestamp).unwrap());
-        headers.insert("X-edgeX-Api-Signature", HeaderValue::from_str(&header_signature).unwrap());
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    /// Transfers collateral to another account. Nonce, expiry and client id
+    /// are filled in the same way as for `place_order`.
+    pub async fn transfer(&self, mut req: TransferRequest, meta: &MetaData) -> Result<Value, ClientError> {
+        if req.client_transfer_id.is_none() {
+            req.client_transfer_id = Some(new_client_order_id());
+        }
+        req.l2_nonce = self.next_nonce(req.account_id)?;
+        if req.l2_expire_time == 0 {
+            req.l2_expire_time = self.time_sync.default_l2_expire_time();
+        }
+        let hash = StarkTransfer::from_request(&req, meta)?.hash()?;
+        req.l2_signature = self.sign_l2_action(&hash).await?;
+        self.post_private("/api/v1/private/transfer/createTransferOut", &req).await
+    }
 
-        let res = self.client.post(&url)
-            .headers(headers)
-            .body(body)
-            .send()
-            .await?;
+    /// Withdraws collateral to an L1 address.
+    pub async fn withdraw(&self, mut req: WithdrawRequest, meta: &MetaData) -> Result<Value, ClientError> {
+        if req.client_withdraw_id.is_none() {
+            req.client_withdraw_id = Some(new_client_order_id());
+        }
+        req.l2_nonce = self.next_nonce(req.account_id)?;
+        if req.l2_expire_time == 0 {
+            req.l2_expire_time = self.time_sync.default_l2_expire_time();
+        }
+        let hash = StarkWithdrawal::from_request(&req, meta)?.hash()?;
+        req.l2_signature = self.sign_l2_action(&hash).await?;
+        self.post_private("/api/v1/private/assets/createNormalWithdraw", &req).await
+    }
 
-        let status = res.status();
-        if !status.is_success() {
-            let text = res.text().await?;
-            return Err(ClientError::ApiError(format!("Status: {}, Body: {}", status, text)));
+    pub async fn cancel_order(&self, req: &CancelOrderRequest) -> Result<Value, ClientError> {
+        self.post_private("/api/v1/private/order/cancelOrderById", req).await
+    }
+
+    /// Mass-cancels the account's open orders, optionally only on `contract_ids`.
+    pub async fn cancel_all_orders(&self, account_id: u64, contract_ids: Option<&[u64]>) -> Result<Value, ClientError> {
+        let body = serde_json::json!({
+            "accountId": account_id.to_string(),
+            "filterContractIdList": contract_ids.unwrap_or_default().iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+        });
+        self.post_private("/api/v1/private/order/cancelAllOrder", &body).await
+    }
+
+    /// Arms the exchange-side dead-man's switch: all open orders of the account
+    /// are cancelled unless this is called again within `timeout_ms`.
+    /// A timeout of 0 disarms it.
+    pub async fn cancel_all_after(&self, account_id: u64, timeout_ms: u64) -> Result<Value, ClientError> {
+        let body = serde_json::json!({
+            "accountId": account_id.to_string(),
+            "timeoutMillis": timeout_ms.to_string(),
+        });
+        self.post_private("/api/v1/private/order/cancelAllOrderAfter", &body).await
+    }
+
+    /// Clean-exit hook: engages the kill switch (if a risk manager is set) so no
+    /// new orders go out, then cancels every open order of the bound account and
+    /// checks that none are left.
+    pub async fn shutdown(&self) -> Result<(), ClientError> {
+        let account_id = self.account_id
+            .ok_or_else(|| ClientError::ApiError("shutdown needs a bound account id".to_string()))?;
+        if let Some(risk) = &self.risk_manager {
+            risk.activate_kill_switch();
         }
 
-        let json: Value = res.json().await?;
-        Ok(json)
+        for _ in 0..SHUTDOWN_CANCEL_ATTEMPTS {
+            self.cancel_all_orders(account_id, None).await?;
+            let remaining = self.get_open_orders(account_id).await?;
+            if remaining.is_empty() {
+                if let Some(manager) = &self.order_manager {
+                    manager.reconcile(account_id, &remaining);
+                }
+                return Ok(());
+            }
+        }
+        Err(ClientError::ApiError(format!("open orders remain on account {} after shutdown", account_id)))
     }
 
-    pub async fn get_open_orders(&self, account_id: u64) -> Result<Vec<crate::model::OpenOrder>, ClientError> {
-        let url = format!("{}/api/v1/private/order/getOpenOrders", self.base_url);
+    pub async fn get_open_orders(&self, account_id: u64) -> Result<Vec<OpenOrder>, ClientError> {
         let params = [("accountId", account_id.to_string())];
-        
-        // GET request with query params
-        // Header signature usually requires Path + QueryString? 
-        // Or strictly Request Body?
-        // Docs usually specify. For now assuming timestamp+method+path+query OR just path.
-        // If GET, body is empty.
-        
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis().to_string();
-        let header_signature = "0x0000000000000000000000000000000000000000".to_string(); // Temporary
+        let json = self.get_private("/api/v1/private/order/getOpenOrders", &params).await?;
+        parse_data(json)
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert("X-edgeX-Api-Timestamp", HeaderValue::from_str(&timestamp).unwrap());
-        headers.insert("X-edgeX-Api-Signature", HeaderValue::from_str(&header_signature).unwrap());
+    pub async fn get_fills(&self, account_id: u64) -> Result<Vec<Fill>, ClientError> {
+        let params = [("accountId", account_id.to_string())];
+        let json = self.get_private("/api/v1/private/order/getFills", &params).await?;
+        parse_data(json)
+    }
 
-        let res = self.client.get(&url)
-            .headers(headers)
-            .query(&params)
-            .send()
-            .await?;
+    pub async fn get_positions(&self, account_id: u64) -> Result<Vec<Position>, ClientError> {
+        let params = [("accountId", account_id.to_string())];
+        let json = self.get_private("/api/v1/private/account/getPositionByAccountId", &params).await?;
+        parse_data(json)
+    }
 
-        let status = res.status();
-        if !status.is_success() {
-            let text = res.text().await?;
-            return Err(ClientError::ApiError(format!("Status: {}, Body: {}", status, text)));
+    /// One page of funding settlements, newest first. Times are in milliseconds,
+    /// `start` inclusive and `end` exclusive.
+    pub async fn get_funding_payment_page(
+        &self,
+        account_id: u64,
+        start_time_ms: Option<u64>,
+        end_time_ms: Option<u64>,
+        offset: Option<&str>,
+    ) -> Result<Page<FundingPayment>, ClientError> {
+        let mut params = vec![
+            ("accountId", account_id.to_string()),
+            ("size", HISTORY_PAGE_SIZE.to_string()),
+        ];
+        if let Some(start) = start_time_ms {
+            params.push(("filterStartCreatedTimeInclusive", start.to_string()));
+        }
+        if let Some(end) = end_time_ms {
+            params.push(("filterEndCreatedTimeExclusive", end.to_string()));
         }
+        if let Some(offset) = offset.filter(|o| !o.is_empty()) {
+            params.push(("offsetData", offset.to_string()));
+        }
+        let json = self.get_private("/api/v1/private/funding/getFundingPaymentPage", &params).await?;
+        parse_data(json)
+    }
 
-        // Response structure might be { "code": "...", "data": [...] }
-        // We'll parse Value first then generic.
-        let json: Value = res.json().await?;
-        // Assuming "data" field contains list, or root is list.
-        // Need to check docs for response format.
-        // Usually "data": [ ... ]
-        if let Some(data) = json.get("data") {
-             let orders: Vec<crate::model::OpenOrder> = serde_json::from_value(data.clone()).map_err(|e| ClientError::ApiError(e.to_string()))?;
-             Ok(orders)
-        } else {
-             // Fallback if root is array
-             let orders: Vec<crate::model::OpenOrder> = serde_json::from_value(json).map_err(|e| ClientError::ApiError(e.to_string()))?;
-             Ok(orders)
+    /// Every funding settlement in the time range, following pagination.
+    pub async fn get_funding_payments(
+        &self,
+        account_id: u64,
+        start_time_ms: Option<u64>,
+        end_time_ms: Option<u64>,
+    ) -> Result<Vec<FundingPayment>, ClientError> {
+        let mut payments = Vec::new();
+        let mut offset = String::new();
+        loop {
+            let page = self.get_funding_payment_page(account_id, start_time_ms, end_time_ms, Some(&offset)).await?;
+            payments.extend(page.data_list);
+            if page.next_page_offset_data.is_empty() || page.next_page_offset_data == offset {
+                return Ok(payments);
+            }
+            offset = page.next_page_offset_data;
         }
     }
 
-    pub async fn get_fills(&self, account_id: u64) -> Result<Vec<crate::model::Fill>, ClientError> {
-        let url = format!("{}/api/v1/private/order/getFills", self.base_url);
+    /// Last-price klines with `kline_time` in `[start_time_ms, end_time_ms)`,
+    /// oldest first, following pagination.
+    pub async fn get_klines(
+        &self,
+        contract_id: u64,
+        interval: KlineInterval,
+        start_time_ms: u64,
+        end_time_ms: u64,
+    ) -> Result<Vec<Kline>, ClientError> {
+        let kline_type = serde_json::to_value(interval).map_err(|e| ClientError::ApiError(e.to_string()))?;
+        let mut klines = Vec::new();
+        let mut offset = String::new();
+        loop {
+            let mut params = vec![
+                ("contractId", contract_id.to_string()),
+                ("klineType", kline_type.as_str().unwrap_or_default().to_string()),
+                ("priceType", "LAST_PRICE".to_string()),
+                ("size", HISTORY_PAGE_SIZE.to_string()),
+                ("filterBeginKlineTimeInclusive", start_time_ms.to_string()),
+                ("filterEndKlineTimeExclusive", end_time_ms.to_string()),
+            ];
+            if !offset.is_empty() {
+                params.push(("offsetData", offset.clone()));
+            }
+            let page: Page<Kline> = parse_data(self.get_public("/api/v1/public/quote/getKline", &params).await?)?;
+            klines.extend(page.data_list);
+            if page.next_page_offset_data.is_empty() || page.next_page_offset_data == offset {
+                break;
+            }
+            offset = page.next_page_offset_data;
+        }
+        klines.sort_by_key(|k| k.kline_time);
+        klines.dedup_by_key(|k| k.kline_time);
+        Ok(klines)
+    }
+
+    pub async fn get_metadata(&self) -> Result<MetaData, ClientError> {
+        let json = self.get_public("/api/v1/public/meta/getMetaData", &[]).await?;
+        parse_data(json)
+    }
+
+    /// Per-contract leverage, margin mode and fee settings of the account.
+    pub async fn get_account_settings(&self, account_id: u64) -> Result<AccountSettings, ClientError> {
         let params = [("accountId", account_id.to_string())];
-        
-        // Similar GET auth pattern
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis().to_string();
-        let header_signature = "0x0000000000000000000000000000000000000000".to_string(); // Temporary
+        let json = self.get_private("/api/v1/private/account/getAccountById", &params).await?;
+        parse_data(json)
+    }
+
+    /// Exchange-computed equity and initial/maintenance margin requirements.
+    pub async fn get_account_asset(&self, account_id: u64) -> Result<AccountAsset, ClientError> {
+        let params = [("accountId", account_id.to_string())];
+        let json = self.get_private("/api/v1/private/account/getAccountAsset", &params).await?;
+        parse_data(json)
+    }
+
+    /// Sets the leverage for `contract` after checking it against the contract's
+    /// risk tier for a position of `position_value`.
+    pub async fn update_leverage(
+        &self,
+        account_id: u64,
+        contract: &ContractMeta,
+        leverage: Decimal,
+        position_value: Decimal,
+    ) -> Result<Value, ClientError> {
+        contract.check_leverage(leverage, position_value)?;
+        let body = serde_json::json!({
+            "accountId": account_id.to_string(),
+            "contractId": contract.contract_id.to_string(),
+            "leverage": leverage.normalize().to_string(),
+        });
+        self.post_private("/api/v1/private/account/updateLeverageSetting", &body).await
+    }
+
+    pub async fn update_margin_mode(&self, account_id: u64, contract_id: u64, margin_mode: MarginMode) -> Result<Value, ClientError> {
+        let body = serde_json::json!({
+            "accountId": account_id.to_string(),
+            "contractId": contract_id.to_string(),
+            "marginMode": margin_mode,
+        });
+        self.post_private("/api/v1/private/account/updateMarginMode", &body).await
+    }
+
+    /// Builds the `X-edgeX-Api-*` auth headers.
+    /// Sign content is `timestamp + METHOD + path + payload`, where payload is the
+    /// JSON body for POST and the key-sorted query string for GET.
+    pub(crate) async fn auth_headers(&self, method: &str, path: &str, payload: &str) -> Result<HeaderMap, ClientError> {
+        let timestamp = self.time_sync.now_millis().to_string();
+        let sign_payload = format!("{}{}{}{}", timestamp, method, path, payload);
+        let header_signature = self.sign_l2_action(&message_hash(&sign_payload)).await?;
 
         let mut headers = HeaderMap::new();
         headers.insert("X-edgeX-Api-Timestamp", HeaderValue::from_str(&timestamp).unwrap());
         headers.insert("X-edgeX-Api-Signature", HeaderValue::from_str(&header_signature).unwrap());
+        Ok(headers)
+    }
+
+    pub(crate) async fn post_private<T: Serialize + ?Sized>(&self, path: &str, req: &T) -> Result<Value, ClientError> {
+        let url = format!("{}{}", self.base_url, path);
+        let body = serde_json::to_string(req).map_err(|e| ClientError::ApiError(e.to_string()))?;
+
+        let mut headers = self.auth_headers("POST", path, &body).await?;
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let res = self.client.post(&url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .await?;
+        read_json(res).await
+    }
+
+    pub(crate) async fn get_private(&self, path: &str, params: &[(&str, String)]) -> Result<Value, ClientError> {
+        let url = format!("{}{}", self.base_url, path);
+        let headers = self.auth_headers("GET", path, &sorted_query(params)).await?;
 
         let res = self.client.get(&url)
             .headers(headers)
-            .query(&params)
+            .query(params)
             .send()
             .await?;
+        read_json(res).await
+    }
 
-        let status = res.status();
-        if !status.is_success() {
-            let text = res.text().await?;
-            return Err(ClientError::ApiError(format!("Status: {}, Body: {}", status, text)));
-        }
+    pub(crate) async fn get_public(&self, path: &str, params: &[(&str, String)]) -> Result<Value, ClientError> {
+        let url = format!("{}{}", self.base_url, path);
+        let res = self.client.get(&url).query(params).send().await?;
+        read_json(res).await
+    }
+}
 
-        let json: Value = res.json().await?;
-        if let Some(data) = json.get("data") {
-             let fills: Vec<crate::model::Fill> = serde_json::from_value(data.clone()).map_err(|e| ClientError::ApiError(e.to_string()))?;
-             Ok(fills)
-        } else {
-             let fills: Vec<crate::model::Fill> = serde_json::from_value(json).map_err(|e| ClientError::ApiError(e.to_string()))?;
-             Ok(fills)
-        }
+/// Query string with keys in lexicographic order, as used in the header sign content.
+pub(crate) fn sorted_query(params: &[(&str, String)]) -> String {
+    let mut params: Vec<_> = params.iter().collect();
+    params.sort_by(|a, b| a.0.cmp(b.0));
+    params.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+pub(crate) async fn read_json(res: reqwest::Response) -> Result<Value, ClientError> {
+    let status = res.status();
+    if !status.is_success() {
+        let text = res.text().await?;
+        return Err(ClientError::ApiError(format!("Status: {}, Body: {}", status, text)));
     }
+    let json: Value = res.json().await?;
+    Ok(json)
+}
+
+/// Responses are usually `{ "code": "...", "data": ... }`; fall back to the root
+/// when there is no `data` envelope.
+pub(crate) fn parse_data<T: DeserializeOwned>(json: Value) -> Result<T, ClientError> {
+    let data = match json {
+        Value::Object(mut map) if map.contains_key("data") => map.remove("data").unwrap(),
+        other => other,
+    };
+    serde_json::from_value(data).map_err(|e| ClientError::ApiError(e.to_string()))
 }
@@ -0,0 +1,234 @@
+use crate::client::{ClientError, EdgeXClient};
+use crate::trading::check_response;
+use futures_util::{Stream, StreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How the switch decides the process is gone.
+#[derive(Debug, Clone, Copy)]
+pub enum DeadMansSwitchMode {
+    /// Re-arms the exchange's cancel-all-after timer every `interval`. If this
+    /// process dies, the exchange cancels everything once `timeout` passes.
+    ExchangeHeartbeat { timeout: Duration, interval: Duration },
+    /// Client-side watchdog: if `heartbeat` isn't called for `timeout` (e.g.
+    /// because the websocket feed went quiet), engage the client's risk kill
+    /// switch, if any, and mass-cancel over REST.
+    Watchdog { timeout: Duration },
+}
+
+/// What the switch last saw; see `DeadMansSwitch::subscribe`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeadMansSwitchStatus {
+    /// The watchdog fired since the last heartbeat.
+    pub triggered: bool,
+    /// Why the last exchange timer refresh or watchdog cancel failed; cleared
+    /// by the next one that succeeds.
+    pub last_error: Option<String>,
+}
+
+struct Liveness {
+    last_heartbeat: Mutex<Instant>,
+    status: watch::Sender<DeadMansSwitchStatus>,
+}
+
+impl Liveness {
+    fn beat(&self) {
+        *self.last_heartbeat.lock().unwrap() = Instant::now();
+        self.status.send_if_modified(|status| std::mem::replace(&mut status.triggered, false));
+    }
+
+    fn record(&self, result: Result<(), ClientError>) {
+        let error = result.err().map(|e| e.to_string());
+        self.status.send_if_modified(|status| {
+            let changed = status.last_error != error;
+            status.last_error = error;
+            changed
+        });
+    }
+}
+
+/// Cancels an account's orders when liveness is lost. Dropping the switch
+/// stops the background task without disarming; call `stop` for a clean exit.
+pub struct DeadMansSwitch {
+    client: Arc<EdgeXClient>,
+    account_id: u64,
+    mode: DeadMansSwitchMode,
+    liveness: Arc<Liveness>,
+    task: JoinHandle<()>,
+}
+
+impl DeadMansSwitch {
+    /// Starts the switch. In `ExchangeHeartbeat` mode the exchange timer is
+    /// armed before this returns, and an error means it is not armed.
+    pub async fn start(client: Arc<EdgeXClient>, account_id: u64, mode: DeadMansSwitchMode) -> Result<Self, ClientError> {
+        let (status, _) = watch::channel(DeadMansSwitchStatus::default());
+        let liveness = Arc::new(Liveness { last_heartbeat: Mutex::new(Instant::now()), status });
+        let task = match mode {
+            DeadMansSwitchMode::ExchangeHeartbeat { timeout, interval } => {
+                check_response(&client.cancel_all_after(account_id, timeout.as_millis() as u64).await?)?;
+                tokio::spawn(run_exchange_heartbeat(client.clone(), account_id, timeout, interval, liveness.clone()))
+            }
+            DeadMansSwitchMode::Watchdog { timeout } => {
+                tokio::spawn(run_watchdog(client.clone(), account_id, timeout, liveness.clone()))
+            }
+        };
+        Ok(Self { client, account_id, mode, liveness, task })
+    }
+
+    /// Liveness signal for `Watchdog` mode. No-op for `ExchangeHeartbeat`.
+    /// A kill switch engaged by the watchdog stays on until deactivated.
+    pub fn heartbeat(&self) {
+        self.liveness.beat();
+    }
+
+    /// Passes `stream` through, counting every item as a heartbeat.
+    pub fn watch_stream<S: Stream>(&self, stream: S) -> impl Stream<Item = S::Item> + use<S> {
+        let liveness = self.liveness.clone();
+        stream.inspect(move |_| liveness.beat())
+    }
+
+    /// Whether the watchdog has fired since the last heartbeat.
+    pub fn is_triggered(&self) -> bool {
+        self.liveness.status.borrow().triggered
+    }
+
+    pub fn status(&self) -> DeadMansSwitchStatus {
+        self.liveness.status.borrow().clone()
+    }
+
+    /// Receives every status change, including failed timer refreshes.
+    pub fn subscribe(&self) -> watch::Receiver<DeadMansSwitchStatus> {
+        self.liveness.status.subscribe()
+    }
+
+    /// Stops the switch and disarms the exchange-side timer, leaving orders in place.
+    pub async fn stop(self) -> Result<(), ClientError> {
+        self.task.abort();
+        if let DeadMansSwitchMode::ExchangeHeartbeat { .. } = self.mode {
+            check_response(&self.client.cancel_all_after(self.account_id, 0).await?)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DeadMansSwitch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run_exchange_heartbeat(
+    client: Arc<EdgeXClient>,
+    account_id: u64,
+    timeout: Duration,
+    interval: Duration,
+    liveness: Arc<Liveness>,
+) {
+    // `start` armed the timer already.
+    let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+    loop {
+        ticker.tick().await;
+        // A failed refresh is reported and retried on the next tick; the
+        // exchange timer keeps running, so persistent failures end in a cancel-all.
+        let result = client.cancel_all_after(account_id, timeout.as_millis() as u64).await;
+        liveness.record(result.and_then(|res| check_response(&res)));
+    }
+}
+
+async fn run_watchdog(client: Arc<EdgeXClient>, account_id: u64, timeout: Duration, liveness: Arc<Liveness>) {
+    let mut ticker = tokio::time::interval((timeout / 4).max(Duration::from_millis(10)));
+    loop {
+        ticker.tick().await;
+        let silent = liveness.last_heartbeat.lock().unwrap().elapsed();
+        if silent < timeout || liveness.status.borrow().triggered {
+            continue;
+        }
+        // Nothing new goes out while liveness is lost, as in `EdgeXClient::shutdown`.
+        if let Some(risk) = client.risk_manager() {
+            risk.activate_kill_switch();
+        }
+        // Only latch once the cancel went through, so failures are retried.
+        let result = client.cancel_all_orders(account_id, None).await.and_then(|res| check_response(&res));
+        let cancelled = result.is_ok();
+        liveness.record(result);
+        if cancelled {
+            liveness.status.send_modify(|status| status.triggered = true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockExchange;
+    use crate::model::{CreateOrderRequest, MetaData, OrderSide};
+    use crate::risk::{RiskConfig, RiskManager};
+    use crate::signature::SignatureManager;
+    use rust_decimal::Decimal;
+
+    const KEY: &str = "0x1";
+    const ACCOUNT_ID: u64 = 1;
+
+    async fn place(client: &EdgeXClient, meta: &MetaData, price: i64) {
+        let req = CreateOrderRequest::limit(meta, ACCOUNT_ID, 10000001, OrderSide::Buy, Decimal::from(price), Decimal::new(1, 2)).unwrap();
+        check_response(&client.place_order(req, meta).await.unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_orders_cancelled_when_liveness_is_lost() {
+        let mock = MockExchange::start().await.unwrap();
+        mock.register_account(ACCOUNT_ID, SignatureManager::new(KEY).unwrap().public_key());
+        let meta = mock.metadata().clone();
+        let risk = Arc::new(RiskManager::new(RiskConfig::default()));
+        let client = Arc::new(EdgeXClient::new(KEY, Some(mock.base_url())).unwrap()
+            .with_account_id(ACCOUNT_ID)
+            .with_risk_manager(risk.clone()));
+        // Read the mock's state directly: requests made while the clock is
+        // paused let it jump ahead to the next timer.
+        let open = || mock.orders(ACCOUNT_ID).iter().filter(|o| o.status == "OPEN").count();
+        let heartbeat = DeadMansSwitchMode::ExchangeHeartbeat { timeout: Duration::from_secs(60), interval: Duration::from_secs(10) };
+
+        // Arming fails up front for an account the exchange refuses.
+        assert!(DeadMansSwitch::start(client.clone(), 999, heartbeat).await.is_err());
+
+        // Exchange heartbeat: the process "dies" (the switch is dropped without
+        // `stop`), so the exchange timer runs out and cancels.
+        place(&client, &meta, 49_000).await;
+        let switch = DeadMansSwitch::start(client.clone(), ACCOUNT_ID, heartbeat).await.unwrap();
+        tokio::time::pause();
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert_eq!(open(), 1);
+        drop(switch);
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        assert_eq!(open(), 0);
+
+        // Watchdog: heartbeats keep orders alive; silence cancels them and
+        // blocks new orders.
+        tokio::time::resume();
+        place(&client, &meta, 49_000).await;
+        let switch = DeadMansSwitch::start(client.clone(), ACCOUNT_ID, DeadMansSwitchMode::Watchdog { timeout: Duration::from_secs(10) })
+            .await
+            .unwrap();
+        tokio::time::pause();
+        for _ in 0..4 {
+            tokio::time::sleep(Duration::from_secs(4)).await;
+            switch.heartbeat();
+        }
+        assert_eq!(open(), 1);
+        switch.subscribe().wait_for(|status| status.triggered).await.unwrap();
+        assert_eq!((open(), switch.status().last_error), (0, None));
+        assert!(risk.is_kill_switch_active());
+        tokio::time::resume();
+        switch.stop().await.unwrap();
+
+        // A clean shutdown leaves nothing open.
+        risk.deactivate_kill_switch();
+        place(&client, &meta, 49_000).await;
+        place(&client, &meta, 48_000).await;
+        client.shutdown().await.unwrap();
+        assert_eq!(open(), 0);
+    }
+}
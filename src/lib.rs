@@ -1,6 +1,31 @@
+pub mod algo;
+pub mod backtest;
+pub mod candle;
 pub mod client;
+pub mod dead_man;
+pub mod margin;
+pub mod market_order;
+#[cfg(any(test, feature = "mock-server"))]
+pub mod mock;
 pub mod model;
+pub mod nonce;
+pub mod onboarding;
+pub mod order_book;
+pub mod order_manager;
+pub mod paper;
+pub mod position;
+pub mod quoter;
+pub mod recorder;
+pub mod report;
+pub mod risk;
+pub mod secret;
+pub mod self_trade;
 pub mod signature;
+pub mod signer;
+pub mod stark_order;
+pub mod strategy;
+pub mod time_sync;
+pub mod trading;
 pub mod utils;
 pub mod websocket;
 
@@ -0,0 +1,420 @@
+use crate::model::{AccountAsset, ContractMeta, MetaData, OrderSide, Position, RiskTier};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Doubling steps before `max_order_size` gives up looking for an upper bound.
+const MAX_SIZE_SEARCH_STEPS: u32 = 64;
+/// Tier re-evaluations when solving for a liquidation price.
+const LIQUIDATION_TIER_ITERATIONS: usize = 8;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MarginError {
+    #[error("Unknown contract: {0}")]
+    UnknownContract(u64),
+    #[error("Position value {position_value} on contract {contract_id} is above every risk tier")]
+    NoRiskTier { contract_id: u64, position_value: Decimal },
+    #[error("No mark price for contract {0}")]
+    MissingMarkPrice(u64),
+    #[error("{field} is not a valid decimal: {value:?}")]
+    InvalidDecimal { field: &'static str, value: String },
+    #[error("{field} must be positive, got {value}")]
+    NotPositive { field: &'static str, value: Decimal },
+    #[error("Contract {0} has no risk tiers")]
+    NoRiskTiers(u64),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MarginPosition {
+    /// Signed: positive for long, negative for short.
+    pub size: Decimal,
+    pub mark_price: Decimal,
+}
+
+/// Account state as StarkEx sees it: a collateral (cash) balance that already
+/// reflects what was paid or received for open positions, plus the positions.
+/// Equity is therefore `collateral + Σ size * mark`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarginAccount {
+    pub collateral: Decimal,
+    pub positions: BTreeMap<u64, MarginPosition>,
+}
+
+impl MarginAccount {
+    pub fn new(collateral: Decimal) -> Self {
+        Self { collateral, positions: BTreeMap::new() }
+    }
+
+    pub fn with_position(mut self, contract_id: u64, size: Decimal, mark_price: Decimal) -> Self {
+        self.positions.insert(contract_id, MarginPosition { size, mark_price });
+        self
+    }
+
+    /// Builds the account from exchange positions and the collateral `amount`
+    /// of `getAccountAsset`. Every non-flat position needs a mark price.
+    pub fn from_positions(
+        collateral: Decimal,
+        positions: &[Position],
+        mark_prices: &HashMap<u64, Decimal>,
+    ) -> Result<Self, MarginError> {
+        let mut account = Self::new(collateral);
+        for p in positions {
+            let size = parse_decimal("openSize", &p.open_size)?;
+            if size.is_zero() {
+                continue;
+            }
+            let mark_price = *mark_prices.get(&p.contract_id).ok_or(MarginError::MissingMarkPrice(p.contract_id))?;
+            account.positions.insert(p.contract_id, MarginPosition { size, mark_price });
+        }
+        Ok(account)
+    }
+
+    pub fn equity(&self) -> Decimal {
+        self.collateral + self.positions.values().map(|p| p.size * p.mark_price).sum::<Decimal>()
+    }
+
+    /// The account as it would be after buying or selling `size` at `price`,
+    /// paying `fee` out of collateral.
+    pub fn with_fill(&self, contract_id: u64, side: OrderSide, size: Decimal, price: Decimal, fee: Decimal) -> Self {
+        let signed_size = match side {
+            OrderSide::Buy => size,
+            OrderSide::Sell => -size,
+        };
+        let mut account = self.clone();
+        account.collateral -= signed_size * price + fee;
+        let position = account.positions.entry(contract_id).or_insert(MarginPosition {
+            size: Decimal::ZERO,
+            mark_price: price,
+        });
+        position.size += signed_size;
+        account
+    }
+}
+
+/// Margin figures for one position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionMargin {
+    pub contract_id: u64,
+    pub size: Decimal,
+    pub mark_price: Decimal,
+    /// `|size| * mark_price`.
+    pub value: Decimal,
+    pub leverage: Decimal,
+    pub tier: u64,
+    pub initial_margin: Decimal,
+    pub maintenance_margin: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarginSummary {
+    pub equity: Decimal,
+    pub initial_margin: Decimal,
+    pub maintenance_margin: Decimal,
+    /// `maintenance_margin / equity`; the account is liquidatable at 1 or above.
+    /// `None` when equity is not positive.
+    pub margin_ratio: Option<Decimal>,
+    /// Equity not tied up as initial margin; negative means no new risk can be added.
+    pub free_collateral: Decimal,
+    pub positions: Vec<PositionMargin>,
+}
+
+/// A field where the local calculation and the exchange disagree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarginDiscrepancy {
+    pub field: String,
+    pub local: Decimal,
+    pub exchange: Decimal,
+}
+
+/// Cross-margin calculator over the contracts' risk tiers.
+///
+/// Pure and synchronous: feed it a `MarginAccount` and ask what-if questions
+/// before placing orders. Initial margin uses the account's leverage for the
+/// contract (see `with_leverage`), capped at the tier maximum.
+pub struct MarginEngine {
+    contracts: HashMap<u64, ContractMeta>,
+    leverage: HashMap<u64, Decimal>,
+}
+
+impl MarginEngine {
+    pub fn new(meta: &MetaData) -> Self {
+        Self {
+            contracts: meta.contract_list.iter().map(|c| (c.contract_id, c.clone())).collect(),
+            leverage: HashMap::new(),
+        }
+    }
+
+    /// Leverage chosen for `contract_id`; defaults to the tier maximum.
+    pub fn with_leverage(mut self, contract_id: u64, leverage: Decimal) -> Self {
+        self.leverage.insert(contract_id, leverage);
+        self
+    }
+
+    pub fn position_margin(&self, contract_id: u64, position: &MarginPosition) -> Result<PositionMargin, MarginError> {
+        let value = position.size.abs() * position.mark_price;
+        let tier = self.tier(contract_id, value)?;
+        let leverage = match self.leverage.get(&contract_id) {
+            Some(leverage) if *leverage > Decimal::ZERO => (*leverage).min(tier.max_leverage),
+            _ => tier.max_leverage,
+        };
+        let initial_margin = if leverage.is_zero() { value } else { value / leverage };
+        Ok(PositionMargin {
+            contract_id,
+            size: position.size,
+            mark_price: position.mark_price,
+            value,
+            leverage,
+            tier: tier.tier,
+            initial_margin,
+            maintenance_margin: value * tier.maintenance_margin_rate,
+        })
+    }
+
+    pub fn summary(&self, account: &MarginAccount) -> Result<MarginSummary, MarginError> {
+        let positions = account.positions.iter()
+            .filter(|(_, p)| !p.size.is_zero())
+            .map(|(id, p)| self.position_margin(*id, p))
+            .collect::<Result<Vec<_>, _>>()?;
+        let equity = account.equity();
+        let initial_margin: Decimal = positions.iter().map(|p| p.initial_margin).sum();
+        let maintenance_margin: Decimal = positions.iter().map(|p| p.maintenance_margin).sum();
+        Ok(MarginSummary {
+            equity,
+            initial_margin,
+            maintenance_margin,
+            margin_ratio: (equity > Decimal::ZERO).then(|| maintenance_margin / equity),
+            free_collateral: equity - initial_margin,
+            positions,
+        })
+    }
+
+    /// Mark price of `contract_id` at which equity falls to maintenance margin,
+    /// holding every other position's mark fixed.
+    ///
+    /// `None` when there is no position, or for a long that stays solvent all the
+    /// way down to zero. `Some(0)` for a short that is already underwater.
+    pub fn liquidation_price(&self, account: &MarginAccount, contract_id: u64) -> Result<Option<Decimal>, MarginError> {
+        let Some(position) = account.positions.get(&contract_id).filter(|p| !p.size.is_zero()) else {
+            return Ok(None);
+        };
+        let mut other_equity = account.collateral;
+        let mut other_maintenance = Decimal::ZERO;
+        for (id, p) in account.positions.iter().filter(|(id, p)| **id != contract_id && !p.size.is_zero()) {
+            other_equity += p.size * p.mark_price;
+            other_maintenance += self.position_margin(*id, p)?.maintenance_margin;
+        }
+
+        // Solve `other_equity + s * p = other_maintenance + |s| * p * mmr` for p,
+        // then re-check the tier at that price since a move can change it.
+        let size = position.size;
+        let mut tier = self.tier(contract_id, size.abs() * position.mark_price)?;
+        let mut price = Decimal::ZERO;
+        for _ in 0..LIQUIDATION_TIER_ITERATIONS {
+            let denominator = size - size.abs() * tier.maintenance_margin_rate;
+            if denominator.is_zero() {
+                return Ok(None);
+            }
+            price = (other_maintenance - other_equity) / denominator;
+            if price <= Decimal::ZERO {
+                return Ok(if size.is_sign_negative() { Some(Decimal::ZERO) } else { None });
+            }
+            match self.tier(contract_id, size.abs() * price) {
+                Ok(next) if next.tier != tier.tier => tier = next,
+                _ => break,
+            }
+        }
+        Ok(Some(price))
+    }
+
+    /// Largest size (a multiple of the contract's step size) that can be bought
+    /// or sold at `price` while keeping free collateral non-negative, taker fee
+    /// included. Zero means not even one step fits; invalid contract metadata
+    /// or a non-positive price is an error.
+    pub fn max_order_size(
+        &self,
+        account: &MarginAccount,
+        contract_id: u64,
+        side: OrderSide,
+        price: Decimal,
+    ) -> Result<Decimal, MarginError> {
+        let contract = self.contracts.get(&contract_id).ok_or(MarginError::UnknownContract(contract_id))?;
+        let step = parse_decimal("stepSize", &contract.step_size)?;
+        let fee_rate = parse_decimal("defaultTakerFeeRate", &contract.default_taker_fee_rate)?;
+        if step <= Decimal::ZERO {
+            return Err(MarginError::NotPositive { field: "stepSize", value: step });
+        }
+        if price <= Decimal::ZERO {
+            return Err(MarginError::NotPositive { field: "price", value: price });
+        }
+        if contract.risk_tier_list.is_empty() {
+            return Err(MarginError::NoRiskTiers(contract_id));
+        }
+
+        let fits = |steps: u64| -> Result<bool, MarginError> {
+            let size = step * Decimal::from(steps);
+            let after = account.with_fill(contract_id, side, size, price, size * price * fee_rate);
+            match self.summary(&after) {
+                Ok(summary) => Ok(summary.free_collateral >= Decimal::ZERO),
+                // Past the last tier the position can't be held at all.
+                Err(MarginError::NoRiskTier { contract_id: id, .. }) if id == contract_id => Ok(false),
+                Err(e) => Err(e),
+            }
+        };
+        if !fits(1)? {
+            return Ok(Decimal::ZERO);
+        }
+
+        // Exponential search for a size that doesn't fit, then bisect.
+        let mut low = 1u64;
+        let mut high = 2u64;
+        for _ in 0..MAX_SIZE_SEARCH_STEPS {
+            if !fits(high)? {
+                break;
+            }
+            low = high;
+            high = high.saturating_mul(2);
+        }
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if fits(mid)? {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(step * Decimal::from(low))
+    }
+
+    /// Compares local figures with `getAccountAsset` and returns every field
+    /// whose relative difference exceeds `tolerance`.
+    pub fn cross_check(
+        &self,
+        account: &MarginAccount,
+        exchange: &AccountAsset,
+        tolerance: Decimal,
+    ) -> Result<Vec<MarginDiscrepancy>, MarginError> {
+        let summary = self.summary(account)?;
+        let mut discrepancies = Vec::new();
+        let mut compare = |field: String, local: Decimal, value: &str| -> Result<(), MarginError> {
+            if value.is_empty() {
+                return Ok(());
+            }
+            let exchange = parse_decimal("accountAsset", value)?;
+            if (local - exchange).abs() > exchange.abs() * tolerance {
+                discrepancies.push(MarginDiscrepancy { field, local, exchange });
+            }
+            Ok(())
+        };
+
+        if let Some(collateral) = exchange.collateral_list.first() {
+            compare("totalEquity".to_string(), summary.equity, &collateral.total_equity)?;
+            compare("initialMarginRequirement".to_string(), summary.initial_margin, &collateral.initial_margin_requirement)?;
+            compare(
+                "maintenanceMarginRequirement".to_string(),
+                summary.maintenance_margin,
+                &collateral.maintenance_margin_requirement,
+            )?;
+        }
+        for remote in &exchange.position_list {
+            let Some(local) = summary.positions.iter().find(|p| p.contract_id == remote.contract_id) else {
+                continue;
+            };
+            let id = remote.contract_id;
+            compare(format!("{}.initialMarginRequirement", id), local.initial_margin, &remote.initial_margin_requirement)?;
+            compare(
+                format!("{}.maintenanceMarginRequirement", id),
+                local.maintenance_margin,
+                &remote.maintenance_margin_requirement,
+            )?;
+            if let Some(liquidation) = self.liquidation_price(account, id)? {
+                compare(format!("{}.liquidatePrice", id), liquidation, &remote.liquidate_price)?;
+            }
+        }
+        Ok(discrepancies)
+    }
+
+    fn tier(&self, contract_id: u64, position_value: Decimal) -> Result<&RiskTier, MarginError> {
+        let contract = self.contracts.get(&contract_id).ok_or(MarginError::UnknownContract(contract_id))?;
+        contract.risk_tier(position_value).ok_or(MarginError::NoRiskTier { contract_id, position_value })
+    }
+}
+
+fn parse_decimal(field: &'static str, value: &str) -> Result<Decimal, MarginError> {
+    Decimal::from_str(value.trim()).map_err(|_| MarginError::InvalidDecimal { field, value: value.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{CollateralMeta, GlobalMeta};
+
+    fn meta() -> MetaData {
+        let tier = |tier, bound, leverage, mmr| RiskTier {
+            tier,
+            position_value_upper_bound: Decimal::from(bound),
+            max_leverage: Decimal::from(leverage),
+            maintenance_margin_rate: Decimal::new(mmr, 3),
+        };
+        MetaData {
+            global: GlobalMeta {
+                stark_ex_collateral_coin: CollateralMeta {
+                    coin_id: 1000,
+                    coin_name: "USDT".to_string(),
+                    stark_ex_asset_id: "0x2".to_string(),
+                    stark_ex_resolution: "0xf4240".to_string(),
+                },
+            },
+            contract_list: vec![ContractMeta {
+                contract_id: 1,
+                contract_name: "BTCUSDT".to_string(),
+                tick_size: "0.1".to_string(),
+                step_size: "0.001".to_string(),
+                stark_ex_synthetic_asset_id: "0x1".to_string(),
+                stark_ex_resolution: "0x2540be400".to_string(),
+                default_maker_fee_rate: "0.0002".to_string(),
+                default_taker_fee_rate: "0".to_string(),
+                risk_tier_list: vec![tier(1, 100_000, 20, 5), tier(2, 1_000_000, 10, 10)],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_margin_summary_and_liquidation() {
+        let engine = MarginEngine::new(&meta());
+        // 1 BTC long bought at 50k with 10k of own funds.
+        let account = MarginAccount::new(Decimal::from(-40_000)).with_position(1, Decimal::ONE, Decimal::from(50_000));
+
+        let summary = engine.summary(&account).unwrap();
+        assert_eq!(summary.equity, Decimal::from(10_000));
+        assert_eq!(summary.initial_margin, Decimal::from(2_500));
+        assert_eq!(summary.maintenance_margin, Decimal::from(250));
+        assert_eq!(summary.margin_ratio, Some(Decimal::new(25, 3)));
+        assert_eq!(summary.free_collateral, Decimal::from(7_500));
+
+        // -40000 + p = 0.005 p  =>  p = 40000 / 0.995
+        let liquidation = engine.liquidation_price(&account, 1).unwrap().unwrap();
+        assert_eq!(liquidation.round_dp(2), Decimal::new(4020101, 2));
+
+        // Free collateral of 7.5k at 20x supports 150k of notional in tier 1,
+        // but that crosses into tier 2 (10x), which caps the position at 100k.
+        let max = engine.max_order_size(&account, 1, OrderSide::Buy, Decimal::from(50_000)).unwrap();
+        assert_eq!(max, Decimal::ONE);
+        let after = account.with_fill(1, OrderSide::Buy, max, Decimal::from(50_000), Decimal::ZERO);
+        assert!(engine.summary(&after).unwrap().free_collateral >= Decimal::ZERO);
+        assert_eq!(
+            engine.max_order_size(&account, 1, OrderSide::Buy, Decimal::ZERO),
+            Err(MarginError::NotPositive { field: "price", value: Decimal::ZERO })
+        );
+        let mut untiered = meta();
+        untiered.contract_list[0].risk_tier_list.clear();
+        assert_eq!(
+            MarginEngine::new(&untiered).max_order_size(&MarginAccount::new(Decimal::from(10_000)), 1, OrderSide::Buy, Decimal::ONE),
+            Err(MarginError::NoRiskTiers(1))
+        );
+
+        let short = MarginAccount::new(Decimal::from(60_000)).with_position(1, Decimal::NEGATIVE_ONE, Decimal::from(50_000));
+        let liquidation = engine.liquidation_price(&short, 1).unwrap().unwrap();
+        assert_eq!(liquidation.round_dp(2), Decimal::new(5970149, 2));
+    }
+}
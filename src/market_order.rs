@@ -0,0 +1,300 @@
+//! Market orders priced from the local order book.
+//!
+//! A market order still has to sign a collateral amount and fee cap, so the
+//! helper picks a worst acceptable price a bounded distance from the touch,
+//! signs against that and sends the order IOC. The report compares what was
+//! achieved with the touch at submission time.
+
+use crate::client::ClientError;
+use crate::model::{CreateOrderRequest, Fill, MetaData, OrderSide, OrderStatus};
+use crate::order_book::OrderBook;
+use crate::order_manager::new_client_order_id;
+use crate::stark_order::OrderHashError;
+use crate::trading::{check_response, response_order_id, TradingApi};
+use crate::websocket::AccountEvent;
+use futures_util::StreamExt;
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MarketOrderError {
+    #[error("Invalid market order: {0}")]
+    InvalidParameters(String),
+    #[error("No liquidity on the opposite side of the book")]
+    NoLiquidity,
+    #[error("Only {available} available within the slippage limit, {requested} requested")]
+    InsufficientLiquidity { requested: Decimal, available: Decimal },
+    #[error("Order hash error: {0}")]
+    OrderHash(#[from] OrderHashError),
+    #[error("Client error: {0}")]
+    Client(#[from] ClientError),
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketOrderConfig {
+    /// Furthest the worst price may be from the touch, as a fraction (0.005 = 50 bps).
+    pub max_slippage: Decimal,
+    /// Refuse to send when the book can't fill the whole size within the limit.
+    /// Otherwise the order goes out and the rest is cancelled by IOC.
+    pub require_full_fill: bool,
+    /// How long to wait for the order's fills before reporting.
+    pub fill_timeout: Duration,
+}
+
+impl Default for MarketOrderConfig {
+    fn default() -> Self {
+        Self { max_slippage: Decimal::new(5, 3), require_full_fill: false, fill_timeout: Duration::from_secs(5) }
+    }
+}
+
+impl MarketOrderConfig {
+    pub fn with_max_slippage(mut self, max_slippage: Decimal) -> Self {
+        self.max_slippage = max_slippage;
+        self
+    }
+
+    pub fn with_require_full_fill(mut self, require_full_fill: bool) -> Self {
+        self.require_full_fill = require_full_fill;
+        self
+    }
+
+    pub fn with_fill_timeout(mut self, fill_timeout: Duration) -> Self {
+        self.fill_timeout = fill_timeout;
+        self
+    }
+}
+
+/// A priced market order, ready to send.
+#[derive(Debug, Clone)]
+pub struct MarketOrderPlan {
+    /// Best opposite price when the plan was made.
+    pub reference_price: Decimal,
+    /// Signed price: the least favourable one the order may trade at.
+    pub worst_price: Decimal,
+    /// Average price of walking the current book for the size, up to `worst_price`.
+    pub expected_avg_price: Option<Decimal>,
+    /// Size the book shows within `worst_price`.
+    pub available: Decimal,
+    pub request: CreateOrderRequest,
+}
+
+/// What a market order achieved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketOrderReport {
+    pub order_id: Option<u64>,
+    pub requested_size: Decimal,
+    pub filled_size: Decimal,
+    pub avg_price: Option<Decimal>,
+    pub reference_price: Decimal,
+    pub worst_price: Decimal,
+    /// How much worse than `reference_price` the average fill was, as a
+    /// fraction; negative means price improvement.
+    pub slippage: Option<Decimal>,
+    pub fees: Decimal,
+}
+
+/// Prices a market order for `size` against `book`.
+///
+/// The worst price is the touch moved by `max_slippage` against us, rounded
+/// to the tick towards the touch so it never exceeds the limit.
+pub fn plan_market_order(
+    book: &OrderBook,
+    meta: &MetaData,
+    account_id: u64,
+    side: OrderSide,
+    size: Decimal,
+    config: &MarketOrderConfig,
+) -> Result<MarketOrderPlan, MarketOrderError> {
+    let contract = meta.contract(book.contract_id).ok_or(OrderHashError::UnknownContract(book.contract_id))?;
+    let tick = parse_decimal("tickSize", &contract.tick_size)?;
+    let step = parse_decimal("stepSize", &contract.step_size)?;
+    if size <= Decimal::ZERO || !(size % step).is_zero() {
+        return Err(MarketOrderError::InvalidParameters(format!("size {} is not a positive multiple of {}", size, step)));
+    }
+    if config.max_slippage.is_sign_negative() || config.max_slippage >= Decimal::ONE {
+        return Err(MarketOrderError::InvalidParameters(format!("max slippage {} is not in [0, 1)", config.max_slippage)));
+    }
+
+    let touch = match side {
+        OrderSide::Buy => book.best_ask(),
+        OrderSide::Sell => book.best_bid(),
+    };
+    let (reference_price, _) = touch.ok_or(MarketOrderError::NoLiquidity)?;
+    let worst_price = match side {
+        OrderSide::Buy => (reference_price * (Decimal::ONE + config.max_slippage) / tick).floor() * tick,
+        OrderSide::Sell => (reference_price * (Decimal::ONE - config.max_slippage) / tick).ceil() * tick,
+    };
+
+    let mut remaining = size;
+    let mut notional = Decimal::ZERO;
+    for (price, level_size) in book.opposite_levels(side, Some(worst_price)) {
+        let take = remaining.min(level_size);
+        notional += take * price;
+        remaining -= take;
+        if remaining.is_zero() {
+            break;
+        }
+    }
+    let available = size - remaining;
+    if config.require_full_fill && !remaining.is_zero() {
+        return Err(MarketOrderError::InsufficientLiquidity { requested: size, available });
+    }
+
+    let request = CreateOrderRequest::market(meta, account_id, book.contract_id, side, worst_price, size)?;
+    Ok(MarketOrderPlan {
+        reference_price,
+        worst_price,
+        expected_avg_price: (!available.is_zero()).then(|| notional / available),
+        available,
+        request,
+    })
+}
+
+/// Plans and sends a market order, then waits for its fills (up to
+/// `fill_timeout`) to report the outcome.
+pub async fn execute_market_order(
+    api: &dyn TradingApi,
+    book: &OrderBook,
+    meta: &MetaData,
+    account_id: u64,
+    side: OrderSide,
+    size: Decimal,
+    config: &MarketOrderConfig,
+) -> Result<MarketOrderReport, MarketOrderError> {
+    let mut plan = plan_market_order(book, meta, account_id, side, size, config)?;
+    let client_order_id = plan.request.client_order_id.get_or_insert_with(new_client_order_id).clone();
+
+    // Subscribe first so fills pushed while the request is in flight aren't missed.
+    let mut events = api.account_events(account_id).await?;
+    let response = api.place_order(plan.request.clone(), meta).await?;
+    check_response(&response)?;
+    let mut order_id = response_order_id(&response);
+
+    let mut tracker = FillTracker::default();
+    let mut order_filled: Option<Decimal> = None;
+    let _ = tokio::time::timeout(config.fill_timeout, async {
+        while let Some(event) = events.next().await {
+            match event {
+                AccountEvent::Order(order) => {
+                    if order_id.is_none() && order.client_order_id.as_deref() == Some(client_order_id.as_str()) {
+                        order_id = Some(order.order_id);
+                    }
+                    if Some(order.order_id) == order_id
+                        && OrderStatus::from_exchange(&order.status).is_some_and(OrderStatus::is_terminal)
+                    {
+                        order_filled = Some(Decimal::from_str(&order.filled_size).unwrap_or_default());
+                    }
+                }
+                AccountEvent::Fill(fill) if Some(fill.order_id) == order_id => tracker.add(&fill),
+                AccountEvent::Fill(_) => {}
+            }
+            // The final order update can arrive before its last fill.
+            if order_filled.is_some_and(|filled| tracker.size >= filled) {
+                break;
+            }
+        }
+    })
+    .await;
+
+    // Whatever the stream missed, the fill history has.
+    if let Some(order_id) = order_id
+        && order_filled.is_none_or(|filled| tracker.size < filled)
+    {
+        for fill in api.get_fills(account_id).await? {
+            if fill.order_id == order_id {
+                tracker.add(&fill);
+            }
+        }
+    }
+
+    let avg_price = (!tracker.size.is_zero()).then(|| tracker.notional / tracker.size);
+    let slippage = avg_price.map(|avg| {
+        let diff = match side {
+            OrderSide::Buy => avg - plan.reference_price,
+            OrderSide::Sell => plan.reference_price - avg,
+        };
+        diff / plan.reference_price
+    });
+    Ok(MarketOrderReport {
+        order_id,
+        requested_size: size,
+        filled_size: tracker.size,
+        avg_price,
+        reference_price: plan.reference_price,
+        worst_price: plan.worst_price,
+        slippage,
+        fees: tracker.fees,
+    })
+}
+
+#[derive(Default)]
+struct FillTracker {
+    seen: HashSet<u64>,
+    size: Decimal,
+    notional: Decimal,
+    fees: Decimal,
+}
+
+impl FillTracker {
+    fn add(&mut self, fill: &Fill) {
+        if !self.seen.insert(fill.id) {
+            return;
+        }
+        let (Ok(price), Ok(size)) = (Decimal::from_str(&fill.price), Decimal::from_str(&fill.size)) else {
+            return;
+        };
+        self.size += size;
+        self.notional += price * size;
+        self.fees += Decimal::from_str(&fill.fee).unwrap_or_default();
+    }
+}
+
+fn parse_decimal(field: &'static str, value: &str) -> Result<Decimal, MarketOrderError> {
+    Decimal::from_str(value.trim())
+        .map_err(|_| MarketOrderError::OrderHash(OrderHashError::InvalidDecimal { field, value: value.to_string() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::default_metadata;
+    use crate::model::{DepthUpdate, OrderType, PriceLevel, TimeInForce};
+    use crate::paper::PaperExchange;
+
+    const CONTRACT: u64 = 10000001;
+
+    #[tokio::test]
+    async fn test_market_order_respects_slippage_limit() {
+        let level = |price: &str, size: &str| PriceLevel { price: price.to_string(), size: size.to_string() };
+        let paper = PaperExchange::new(1, default_metadata());
+        paper.apply_depth(&DepthUpdate {
+            contract_id: CONTRACT,
+            depth_type: "SNAPSHOT".to_string(),
+            bids: vec![level("49990", "5")],
+            asks: vec![level("50000", "1"), level("50100", "1"), level("51000", "5")],
+        });
+        let book = paper.book(CONTRACT).unwrap();
+        let meta = default_metadata();
+        // 50 bps from 50000 allows 50250: the first two levels only.
+        let config = MarketOrderConfig::default();
+
+        let plan = plan_market_order(&book, &meta, 1, OrderSide::Buy, Decimal::from(3), &config).unwrap();
+        assert_eq!((plan.worst_price, plan.available), (Decimal::from(50_250), Decimal::TWO));
+        assert_eq!(plan.expected_avg_price, Some(Decimal::from(50_050)));
+        assert_eq!((plan.request.r#type, plan.request.time_in_force), (OrderType::Market, TimeInForce::Ioc));
+        assert_eq!((plan.request.price.as_str(), plan.request.l2_value.as_str()), ("50250", "150750"));
+        assert!(matches!(
+            plan_market_order(&book, &meta, 1, OrderSide::Buy, Decimal::from(3), &config.clone().with_require_full_fill(true)),
+            Err(MarketOrderError::InsufficientLiquidity { .. })
+        ));
+
+        let report = execute_market_order(&paper, &book, &meta, 1, OrderSide::Buy, Decimal::from(3), &config).await.unwrap();
+        assert_eq!((report.filled_size, report.avg_price), (Decimal::TWO, Some(Decimal::from(50_050))));
+        assert_eq!(report.slippage, Some(Decimal::new(1, 3)));
+        assert!(report.fees > Decimal::ZERO);
+        assert!(paper.get_open_orders(1).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,790 @@
+//! In-process stand-in for the EdgeX REST API and websocket, for tests that
+//! need to run offline. Enabled with the `mock-server` feature.
+//!
+//! REST requests must carry valid `X-edgeX-Api-*` headers for a registered
+//! account (or, for the account lookup and creation routes, for a key
+//! onboarded through `user/onboard`), and `createOrder` must carry a valid
+//! `l2Signature` over a nonce the account hasn't used before. Orders rest
+//! until filled with `fill_order` or cancelled; there is no matching engine.
+
+use crate::model::{
+    CollateralMeta, ContractMeta, CreateOrderRequest, Fill, GlobalMeta, Kline, LiquidityRole, MetaData, OpenOrder,
+    OrderSide, Position, TradeSetting,
+};
+use crate::onboarding::{AccountInfo, OnboardRequest, ALREADY_REGISTERED};
+use crate::position::ContractPosition;
+use crate::signature::{message_hash, parse_signature, verify_signature};
+use crate::stark_order::StarkLimitOrder;
+use crate::time_sync::local_millis;
+use futures_util::{SinkExt, StreamExt};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, HeaderMap, Method, Request, Response, Server, StatusCode};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use starknet_types_core::felt::Felt;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request as WsRequest, Response as WsResponse};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+const PRIVATE_WS_PATH: &str = "/api/v1/private/ws";
+const GET_ACCOUNT_PAGE_PATH: &str = "/api/v1/private/account/getAccountPage";
+const CREATE_ACCOUNT_PATH: &str = "/api/v1/private/account/createAccount";
+const FIRST_ORDER_ID: u64 = 1_000_000;
+
+#[derive(Debug, Clone)]
+pub struct MockExchangeConfig {
+    pub meta: MetaData,
+    /// How often the websocket sends `ping` frames.
+    pub ping_interval: Duration,
+    /// Added to the local clock for `getServerTime`.
+    pub server_time_offset_ms: i64,
+}
+
+impl Default for MockExchangeConfig {
+    fn default() -> Self {
+        Self {
+            meta: default_metadata(),
+            ping_interval: Duration::from_secs(5),
+            server_time_offset_ms: 0,
+        }
+    }
+}
+
+/// One BTCUSDT contract settled in USDT, with the exchange's real asset resolutions.
+pub fn default_metadata() -> MetaData {
+    MetaData {
+        global: GlobalMeta {
+            stark_ex_collateral_coin: CollateralMeta {
+                coin_id: 1000,
+                coin_name: "USDT".to_string(),
+                stark_ex_asset_id: "0x2".to_string(),
+                stark_ex_resolution: "0xf4240".to_string(),
+            },
+        },
+        contract_list: vec![ContractMeta {
+            contract_id: 10000001,
+            contract_name: "BTCUSDT".to_string(),
+            tick_size: "0.1".to_string(),
+            step_size: "0.001".to_string(),
+            stark_ex_synthetic_asset_id: "0x1".to_string(),
+            stark_ex_resolution: "0x2540be400".to_string(),
+            default_maker_fee_rate: "0.0002".to_string(),
+            default_taker_fee_rate: "0.0005".to_string(),
+            risk_tier_list: Vec::new(),
+        }],
+    }
+}
+
+#[derive(Debug, Clone)]
+enum WsEvent {
+    Public { channel: String, message: Value },
+    Account { account_id: u64, message: Value },
+}
+
+#[derive(Default)]
+struct State {
+    accounts: HashMap<u64, Felt>,
+    orders: Vec<(u64, OpenOrder)>,
+    fills: Vec<(u64, Fill)>,
+    positions: HashMap<(u64, u64), ContractPosition>,
+    leverage: HashMap<(u64, u64), String>,
+    klines: HashMap<u64, Vec<Kline>>,
+    cancel_after_generation: HashMap<u64, u64>,
+    /// `(account, l2Nonce)` pairs already spent by orders.
+    used_nonces: HashSet<(u64, u64)>,
+    /// Onboarded L2 keys and the L1 address each was registered by.
+    users: HashMap<Felt, String>,
+    /// Accounts opened through `createAccount`.
+    account_infos: Vec<AccountInfo>,
+}
+
+struct Shared {
+    config: MockExchangeConfig,
+    state: Mutex<State>,
+    events: broadcast::Sender<WsEvent>,
+    next_id: AtomicU64,
+    auth_failures: AtomicU64,
+    pongs: AtomicU64,
+}
+
+impl Shared {
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn push_account_event(&self, account_id: u64, orders: &[OpenOrder], fills: &[Fill]) {
+        let message = json!({
+            "type": "trade-event",
+            "content": { "data": { "order": orders, "orderFillTransaction": fills } },
+        });
+        let _ = self.events.send(WsEvent::Account { account_id, message });
+    }
+
+    fn cancel_all(&self, account_id: u64, contract_ids: &[u64]) -> Vec<OpenOrder> {
+        let mut state = self.state.lock().unwrap();
+        let cancelled: Vec<_> = state.orders.iter_mut()
+            .filter(|(account, o)| {
+                *account == account_id && is_open(o) && (contract_ids.is_empty() || contract_ids.contains(&o.contract_id))
+            })
+            .map(|(_, o)| {
+                o.status = "CANCELED".to_string();
+                o.clone()
+            })
+            .collect();
+        drop(state);
+        if !cancelled.is_empty() {
+            self.push_account_event(account_id, &cancelled, &[]);
+        }
+        cancelled
+    }
+}
+
+/// A running mock exchange; shuts down when dropped.
+pub struct MockExchange {
+    http_addr: SocketAddr,
+    ws_addr: SocketAddr,
+    shared: Arc<Shared>,
+    http_shutdown: Option<oneshot::Sender<()>>,
+    ws_task: JoinHandle<()>,
+}
+
+impl MockExchange {
+    pub async fn start() -> std::io::Result<Self> {
+        Self::start_with(MockExchangeConfig::default()).await
+    }
+
+    pub async fn start_with(config: MockExchangeConfig) -> std::io::Result<Self> {
+        let (events, _) = broadcast::channel(1024);
+        let shared = Arc::new(Shared {
+            config,
+            state: Mutex::new(State::default()),
+            events,
+            next_id: AtomicU64::new(FIRST_ORDER_ID),
+            auth_failures: AtomicU64::new(0),
+            pongs: AtomicU64::new(0),
+        });
+
+        let http_shared = shared.clone();
+        let make_svc = make_service_fn(move |_| {
+            let shared = http_shared.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle_http(shared.clone(), req))) }
+        });
+        let server = Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .map_err(std::io::Error::other)?
+            .serve(make_svc);
+        let http_addr = server.local_addr();
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            let _ = rx.await;
+        }));
+
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let ws_addr = listener.local_addr()?;
+        let ws_shared = shared.clone();
+        let ws_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_ws(ws_shared.clone(), stream));
+            }
+        });
+
+        Ok(Self { http_addr, ws_addr, shared, http_shutdown: Some(tx), ws_task })
+    }
+
+    /// Pass as `base_url` to `EdgeXClient`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.http_addr)
+    }
+
+    /// Pass as `ws_url` to `EdgeXWebSocket`.
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}", self.ws_addr)
+    }
+
+    pub fn metadata(&self) -> &MetaData {
+        &self.shared.config.meta
+    }
+
+    /// Accepts requests for `account_id` signed by the key behind `public_key`.
+    pub fn register_account(&self, account_id: u64, public_key: Felt) {
+        self.shared.state.lock().unwrap().accounts.insert(account_id, public_key);
+    }
+
+    /// Every order ever accepted for the account, including closed ones.
+    pub fn orders(&self, account_id: u64) -> Vec<OpenOrder> {
+        self.shared.state.lock().unwrap().orders.iter()
+            .filter(|(account, _)| *account == account_id)
+            .map(|(_, o)| o.clone())
+            .collect()
+    }
+
+    /// Requests refused for a bad or missing header or L2 signature.
+    pub fn auth_failures(&self) -> u64 {
+        self.shared.auth_failures.load(Ordering::SeqCst)
+    }
+
+    /// `pong` replies received on any websocket.
+    pub fn pongs(&self) -> u64 {
+        self.shared.pongs.load(Ordering::SeqCst)
+    }
+
+    /// Served by `getKline`.
+    pub fn set_klines(&self, contract_id: u64, klines: Vec<Kline>) {
+        self.shared.state.lock().unwrap().klines.insert(contract_id, klines);
+    }
+
+    /// Fills `size` of a resting order as maker, at the order price unless
+    /// `price` is given. Updates the position and pushes a `trade-event`.
+    pub fn fill_order(&self, order_id: u64, size: &str, price: Option<&str>) -> Option<Fill> {
+        let size = Decimal::from_str(size).ok()?;
+        let mut state = self.shared.state.lock().unwrap();
+        let (account_id, order) = state.orders.iter_mut().find(|(_, o)| o.order_id == order_id)?;
+        if !is_open(order) {
+            return None;
+        }
+        let account_id = *account_id;
+        let order_size = Decimal::from_str(&order.size).ok()?;
+        let filled = Decimal::from_str(&order.filled_size).unwrap_or_default();
+        let size = size.min(order_size - filled);
+        let price = Decimal::from_str(price.unwrap_or(&order.price)).ok()?;
+        order.filled_size = (filled + size).normalize().to_string();
+        order.remaining_size = (order_size - filled - size).normalize().to_string();
+        order.status = if filled + size >= order_size { "FILLED" } else { "PARTIALLY_FILLED" }.to_string();
+        let order = order.clone();
+
+        let fee_rate = self.shared.config.meta.contract(order.contract_id)
+            .and_then(|c| Decimal::from_str(&c.default_maker_fee_rate).ok())
+            .unwrap_or_default();
+        let fill = Fill {
+            id: self.shared.next_id(),
+            order_id,
+            contract_id: order.contract_id,
+            price: price.normalize().to_string(),
+            size: size.normalize().to_string(),
+            side: order.side,
+            time: local_millis(),
+            fee: (size * price * fee_rate).normalize().to_string(),
+            fee_asset_id: self.shared.config.meta.collateral().coin_id,
+            direction: Some(LiquidityRole::Maker),
+        };
+        let signed_size = if order.side == OrderSide::Buy { size } else { -size };
+        state.positions.entry((account_id, order.contract_id)).or_default().trade(signed_size, price);
+        state.fills.push((account_id, fill.clone()));
+        drop(state);
+
+        self.shared.push_account_event(account_id, std::slice::from_ref(&order), std::slice::from_ref(&fill));
+        Some(fill)
+    }
+
+    /// Sends `data` to subscribers of `channel` as a `quote-event`.
+    pub fn publish(&self, channel: &str, data: Value) {
+        let message = json!({
+            "type": "quote-event",
+            "channel": channel,
+            "content": { "dataType": "changed", "channel": channel, "data": data },
+        });
+        let _ = self.shared.events.send(WsEvent::Public { channel: channel.to_string(), message });
+    }
+}
+
+impl Drop for MockExchange {
+    fn drop(&mut self) {
+        if let Some(tx) = self.http_shutdown.take() {
+            let _ = tx.send(());
+        }
+        self.ws_task.abort();
+    }
+}
+
+fn is_open(order: &OpenOrder) -> bool {
+    matches!(order.status.as_str(), "OPEN" | "PARTIALLY_FILLED")
+}
+
+fn ok(data: Value) -> Response<Body> {
+    respond(StatusCode::OK, json!({ "code": "SUCCESS", "data": data }))
+}
+
+fn fail(status: StatusCode, code: &str, msg: &str) -> Response<Body> {
+    respond(status, json!({ "code": code, "msg": msg }))
+}
+
+fn respond(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn page<T: serde::Serialize>(items: &[T]) -> Value {
+    json!({ "dataList": items, "nextPageOffsetData": "" })
+}
+
+/// Checks the `X-edgeX-Api-*` headers against the account's registered key.
+fn verify_headers(shared: &Shared, headers: &HeaderMap, method: &str, path: &str, payload: &str, account_id: u64) -> Result<(), String> {
+    let public_key = *shared.state.lock().unwrap().accounts.get(&account_id).ok_or("unknown account")?;
+    verify_signed_by(&public_key, headers, method, path, payload)
+}
+
+/// Checks the `X-edgeX-Api-*` headers against `public_key`.
+fn verify_signed_by(public_key: &Felt, headers: &HeaderMap, method: &str, path: &str, payload: &str) -> Result<(), String> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let timestamp = header("X-edgeX-Api-Timestamp").ok_or("missing timestamp header")?;
+    let signature = header("X-edgeX-Api-Signature").ok_or("missing signature header")?;
+
+    let hash = message_hash(&format!("{}{}{}{}", timestamp, method, path, payload));
+    let signature = parse_signature(&signature).map_err(|e| e.to_string())?;
+    match verify_signature(public_key, &hash, &signature) {
+        Ok(true) => Ok(()),
+        _ => Err("invalid header signature".to_string()),
+    }
+}
+
+fn query_params(query: Option<&str>) -> Vec<(String, String)> {
+    url::form_urlencoded::parse(query.unwrap_or_default().as_bytes()).into_owned().collect()
+}
+
+fn param_u64(params: &[(String, String)], name: &str) -> Option<u64> {
+    params.iter().find(|(k, _)| k == name).and_then(|(_, v)| v.parse().ok())
+}
+
+fn json_u64(value: &Value) -> Option<u64> {
+    value.as_u64().or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+async fn handle_http(shared: Arc<Shared>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let params = query_params(req.uri().query());
+    let headers = req.headers().clone();
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        Err(e) => return Ok(fail(StatusCode::BAD_REQUEST, "BAD_REQUEST", &e.to_string())),
+    };
+
+    if method == Method::POST && path == "/api/v1/public/user/onboard" {
+        return Ok(onboard(&shared, &body));
+    }
+    if path.starts_with("/api/v1/public/") {
+        return Ok(handle_public(&shared, &path, &params));
+    }
+    if !path.starts_with("/api/v1/private/") {
+        return Ok(fail(StatusCode::NOT_FOUND, "NOT_FOUND", &path));
+    }
+
+    let json: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+    let payload = if method == Method::POST {
+        body.clone()
+    } else {
+        let mut sorted = params.clone();
+        sorted.sort_by(|a, b| a.0.cmp(&b.0));
+        sorted.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&")
+    };
+
+    // Account lookup and creation happen before there is an account, so the
+    // caller is whichever onboarded key signed the headers.
+    if path == GET_ACCOUNT_PAGE_PATH || path == CREATE_ACCOUNT_PATH {
+        let users: Vec<_> = shared.state.lock().unwrap().users.keys().copied().collect();
+        let Some(l2_key) = users.into_iter().find(|key| verify_signed_by(key, &headers, method.as_str(), &path, &payload).is_ok()) else {
+            shared.auth_failures.fetch_add(1, Ordering::SeqCst);
+            return Ok(fail(StatusCode::UNAUTHORIZED, "UNAUTHORIZED", "no onboarded key signed the request"));
+        };
+        return Ok(if path == CREATE_ACCOUNT_PATH {
+            create_account(&shared, l2_key, &json)
+        } else {
+            let state = shared.state.lock().unwrap();
+            let address = params.iter().find(|(k, _)| k == "ethereumAddress").map(|(_, v)| v.as_str());
+            let accounts: Vec<_> = state.account_infos.iter()
+                .filter(|a| a.l2_key == format!("{:#x}", l2_key) && address.is_none_or(|address| a.ethereum_address == address))
+                .collect();
+            ok(page(&accounts))
+        });
+    }
+
+    // Other private routes: authenticate against the account named in the request.
+    let account_id = if method == Method::POST { json_u64(&json["accountId"]) } else { param_u64(&params, "accountId") };
+    let Some(account_id) = account_id else {
+        return Ok(fail(StatusCode::BAD_REQUEST, "BAD_REQUEST", "missing accountId"));
+    };
+    if let Err(e) = verify_headers(&shared, &headers, method.as_str(), &path, &payload, account_id) {
+        shared.auth_failures.fetch_add(1, Ordering::SeqCst);
+        return Ok(fail(StatusCode::UNAUTHORIZED, "UNAUTHORIZED", &e));
+    }
+
+    Ok(match (method, path.as_str()) {
+        (Method::POST, "/api/v1/private/order/createOrder") => create_order(&shared, account_id, &body),
+        (Method::POST, "/api/v1/private/order/cancelOrderById") => {
+            let order_id = json_u64(&json["orderId"]);
+            let client_order_id = json["clientOrderId"].as_str();
+            let mut state = shared.state.lock().unwrap();
+            let order = state.orders.iter_mut().find(|(account, o)| {
+                *account == account_id
+                    && (Some(o.order_id) == order_id || (client_order_id.is_some() && o.client_order_id.as_deref() == client_order_id))
+            });
+            match order {
+                Some((_, order)) if is_open(order) => {
+                    order.status = "CANCELED".to_string();
+                    let order = order.clone();
+                    drop(state);
+                    shared.push_account_event(account_id, std::slice::from_ref(&order), &[]);
+                    ok(json!({ "orderId": order.order_id.to_string() }))
+                }
+                Some(_) => ok_code("ORDER_NOT_OPEN"),
+                None => ok_code("ORDER_NOT_FOUND"),
+            }
+        }
+        (Method::POST, "/api/v1/private/order/cancelAllOrder") => {
+            let contract_ids: Vec<u64> = json["filterContractIdList"].as_array()
+                .map(|ids| ids.iter().filter_map(json_u64).collect())
+                .unwrap_or_default();
+            let cancelled = shared.cancel_all(account_id, &contract_ids);
+            ok(json!({ "cancelled": cancelled.len() }))
+        }
+        (Method::POST, "/api/v1/private/order/cancelAllOrderAfter") => {
+            let timeout_ms = json_u64(&json["timeoutMillis"]).unwrap_or(0);
+            let generation = {
+                let mut state = shared.state.lock().unwrap();
+                let generation = state.cancel_after_generation.entry(account_id).or_default();
+                *generation += 1;
+                *generation
+            };
+            if timeout_ms > 0 {
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(timeout_ms)).await;
+                    let current = shared.state.lock().unwrap().cancel_after_generation.get(&account_id).copied();
+                    if current == Some(generation) {
+                        shared.cancel_all(account_id, &[]);
+                    }
+                });
+            }
+            ok(json!({}))
+        }
+        (Method::GET, "/api/v1/private/order/getOpenOrders") => {
+            let state = shared.state.lock().unwrap();
+            let orders: Vec<_> = state.orders.iter()
+                .filter(|(account, o)| *account == account_id && is_open(o))
+                .map(|(_, o)| o)
+                .collect();
+            ok(json!(orders))
+        }
+        (Method::GET, "/api/v1/private/order/getFills") => {
+            let state = shared.state.lock().unwrap();
+            let fills: Vec<_> = state.fills.iter().filter(|(account, _)| *account == account_id).map(|(_, f)| f).collect();
+            ok(json!(fills))
+        }
+        (Method::GET, "/api/v1/private/account/getPositionByAccountId") => {
+            let state = shared.state.lock().unwrap();
+            let mut positions: Vec<_> = state.positions.iter()
+                .filter(|((account, _), p)| *account == account_id && !p.net_size.is_zero())
+                .map(|((_, contract_id), p)| Position {
+                    contract_id: *contract_id,
+                    open_size: p.net_size.normalize().to_string(),
+                    open_value: (p.net_size * p.avg_entry_price).normalize().to_string(),
+                    open_fee: String::new(),
+                    funding_fee: String::new(),
+                })
+                .collect();
+            positions.sort_by_key(|p| p.contract_id);
+            ok(json!(positions))
+        }
+        (Method::GET, "/api/v1/private/account/getAccountById") => {
+            let state = shared.state.lock().unwrap();
+            let settings: HashMap<String, TradeSetting> = state.leverage.iter()
+                .filter(|((account, _), _)| *account == account_id)
+                .map(|((_, contract_id), leverage)| {
+                    let setting = TradeSetting {
+                        max_leverage: leverage.clone(),
+                        is_set_max_leverage: true,
+                        ..Default::default()
+                    };
+                    (contract_id.to_string(), setting)
+                })
+                .collect();
+            ok(json!({ "id": account_id.to_string(), "contractIdToTradeSetting": settings }))
+        }
+        (Method::POST, "/api/v1/private/account/updateLeverageSetting") => {
+            let (Some(contract_id), Some(leverage)) = (json_u64(&json["contractId"]), json["leverage"].as_str()) else {
+                return Ok(fail(StatusCode::BAD_REQUEST, "BAD_REQUEST", "contractId and leverage are required"));
+            };
+            shared.state.lock().unwrap().leverage.insert((account_id, contract_id), leverage.to_string());
+            ok(json!({}))
+        }
+        (Method::GET, "/api/v1/private/funding/getFundingPaymentPage") => ok(page::<Value>(&[])),
+        _ => fail(StatusCode::NOT_FOUND, "NOT_FOUND", &path),
+    })
+}
+
+/// Registers an L2 key against an L1 address. The EIP-712 signature is not
+/// checked; re-registering the same pair is reported as already registered.
+fn onboard(shared: &Shared, body: &str) -> Response<Body> {
+    let req: OnboardRequest = match serde_json::from_str(body) {
+        Ok(req) => req,
+        Err(e) => return fail(StatusCode::BAD_REQUEST, "BAD_REQUEST", &e.to_string()),
+    };
+    let Ok(l2_key) = Felt::from_hex(&req.l2_key) else {
+        return fail(StatusCode::BAD_REQUEST, "BAD_REQUEST", "invalid l2Key");
+    };
+    if req.onboarding_signature.is_empty() {
+        return ok_code("INVALID_ONBOARDING_SIGNATURE");
+    }
+    let mut state = shared.state.lock().unwrap();
+    match state.users.get(&l2_key) {
+        Some(address) if *address == req.ethereum_address => ok_code(ALREADY_REGISTERED),
+        Some(_) => ok_code("L2_KEY_IN_USE"),
+        None => {
+            state.users.insert(l2_key, req.ethereum_address.clone());
+            ok(json!({ "ethereumAddress": req.ethereum_address, "l2Key": req.l2_key }))
+        }
+    }
+}
+
+/// Opens an account for an onboarded key; its requests are then accepted.
+fn create_account(shared: &Shared, l2_key: Felt, json: &Value) -> Response<Body> {
+    let Some(client_account_id) = json["clientAccountId"].as_str() else {
+        return fail(StatusCode::BAD_REQUEST, "BAD_REQUEST", "clientAccountId is required");
+    };
+    let mut state = shared.state.lock().unwrap();
+    let l2_key_hex = format!("{:#x}", l2_key);
+    if state.account_infos.iter().any(|a| a.l2_key == l2_key_hex && a.client_account_id == client_account_id) {
+        return ok_code("ACCOUNT_ALREADY_EXISTS");
+    }
+    let account = AccountInfo {
+        id: shared.next_id(),
+        ethereum_address: state.users[&l2_key].clone(),
+        l2_key: l2_key_hex,
+        client_account_id: client_account_id.to_string(),
+    };
+    state.accounts.insert(account.id, l2_key);
+    state.account_infos.push(account.clone());
+    ok(json!(account))
+}
+
+/// Business errors come back as HTTP 200 with a non-`SUCCESS` code.
+fn ok_code(code: &str) -> Response<Body> {
+    respond(StatusCode::OK, json!({ "code": code, "msg": code }))
+}
+
+fn handle_public(shared: &Shared, path: &str, params: &[(String, String)]) -> Response<Body> {
+    match path {
+        "/api/v1/public/meta/getServerTime" => {
+            let now = local_millis() as i64 + shared.config.server_time_offset_ms;
+            ok(json!({ "timeMillis": now.to_string() }))
+        }
+        "/api/v1/public/meta/getMetaData" => ok(json!(shared.config.meta)),
+        "/api/v1/public/quote/getKline" => {
+            let contract_id = param_u64(params, "contractId").unwrap_or_default();
+            let start = param_u64(params, "filterBeginKlineTimeInclusive").unwrap_or(0);
+            let end = param_u64(params, "filterEndKlineTimeExclusive").unwrap_or(u64::MAX);
+            let state = shared.state.lock().unwrap();
+            let klines: Vec<_> = state.klines.get(&contract_id).into_iter().flatten()
+                .filter(|k| k.kline_time >= start && k.kline_time < end)
+                .cloned()
+                .collect();
+            ok(page(&klines))
+        }
+        _ => fail(StatusCode::NOT_FOUND, "NOT_FOUND", path),
+    }
+}
+
+/// Checks an L2 signature against the account's key and spends the nonce.
+/// Returns the refusal code on failure.
+fn check_l2_auth(shared: &Shared, account_id: u64, hash: Option<Felt>, signature: &str, nonce: u64) -> Result<(), &'static str> {
+    let mut state = shared.state.lock().unwrap();
+    let public_key = state.accounts[&account_id];
+    let valid = hash
+        .zip(parse_signature(signature).ok())
+        .is_some_and(|(hash, signature)| verify_signature(&public_key, &hash, &signature).unwrap_or(false));
+    if !valid {
+        shared.auth_failures.fetch_add(1, Ordering::SeqCst);
+        return Err("INVALID_L2_SIGNATURE");
+    }
+    if !state.used_nonces.insert((account_id, nonce)) {
+        return Err("NONCE_REUSED");
+    }
+    Ok(())
+}
+
+fn create_order(shared: &Shared, account_id: u64, body: &str) -> Response<Body> {
+    let req: CreateOrderRequest = match serde_json::from_str(body) {
+        Ok(req) => req,
+        Err(e) => return fail(StatusCode::BAD_REQUEST, "BAD_REQUEST", &e.to_string()),
+    };
+    let hash = StarkLimitOrder::from_request(&req, &shared.config.meta).and_then(|order| order.hash()).ok();
+    if let Err(code) = check_l2_auth(shared, account_id, hash, &req.l2_signature, req.l2_nonce) {
+        return ok_code(code);
+    }
+
+    let order = OpenOrder {
+        order_id: shared.next_id(),
+        client_order_id: req.client_order_id.clone(),
+        contract_id: req.contract_id,
+        price: req.price.clone(),
+        size: req.size.clone(),
+        side: req.side,
+        status: "OPEN".to_string(),
+        filled_size: "0".to_string(),
+        remaining_size: req.size.clone(),
+    };
+    shared.state.lock().unwrap().orders.push((account_id, order.clone()));
+    shared.push_account_event(account_id, std::slice::from_ref(&order), &[]);
+    ok(json!({ "orderId": order.order_id.to_string(), "clientOrderId": order.client_order_id }))
+}
+
+async fn handle_ws(shared: Arc<Shared>, stream: TcpStream) {
+    // `Some(account)` for an authenticated private connection.
+    let mut private_account: Option<u64> = None;
+    // The signature is fixed by tungstenite's handshake callback.
+    #[allow(clippy::result_large_err)]
+    let callback = |req: &WsRequest, response: WsResponse| -> Result<WsResponse, ErrorResponse> {
+        if req.uri().path() != PRIVATE_WS_PATH {
+            return Ok(response);
+        }
+        let query = req.uri().query().unwrap_or_default().to_string();
+        let account_id = param_u64(&query_params(Some(&query)), "accountId");
+        let verified = account_id
+            .ok_or_else(|| "missing accountId".to_string())
+            .and_then(|id| verify_headers(&shared, req.headers(), "GET", PRIVATE_WS_PATH, &query, id).map(|_| id));
+        match verified {
+            Ok(id) => {
+                private_account = Some(id);
+                Ok(response)
+            }
+            Err(e) => {
+                shared.auth_failures.fetch_add(1, Ordering::SeqCst);
+                let mut error = ErrorResponse::new(Some(e));
+                *error.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(error)
+            }
+        }
+    };
+    let Ok(ws) = tokio_tungstenite::accept_hdr_async(stream, callback).await else {
+        return;
+    };
+
+    let (mut sink, mut source) = ws.split();
+    let mut events = shared.events.subscribe();
+    let mut subscriptions = HashSet::new();
+    let mut ping = tokio::time::interval(shared.config.ping_interval);
+    ping.tick().await;
+
+    loop {
+        let outgoing = tokio::select! {
+            incoming = source.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    let msg: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+                    let channel = msg["channel"].as_str().unwrap_or_default().to_string();
+                    match msg["type"].as_str() {
+                        Some("pong") => {
+                            shared.pongs.fetch_add(1, Ordering::SeqCst);
+                            None
+                        }
+                        Some("ping") => Some(json!({ "type": "pong", "time": msg["time"] })),
+                        Some("subscribe") => {
+                            subscriptions.insert(channel.clone());
+                            Some(json!({ "type": "subscribed", "channel": channel }))
+                        }
+                        Some("unsubscribe") => {
+                            subscriptions.remove(&channel);
+                            Some(json!({ "type": "unsubscribed", "channel": channel }))
+                        }
+                        _ => Some(json!({ "type": "error", "content": { "msg": "unknown message" } })),
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => None,
+            },
+            event = events.recv() => match event {
+                Ok(WsEvent::Public { channel, message }) if subscriptions.contains(&channel) => Some(message),
+                Ok(WsEvent::Account { account_id, message }) if private_account == Some(account_id) => Some(message),
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => None,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = ping.tick() => Some(json!({ "type": "ping", "time": local_millis().to_string() })),
+        };
+        if let Some(message) = outgoing
+            && sink.send(Message::Text(message.to_string())).await.is_err()
+        {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::EdgeXClient;
+    use crate::model::CancelOrderRequest;
+    use crate::order_manager::OrderManager;
+    use crate::signature::SignatureManager;
+    use crate::websocket::{parse_account_events, AccountEvent, EdgeXWebSocket};
+
+    const KEY: &str = "0x0234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+    const ACCOUNT_ID: u64 = 12345;
+
+    fn order(meta: &MetaData) -> CreateOrderRequest {
+        CreateOrderRequest::limit(meta, ACCOUNT_ID, 10000001, OrderSide::Buy, Decimal::from(50_000), Decimal::new(1, 2)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_against_mock_exchange() {
+        let mock = MockExchange::start_with(MockExchangeConfig {
+            ping_interval: Duration::from_millis(50),
+            ..Default::default()
+        }).await.unwrap();
+        mock.register_account(ACCOUNT_ID, SignatureManager::new(KEY).unwrap().public_key());
+
+        let orders = Arc::new(OrderManager::new());
+        let client = EdgeXClient::new(KEY, Some(mock.base_url())).unwrap()
+            .with_account_id(ACCOUNT_ID)
+            .with_order_manager(orders.clone());
+        client.time_sync().sync().await.unwrap();
+        let meta = client.get_metadata().await.unwrap();
+
+        let ws = EdgeXWebSocket::connect_private(&client, ACCOUNT_ID, Some(&mock.ws_url())).await.unwrap();
+        let mut messages = Box::pin(EdgeXWebSocket::messages(ws));
+
+        let response = client.place_order(order(&meta), &meta).await.unwrap();
+        assert_eq!(response["code"], "SUCCESS");
+        let open = client.get_open_orders(ACCOUNT_ID).await.unwrap();
+        assert_eq!(open.len(), 1);
+
+        let fill = mock.fill_order(open[0].order_id, "0.004", None).unwrap();
+        assert_eq!(client.get_fills(ACCOUNT_ID).await.unwrap()[0].id, fill.id);
+        assert_eq!(client.get_positions(ACCOUNT_ID).await.unwrap()[0].open_size, "0.004");
+
+        // Private stream: order ack, then the fill.
+        let mut saw_fill = false;
+        while !saw_fill {
+            let msg = messages.next().await.unwrap().unwrap();
+            saw_fill = parse_account_events(&msg).iter().any(|e| matches!(e, AccountEvent::Fill(f) if f.id == fill.id));
+        }
+        // Pings are answered by `messages` as a side effect of polling.
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let _ = tokio::time::timeout(Duration::from_millis(50), messages.next()).await;
+        assert!(mock.pongs() > 0);
+
+        let cancel = CancelOrderRequest::by_order_id(ACCOUNT_ID, 10000001, open[0].order_id);
+        assert_eq!(client.cancel_order(&cancel).await.unwrap()["code"], "SUCCESS");
+        assert!(client.get_open_orders(ACCOUNT_ID).await.unwrap().is_empty());
+        assert_eq!(client.cancel_order(&cancel).await.unwrap()["code"], "ORDER_NOT_OPEN");
+
+        // Tampered L2 signature and a foreign key are both refused.
+        let mut bad = order(&meta);
+        bad.l2_expire_time = client.time_sync().default_l2_expire_time();
+        client.sign_order(&mut bad, &meta).await.unwrap();
+        bad.l2_size = "0.02".to_string();
+        assert_eq!(client.create_order(&bad).await.unwrap()["code"], "INVALID_L2_SIGNATURE");
+        let other = EdgeXClient::new("0x2", Some(mock.base_url())).unwrap();
+        assert!(other.get_open_orders(ACCOUNT_ID).await.is_err());
+        assert_eq!(mock.auth_failures(), 2);
+    }
+}
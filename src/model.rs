@@ -1,20 +1,39 @@
-use serde::{Serialize, Deserialize};
+use crate::stark_order::{collateral_decimals, OrderHashError};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Serialize, Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::str::FromStr;
+use thiserror::Error;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// EdgeX returns most ids as JSON strings; accept either strings or numbers.
+pub(crate) fn de_u64_from_str_or_num<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StrOrNum {
+        Str(String),
+        Num(u64),
+    }
+    match StrOrNum::deserialize(deserializer)? {
+        StrOrNum::Str(s) => s.parse().map_err(serde::de::Error::custom),
+        StrOrNum::Num(n) => Ok(n),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum OrderSide {
     Buy,
     Sell,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum OrderType {
     Limit,
     Market,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum TimeInForce {
     Gtc,
@@ -32,6 +51,8 @@ pub struct CreateOrderRequest {
     pub account_id: u64,
     pub contract_id: u64,
     pub side: OrderSide,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
     // L2 Auth fields
     pub l2_nonce: u64,
     pub l2_value: String,
@@ -41,6 +62,84 @@ pub struct CreateOrderRequest {
     pub l2_signature: String,
 }
 
+impl CreateOrderRequest {
+    /// A GTC limit order with `l2Value` / `l2Size` set from `price * size` and
+    /// `l2LimitFee` at the contract's taker rate, rounded up to the collateral
+    /// resolution. Nonce, expiry and signature are left for `EdgeXClient::place_order`.
+    pub fn limit(
+        meta: &MetaData,
+        account_id: u64,
+        contract_id: u64,
+        side: OrderSide,
+        price: Decimal,
+        size: Decimal,
+    ) -> Result<Self, OrderHashError> {
+        let contract = meta.contract(contract_id).ok_or(OrderHashError::UnknownContract(contract_id))?;
+        let taker_fee_rate = Decimal::from_str(contract.default_taker_fee_rate.trim()).map_err(|_| {
+            OrderHashError::InvalidDecimal { field: "defaultTakerFeeRate", value: contract.default_taker_fee_rate.clone() }
+        })?;
+        let value = price * size;
+        let fee = (value * taker_fee_rate)
+            .round_dp_with_strategy(collateral_decimals(meta)?, RoundingStrategy::AwayFromZero);
+        Ok(Self {
+            price: price.normalize().to_string(),
+            size: size.normalize().to_string(),
+            r#type: OrderType::Limit,
+            time_in_force: TimeInForce::Gtc,
+            account_id,
+            contract_id,
+            side,
+            client_order_id: None,
+            l2_nonce: 0,
+            l2_value: value.normalize().to_string(),
+            l2_size: size.normalize().to_string(),
+            l2_limit_fee: fee.normalize().to_string(),
+            l2_expire_time: 0,
+            l2_signature: String::new(),
+        })
+    }
+
+    /// An IOC market order. `worst_price` is the least favourable price accepted;
+    /// the signed collateral amount and fee cap are derived from it as in `limit`.
+    pub fn market(
+        meta: &MetaData,
+        account_id: u64,
+        contract_id: u64,
+        side: OrderSide,
+        worst_price: Decimal,
+        size: Decimal,
+    ) -> Result<Self, OrderHashError> {
+        let mut req = Self::limit(meta, account_id, contract_id, side, worst_price, size)?;
+        req.r#type = OrderType::Market;
+        req.time_in_force = TimeInForce::Ioc;
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+impl CreateOrderRequest {
+    /// Unsigned GTC limit order with the given price and size taken verbatim,
+    /// for tests that don't need L2 fields or want malformed values.
+    pub(crate) fn unsigned(account_id: u64, contract_id: u64, side: OrderSide, price: &str, size: &str) -> Self {
+        Self {
+            price: price.to_string(),
+            size: size.to_string(),
+            r#type: OrderType::Limit,
+            time_in_force: TimeInForce::Gtc,
+            account_id,
+            contract_id,
+            side,
+            client_order_id: None,
+            l2_nonce: 0,
+            l2_value: String::new(),
+            l2_size: String::new(),
+            l2_limit_fee: String::new(),
+            l2_expire_time: 0,
+            l2_signature: String::new(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CancelOrderRequest {
@@ -53,6 +152,66 @@ pub struct CancelOrderRequest {
     pub l2_signature: String,
 }
 
+impl CancelOrderRequest {
+    /// Cancels by exchange order id.
+    pub fn by_order_id(account_id: u64, contract_id: u64, order_id: u64) -> Self {
+        Self {
+            account_id,
+            order_id: Some(order_id),
+            client_order_id: None,
+            contract_id,
+            l2_nonce: 0,
+            l2_signature: String::new(),
+        }
+    }
+
+    /// Cancels by the client order id given at placement.
+    pub fn by_client_order_id(account_id: u64, contract_id: u64, client_order_id: &str) -> Self {
+        Self {
+            account_id,
+            order_id: None,
+            client_order_id: Some(client_order_id.to_string()),
+            contract_id,
+            l2_nonce: 0,
+            l2_signature: String::new(),
+        }
+    }
+}
+
+/// Moves collateral to another account. Signed like an order; see `EdgeXClient::transfer`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferRequest {
+    pub account_id: u64,
+    pub coin_id: u64,
+    pub amount: String,
+    pub receiver_account_id: u64,
+    /// Stark public key of the receiving account, hex.
+    pub receiver_l2_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_transfer_id: Option<String>,
+    // L2 Auth fields
+    pub l2_nonce: u64,
+    pub l2_expire_time: u64,
+    pub l2_signature: String,
+}
+
+/// Withdraws collateral to an L1 address; see `EdgeXClient::withdraw`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawRequest {
+    pub account_id: u64,
+    pub coin_id: u64,
+    pub amount: String,
+    pub eth_address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_withdraw_id: Option<String>,
+    // L2 Auth fields
+    pub l2_nonce: u64,
+    pub l2_expire_time: u64,
+    pub l2_signature: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderResponse {
@@ -65,26 +224,490 @@ pub struct OrderResponse {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct OpenOrder {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
     pub order_id: u64,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
     pub contract_id: u64,
     pub price: String,
     pub size: String,
     pub side: OrderSide,
     pub status: String,
+    #[serde(default)]
     pub filled_size: String,
+    #[serde(default)]
     pub remaining_size: String,
 }
 
+/// Order lifecycle as tracked client-side.
+/// `Filled`, `Cancelled`, `Rejected` and `Expired` are terminal.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderStatus {
+    PendingNew,
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+    Expired,
+}
+
+impl OrderStatus {
+    /// Maps an exchange status string. Unknown values return `None`.
+    pub fn from_exchange(status: &str) -> Option<Self> {
+        match status.to_ascii_uppercase().as_str() {
+            "PENDING" | "PENDING_NEW" | "UNTRIGGERED" => Some(Self::PendingNew),
+            "OPEN" | "NEW" | "CANCELING" => Some(Self::Open),
+            "PARTIALLY_FILLED" => Some(Self::PartiallyFilled),
+            "FILLED" => Some(Self::Filled),
+            "CANCELED" | "CANCELLED" => Some(Self::Cancelled),
+            "REJECTED" | "FAILED" => Some(Self::Rejected),
+            "EXPIRED" => Some(Self::Expired),
+            _ => None,
+        }
+    }
+
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Filled | Self::Cancelled | Self::Rejected | Self::Expired)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Fill {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
     pub id: u64,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
     pub order_id: u64,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
     pub contract_id: u64,
     pub price: String,
     pub size: String,
     pub side: OrderSide,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
     pub time: u64,
     pub fee: String,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
     pub fee_asset_id: u64,
+    /// Whether this side of the trade provided or took liquidity, when reported.
+    #[serde(default)]
+    pub direction: Option<LiquidityRole>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LiquidityRole {
+    Maker,
+    Taker,
+}
+
+/// One funding settlement on a position.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FundingPayment {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub id: u64,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub contract_id: u64,
+    pub funding_rate: String,
+    /// Signed position size the payment was computed on.
+    #[serde(default)]
+    pub position_size: String,
+    #[serde(default)]
+    pub oracle_price: String,
+    /// Collateral change: positive when funding was received, negative when paid.
+    pub amount: String,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub time: u64,
+}
+
+/// A trade from the public `trades.{contractId}` channel.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicTrade {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub ticket_id: u64,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub contract_id: u64,
+    pub price: String,
+    pub size: String,
+    #[serde(default)]
+    pub is_buyer_maker: bool,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub time: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PriceLevel {
+    pub price: String,
+    pub size: String,
+}
+
+/// Order book update from the public `depth.{contractId}.{levels}` channel.
+/// A size of zero removes the level.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DepthUpdate {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub contract_id: u64,
+    /// `SNAPSHOT` replaces the book, `CHANGED` patches it.
+    #[serde(default)]
+    pub depth_type: String,
+    #[serde(default)]
+    pub bids: Vec<PriceLevel>,
+    #[serde(default)]
+    pub asks: Vec<PriceLevel>,
+}
+
+impl DepthUpdate {
+    pub fn is_snapshot(&self) -> bool {
+        self.depth_type.eq_ignore_ascii_case("SNAPSHOT")
+    }
+}
+
+/// Bar sizes served by the REST kline endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum KlineInterval {
+    Minute1,
+    Minute5,
+    Minute15,
+    Minute30,
+    Hour1,
+    Hour2,
+    Hour4,
+    Hour6,
+    Hour8,
+    Hour12,
+    Day1,
+    Week1,
+}
+
+impl KlineInterval {
+    pub const ALL: [KlineInterval; 12] = [
+        KlineInterval::Minute1,
+        KlineInterval::Minute5,
+        KlineInterval::Minute15,
+        KlineInterval::Minute30,
+        KlineInterval::Hour1,
+        KlineInterval::Hour2,
+        KlineInterval::Hour4,
+        KlineInterval::Hour6,
+        KlineInterval::Hour8,
+        KlineInterval::Hour12,
+        KlineInterval::Day1,
+        KlineInterval::Week1,
+    ];
+
+    pub fn as_millis(&self) -> u64 {
+        const MINUTE: u64 = 60 * 1000;
+        match self {
+            KlineInterval::Minute1 => MINUTE,
+            KlineInterval::Minute5 => 5 * MINUTE,
+            KlineInterval::Minute15 => 15 * MINUTE,
+            KlineInterval::Minute30 => 30 * MINUTE,
+            KlineInterval::Hour1 => 60 * MINUTE,
+            KlineInterval::Hour2 => 2 * 60 * MINUTE,
+            KlineInterval::Hour4 => 4 * 60 * MINUTE,
+            KlineInterval::Hour6 => 6 * 60 * MINUTE,
+            KlineInterval::Hour8 => 8 * 60 * MINUTE,
+            KlineInterval::Hour12 => 12 * 60 * MINUTE,
+            KlineInterval::Day1 => 24 * 60 * MINUTE,
+            KlineInterval::Week1 => 7 * 24 * 60 * MINUTE,
+        }
+    }
+}
+
+/// One exchange kline; `kline_time` is the bar's open time in milliseconds.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Kline {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub kline_time: u64,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    /// Traded size.
+    pub size: String,
+    /// Traded notional.
+    #[serde(default)]
+    pub value: String,
+    #[serde(default, deserialize_with = "de_u64_from_str_or_num")]
+    pub trades: u64,
+}
+
+/// A public funding rate settlement for a contract.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FundingRate {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub contract_id: u64,
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub funding_time: u64,
+    /// Positive: longs pay shorts.
+    pub funding_rate: String,
+    pub oracle_price: String,
+}
+
+/// Cursor-paginated list as returned by the `get*Page` endpoints.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    #[serde(default = "Vec::new")]
+    pub data_list: Vec<T>,
+    /// Offset for the next page; empty on the last one.
+    #[serde(default)]
+    pub next_page_offset_data: String,
+}
+
+/// Exchange-side position for one contract.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Position {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub contract_id: u64,
+    /// Signed size: positive for long, negative for short.
+    pub open_size: String,
+    /// Signed entry notional (`open_size * average entry price`).
+    pub open_value: String,
+    #[serde(default)]
+    pub open_fee: String,
+    #[serde(default)]
+    pub funding_fee: String,
+}
+
+/// Per-contract metadata from `/api/v1/public/meta/getMetaData`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractMeta {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub contract_id: u64,
+    pub contract_name: String,
+    pub tick_size: String,
+    pub step_size: String,
+    pub stark_ex_synthetic_asset_id: String,
+    /// Number of synthetic asset quantums per unit of size (hex or decimal string).
+    pub stark_ex_resolution: String,
+    pub default_maker_fee_rate: String,
+    pub default_taker_fee_rate: String,
+    /// Leverage/margin tiers, ordered by position value.
+    #[serde(default)]
+    pub risk_tier_list: Vec<RiskTier>,
+}
+
+impl ContractMeta {
+    /// The tier a position of `position_value` (absolute notional) falls in;
+    /// `None` if it is above the last tier.
+    pub fn risk_tier(&self, position_value: Decimal) -> Option<&RiskTier> {
+        let mut tiers: Vec<_> = self.risk_tier_list.iter().collect();
+        tiers.sort_by_key(|t| t.tier);
+        tiers.into_iter().find(|t| position_value.abs() <= t.position_value_upper_bound)
+    }
+
+    /// Highest leverage allowed for a position of `position_value`.
+    pub fn max_leverage(&self, position_value: Decimal) -> Option<Decimal> {
+        self.risk_tier(position_value).map(|t| t.max_leverage)
+    }
+
+    /// Checks that `leverage` is allowed for a position of `position_value`.
+    pub fn check_leverage(&self, leverage: Decimal, position_value: Decimal) -> Result<(), LeverageError> {
+        if leverage <= Decimal::ZERO {
+            return Err(LeverageError::NotPositive(leverage));
+        }
+        let tier = self.risk_tier(position_value).ok_or(LeverageError::NoRiskTier {
+            contract_id: self.contract_id,
+            position_value,
+        })?;
+        if leverage > tier.max_leverage {
+            return Err(LeverageError::AboveTierMax {
+                contract_id: self.contract_id,
+                leverage,
+                max_leverage: tier.max_leverage,
+                tier: tier.tier,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// One step of a contract's risk ladder: larger positions get lower leverage
+/// and a higher maintenance margin rate.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskTier {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub tier: u64,
+    /// Inclusive upper bound on absolute position value for this tier.
+    pub position_value_upper_bound: Decimal,
+    pub max_leverage: Decimal,
+    pub maintenance_margin_rate: Decimal,
+}
+
+impl RiskTier {
+    /// Initial margin rate at the tier's maximum leverage (`1 / max_leverage`).
+    pub fn initial_margin_rate(&self) -> Decimal {
+        if self.max_leverage.is_zero() {
+            return Decimal::ONE;
+        }
+        Decimal::ONE / self.max_leverage
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum LeverageError {
+    #[error("Leverage must be positive, got {0}")]
+    NotPositive(Decimal),
+    #[error("Position value {position_value} on contract {contract_id} is above every risk tier")]
+    NoRiskTier { contract_id: u64, position_value: Decimal },
+    #[error("Leverage {leverage} exceeds tier {tier} max {max_leverage} on contract {contract_id}")]
+    AboveTierMax { contract_id: u64, leverage: Decimal, max_leverage: Decimal, tier: u64 },
+}
+
+/// The StarkEx collateral coin every contract settles in.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CollateralMeta {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub coin_id: u64,
+    pub coin_name: String,
+    pub stark_ex_asset_id: String,
+    pub stark_ex_resolution: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalMeta {
+    pub stark_ex_collateral_coin: CollateralMeta,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaData {
+    pub global: GlobalMeta,
+    pub contract_list: Vec<ContractMeta>,
+}
+
+impl MetaData {
+    pub fn contract(&self, contract_id: u64) -> Option<&ContractMeta> {
+        self.contract_list.iter().find(|c| c.contract_id == contract_id)
+    }
+
+    pub fn collateral(&self) -> &CollateralMeta {
+        &self.global.stark_ex_collateral_coin
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MarginMode {
+    #[default]
+    Cross,
+    Isolated,
+}
+
+/// Per-contract trading settings of an account.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TradeSetting {
+    /// Leverage chosen by the account; only meaningful when `is_set_max_leverage`.
+    pub max_leverage: String,
+    pub is_set_max_leverage: bool,
+    pub margin_mode: MarginMode,
+    pub maker_fee_rate: String,
+    pub taker_fee_rate: String,
+}
+
+/// Account configuration from `getAccountById`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AccountSettings {
+    pub default_trade_setting: TradeSetting,
+    /// Keyed by contract id (as a string, the way the API sends it).
+    pub contract_id_to_trade_setting: HashMap<String, TradeSetting>,
+}
+
+impl AccountSettings {
+    /// Settings for `contract_id`, falling back to the account default.
+    pub fn trade_setting(&self, contract_id: u64) -> &TradeSetting {
+        self.contract_id_to_trade_setting
+            .get(&contract_id.to_string())
+            .unwrap_or(&self.default_trade_setting)
+    }
+}
+
+/// Exchange-computed collateral figures for one coin.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CollateralAsset {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub coin_id: u64,
+    pub amount: String,
+    pub total_equity: String,
+    pub total_position_value: String,
+    pub initial_margin_requirement: String,
+    pub maintenance_margin_requirement: String,
+    pub available_amount: String,
+}
+
+/// Exchange-computed margin figures for one position.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PositionAsset {
+    #[serde(deserialize_with = "de_u64_from_str_or_num")]
+    pub contract_id: u64,
+    pub position_value: String,
+    pub max_leverage: String,
+    pub initial_margin_requirement: String,
+    pub maintenance_margin_requirement: String,
+    pub liquidate_price: String,
+}
+
+/// Margin requirements from `getAccountAsset`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AccountAsset {
+    #[serde(rename = "collateralAssetModelList")]
+    pub collateral_list: Vec<CollateralAsset>,
+    #[serde(rename = "positionAssetList")]
+    pub position_list: Vec<PositionAsset>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leverage_within_risk_tier() {
+        let contract: ContractMeta = serde_json::from_value(serde_json::json!({
+            "contractId": "10000001",
+            "contractName": "BTCUSDT",
+            "tickSize": "0.1",
+            "stepSize": "0.001",
+            "starkExSyntheticAssetId": "0x1",
+            "starkExResolution": "0x2540be400",
+            "defaultMakerFeeRate": "0.0002",
+            "defaultTakerFeeRate": "0.0005",
+            "riskTierList": [
+                { "tier": "2", "positionValueUpperBound": "1000000", "maxLeverage": "50", "maintenanceMarginRate": "0.01" },
+                { "tier": "1", "positionValueUpperBound": "100000", "maxLeverage": "100", "maintenanceMarginRate": "0.005" },
+            ],
+        })).unwrap();
+
+        let tier = contract.risk_tier(Decimal::from(-50_000)).unwrap();
+        assert_eq!(tier.tier, 1);
+        assert_eq!(tier.initial_margin_rate(), Decimal::new(1, 2));
+        assert!(contract.check_leverage(Decimal::from(100), Decimal::from(50_000)).is_ok());
+        assert!(matches!(
+            contract.check_leverage(Decimal::from(100), Decimal::from(200_000)),
+            Err(LeverageError::AboveTierMax { tier: 2, .. })
+        ));
+        assert!(matches!(
+            contract.check_leverage(Decimal::from(10), Decimal::from(2_000_000)),
+            Err(LeverageError::NoRiskTier { .. })
+        ));
+        assert!(contract.check_leverage(Decimal::ZERO, Decimal::ZERO).is_err());
+    }
 }
@@ -0,0 +1,260 @@
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+// Random nonces remembered per account for collision checks.
+const RANDOM_HISTORY: usize = 100_000;
+// Monotonic / time-derived nonces are persisted in blocks of this size, so the
+// store is written once per block instead of once per nonce.
+const DEFAULT_RESERVE_BLOCK: u32 = 1_000;
+
+#[derive(Error, Debug)]
+pub enum NonceError {
+    #[error("Nonce space exhausted for account {0}")]
+    Exhausted(u64),
+    #[error("Nonce store error: {0}")]
+    StoreError(String),
+    #[error("{0:?} nonces cannot be persisted, use Monotonic or TimeDerived with a store")]
+    NotPersistable(NonceStrategy),
+}
+
+/// How `NonceManager` picks the next 32-bit L2 nonce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceStrategy {
+    /// Uniformly random, re-drawn on collision with a recently issued nonce.
+    /// The collision history is in memory only and does not survive a restart.
+    Random,
+    /// `last + 1`, starting from 1 (or from the persisted high-water mark).
+    Monotonic,
+    /// Unix seconds, bumped past the last issued nonce when several are
+    /// requested within the same second. Bursts run ahead of the clock, so a
+    /// quick restart can reissue nonces unless a `NonceStore` (e.g.
+    /// `FileNonceStore`) is configured with `with_store`.
+    TimeDerived,
+}
+
+/// Persists the per-account nonce high-water mark across restarts.
+pub trait NonceStore: Send + Sync {
+    fn load(&self, account_id: u64) -> Result<Option<u32>, NonceError>;
+    fn save(&self, account_id: u64, high_water_mark: u32) -> Result<(), NonceError>;
+}
+
+/// `NonceStore` backed by a small JSON file (`{"<accountId>": <highWaterMark>}`).
+pub struct FileNonceStore {
+    path: PathBuf,
+}
+
+impl FileNonceStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read_all(&self) -> Result<HashMap<u64, u32>, NonceError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| NonceError::StoreError(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(NonceError::StoreError(e.to_string())),
+        }
+    }
+}
+
+impl NonceStore for FileNonceStore {
+    fn load(&self, account_id: u64) -> Result<Option<u32>, NonceError> {
+        Ok(self.read_all()?.get(&account_id).copied())
+    }
+
+    fn save(&self, account_id: u64, high_water_mark: u32) -> Result<(), NonceError> {
+        let mut all = self.read_all()?;
+        all.insert(account_id, high_water_mark);
+        let contents = serde_json::to_string(&all).map_err(|e| NonceError::StoreError(e.to_string()))?;
+        // Write-then-rename so a crash never leaves a truncated file behind.
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, contents).map_err(|e| NonceError::StoreError(e.to_string()))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| NonceError::StoreError(e.to_string()))
+    }
+}
+
+#[derive(Default)]
+struct AccountNonces {
+    loaded: bool,
+    last: u32,
+    // Highest nonce covered by the persisted reservation.
+    reserved: u32,
+    recent: HashSet<u32>,
+    recent_order: VecDeque<u32>,
+}
+
+/// Hands out unique 32-bit L2 nonces per account, safe to share across tasks.
+pub struct NonceManager {
+    strategy: NonceStrategy,
+    store: Option<Box<dyn NonceStore>>,
+    reserve_block: u32,
+    accounts: Mutex<HashMap<u64, AccountNonces>>,
+}
+
+/// `Random`, which keeps no state across restarts; configure `Monotonic` or
+/// `TimeDerived` with a store when reissuing a nonce after a restart matters.
+impl Default for NonceManager {
+    fn default() -> Self {
+        Self::new(NonceStrategy::Random)
+    }
+}
+
+impl NonceManager {
+    pub fn new(strategy: NonceStrategy) -> Self {
+        Self {
+            strategy,
+            store: None,
+            reserve_block: DEFAULT_RESERVE_BLOCK,
+            accounts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Persists high-water marks so a restart never reissues a nonce.
+    /// Fails for `Random`, which has no high-water mark to persist.
+    pub fn with_store(mut self, store: impl NonceStore + 'static) -> Result<Self, NonceError> {
+        if self.strategy == NonceStrategy::Random {
+            return Err(NonceError::NotPersistable(self.strategy));
+        }
+        self.store = Some(Box::new(store));
+        Ok(self)
+    }
+
+    pub fn with_reserve_block(mut self, reserve_block: u32) -> Self {
+        self.reserve_block = reserve_block.max(1);
+        self
+    }
+
+    pub fn strategy(&self) -> NonceStrategy {
+        self.strategy
+    }
+
+    /// Allocates the next nonce for `account_id`.
+    pub fn next(&self, account_id: u64) -> Result<u32, NonceError> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let state = accounts.entry(account_id).or_default();
+
+        if !state.loaded {
+            if let Some(store) = &self.store
+                && let Some(mark) = store.load(account_id)?
+            {
+                state.last = mark;
+                state.reserved = mark;
+            }
+            state.loaded = true;
+        }
+
+        let nonce = match self.strategy {
+            NonceStrategy::Random => {
+                let mut rng = rand::thread_rng();
+                loop {
+                    let candidate: u32 = rng.r#gen();
+                    if candidate != 0 && !state.recent.contains(&candidate) {
+                        break candidate;
+                    }
+                }
+            }
+            NonceStrategy::Monotonic => state.last.checked_add(1).ok_or(NonceError::Exhausted(account_id))?,
+            NonceStrategy::TimeDerived => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                let now = u32::try_from(now).map_err(|_| NonceError::Exhausted(account_id))?;
+                let bumped = state.last.checked_add(1).ok_or(NonceError::Exhausted(account_id))?;
+                now.max(bumped)
+            }
+        };
+
+        match self.strategy {
+            NonceStrategy::Random => {
+                state.recent.insert(nonce);
+                state.recent_order.push_back(nonce);
+                if state.recent_order.len() > RANDOM_HISTORY
+                    && let Some(old) = state.recent_order.pop_front()
+                {
+                    state.recent.remove(&old);
+                }
+            }
+            NonceStrategy::Monotonic | NonceStrategy::TimeDerived => {
+                if nonce > state.reserved
+                    && let Some(store) = &self.store
+                {
+                    let reserved = nonce.saturating_add(self.reserve_block - 1);
+                    store.save(account_id, reserved)?;
+                    state.reserved = reserved;
+                }
+            }
+        }
+        state.last = nonce;
+        Ok(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_concurrent_nonces_are_unique() {
+        for strategy in [NonceStrategy::Random, NonceStrategy::Monotonic, NonceStrategy::TimeDerived] {
+            let manager = Arc::new(NonceManager::new(strategy));
+            let handles: Vec<_> = (0..8).map(|_| {
+                let manager = manager.clone();
+                std::thread::spawn(move || (0..500).map(|_| manager.next(1).unwrap()).collect::<Vec<_>>())
+            }).collect();
+
+            let mut seen = HashSet::new();
+            for handle in handles {
+                for nonce in handle.join().unwrap() {
+                    assert!(seen.insert(nonce), "{:?} reissued {}", strategy, nonce);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_monotonic_nonces_survive_restart() {
+        let path = std::env::temp_dir().join(format!("edgex-nonce-test-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let manager = NonceManager::new(NonceStrategy::Monotonic)
+            .with_store(FileNonceStore::new(&path))
+            .unwrap()
+            .with_reserve_block(10);
+        let last = (0..25).map(|_| manager.next(7).unwrap()).last().unwrap();
+        assert_eq!(last, 25);
+        assert_eq!(manager.next(8).unwrap(), 1);
+
+        let restarted = NonceManager::new(NonceStrategy::Monotonic).with_store(FileNonceStore::new(&path)).unwrap();
+        assert!(restarted.next(7).unwrap() > last);
+
+        assert!(matches!(
+            NonceManager::new(NonceStrategy::Random).with_store(FileNonceStore::new(&path)),
+            Err(NonceError::NotPersistable(NonceStrategy::Random))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_time_derived_burst_survives_restart() {
+        let path = std::env::temp_dir().join(format!("edgex-nonce-time-test-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // A burst runs well past the current second.
+        let manager = NonceManager::new(NonceStrategy::TimeDerived)
+            .with_store(FileNonceStore::new(&path))
+            .unwrap()
+            .with_reserve_block(10);
+        let last = (0..2_000).map(|_| manager.next(7).unwrap()).last().unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        assert!(last > now + 1_000);
+
+        let restarted = NonceManager::new(NonceStrategy::TimeDerived).with_store(FileNonceStore::new(&path)).unwrap();
+        assert!(restarted.next(7).unwrap() > last);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
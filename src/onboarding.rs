@@ -0,0 +1,280 @@
+use crate::client::{parse_data, read_json, ClientError, EdgeXClient, BASE_URL};
+use crate::secret::{StarkSecretKey, EC_ORDER};
+use crate::signature::{SignatureError, SignatureManager};
+use crate::trading::check_response;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip712::TypedData;
+use num_bigint::BigUint;
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use starknet_types_core::felt::Felt;
+use std::sync::Arc;
+
+const ONBOARD_PATH: &str = "/api/v1/public/user/onboard";
+const GET_ACCOUNT_PAGE_PATH: &str = "/api/v1/private/account/getAccountPage";
+const CREATE_ACCOUNT_PATH: &str = "/api/v1/private/account/createAccount";
+
+/// `register` response code when the L2 key is already bound to this L1 address.
+pub const ALREADY_REGISTERED: &str = "USER_ALREADY_REGISTERED";
+
+/// Settings that end up in the signed onboarding payloads.
+#[derive(Debug, Clone)]
+pub struct OnboardingConfig {
+    pub base_url: String,
+    pub env_id: String,
+    pub chain_id: u64,
+    /// Label used when deriving the L2 key; different labels give different keys.
+    pub client_account_id: String,
+}
+
+impl Default for OnboardingConfig {
+    fn default() -> Self {
+        Self {
+            base_url: BASE_URL.to_string(),
+            env_id: "mainnet".to_string(),
+            chain_id: 1,
+            client_account_id: "main".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OnboardRequest {
+    pub ethereum_address: String,
+    pub l2_key: String,
+    pub l2_key_y_coordinate: String,
+    pub client_account_id: String,
+    pub onboarding_signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    #[serde(deserialize_with = "crate::model::de_u64_from_str_or_num")]
+    pub id: u64,
+    pub ethereum_address: String,
+    pub l2_key: String,
+    pub client_account_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountPage {
+    #[serde(rename = "dataList", default)]
+    data_list: Vec<AccountInfo>,
+}
+
+/// Provisions a trading account from an L1 (Ethereum) key.
+///
+/// The flow is:
+/// 1. personal-sign the key derivation message and grind the signature into a Stark key,
+/// 2. EIP-712 sign the onboarding payload and register the derived Stark public key,
+/// 3. look up the account bound to that key (creating it if needed),
+/// 4. hand back an `EdgeXClient` bound to that account.
+pub struct Onboarding {
+    wallet: LocalWallet,
+    config: OnboardingConfig,
+    http: Client,
+}
+
+impl Onboarding {
+    pub fn new(l1_private_key_hex: &str, config: OnboardingConfig) -> Result<Self, ClientError> {
+        let wallet: LocalWallet = l1_private_key_hex.trim_start_matches("0x")
+            .parse()
+            .map_err(SignatureError::WalletError)?;
+        let wallet = wallet.with_chain_id(config.chain_id);
+        let http = Client::builder().build()?;
+        Ok(Self { wallet, config, http })
+    }
+
+    pub fn l1_address(&self) -> String {
+        format!("{:?}", self.wallet.address())
+    }
+
+    /// Message personal-signed by the L1 key to derive the L2 key.
+    pub fn key_derivation_message(&self) -> String {
+        format!(
+            "name: edgeX\nenvId: {}\naction: L2 Key\nonlySignOn: {}\nclientAccountId: {}",
+            self.config.env_id, self.config.base_url, self.config.client_account_id
+        )
+    }
+
+    /// Derives the L2 (Stark) private key from the L1 key.
+    /// Deterministic: the same L1 key and config always give the same L2 key.
+    pub async fn derive_l2_private_key(&self) -> Result<StarkSecretKey, ClientError> {
+        let signature = self.wallet
+            .sign_message(self.key_derivation_message())
+            .await
+            .map_err(SignatureError::WalletError)?;
+        let mut r = [0u8; 32];
+        signature.r.to_big_endian(&mut r);
+        let seed = zeroize::Zeroizing::new(ethers::utils::keccak256(r));
+        Ok(StarkSecretKey::from_felt(&grind_key(seed.as_ref()))?)
+    }
+
+    /// EIP-712 typed data signed to prove ownership of the L1 address when registering.
+    pub fn onboarding_typed_data(&self, l2_key: &Felt) -> Result<TypedData, ClientError> {
+        let typed = serde_json::json!({
+            "domain": {
+                "name": "edgeX",
+                "version": "1",
+                "chainId": self.config.chain_id,
+            },
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                ],
+                "Onboarding": [
+                    { "name": "action", "type": "string" },
+                    { "name": "onlySignOn", "type": "string" },
+                    { "name": "l2Key", "type": "string" },
+                ],
+            },
+            "primaryType": "Onboarding",
+            "message": {
+                "action": "edgeX Onboard",
+                "onlySignOn": self.config.base_url,
+                "l2Key": format!("{:#x}", l2_key),
+            },
+        });
+        serde_json::from_value(typed).map_err(|e| ClientError::ApiError(e.to_string()))
+    }
+
+    /// Registers the Stark public key derived from `l2_private_key` against the L1 address.
+    /// Returns the raw response; a repeat registration has code `ALREADY_REGISTERED`.
+    pub async fn register(&self, l2_private_key: &StarkSecretKey) -> Result<Value, ClientError> {
+        let l2_point = crate::signature::public_key_point(&l2_private_key.expose_felt());
+        let l2_key = l2_point.x();
+        let typed = self.onboarding_typed_data(&l2_key)?;
+        let signature = self.wallet
+            .sign_typed_data(&typed)
+            .await
+            .map_err(SignatureError::WalletError)?;
+
+        let req = OnboardRequest {
+            ethereum_address: self.l1_address(),
+            l2_key: format!("{:#x}", l2_key),
+            l2_key_y_coordinate: format!("{:#x}", l2_point.y()),
+            client_account_id: self.config.client_account_id.clone(),
+            onboarding_signature: format!("0x{}", signature),
+        };
+
+        let url = format!("{}{}", self.config.base_url, ONBOARD_PATH);
+        let res = self.http.post(&url)
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .json(&req)
+            .send()
+            .await?;
+        read_json(res).await
+    }
+
+    /// Runs the whole flow and returns a client bound to the account.
+    /// Safe to re-run: an existing registration and account are reused.
+    pub async fn onboard(&self) -> Result<EdgeXClient, ClientError> {
+        let l2_private_key = self.derive_l2_private_key().await?;
+        let registered = self.register(&l2_private_key).await?;
+        if registered["code"].as_str() != Some(ALREADY_REGISTERED) {
+            check_response(&registered)?;
+        }
+
+        let signer = SignatureManager::from_secret(l2_private_key);
+        let client = EdgeXClient::with_signer(Arc::new(signer), Some(self.config.base_url.clone()))?;
+        let account = match self.find_account(&client).await? {
+            Some(account) => account,
+            None => {
+                let json = client.post_private(CREATE_ACCOUNT_PATH, &serde_json::json!({
+                    "clientAccountId": self.config.client_account_id,
+                })).await?;
+                check_response(&json)?;
+                parse_data::<AccountInfo>(json)?
+            }
+        };
+        Ok(client.with_account_id(account.id))
+    }
+
+    async fn find_account(&self, client: &EdgeXClient) -> Result<Option<AccountInfo>, ClientError> {
+        let params = [("ethereumAddress", self.l1_address())];
+        let json = client.get_private(GET_ACCOUNT_PAGE_PATH, &params).await?;
+        check_response(&json)?;
+        let page: AccountPage = parse_data(json)?;
+        Ok(page.data_list.into_iter().find(|a| a.client_account_id == self.config.client_account_id))
+    }
+}
+
+/// StarkEx key grinding: hash `seed || index` until the result is below the largest
+/// multiple of the curve order that fits in 256 bits, then reduce. Avoids modulo bias.
+pub fn grind_key(seed: &[u8]) -> Felt {
+    let order = BigUint::from_bytes_be(&EC_ORDER);
+    let two_256 = BigUint::from(1u8) << 256;
+    let max_allowed = &two_256 - (&two_256 % &order);
+
+    let mut index: u64 = 0;
+    loop {
+        let mut hasher = Sha256::new();
+        hasher.update(seed);
+        let index_bytes = BigUint::from(index).to_bytes_be();
+        hasher.update(&index_bytes);
+        let key = BigUint::from_bytes_be(&hasher.finalize());
+        if key < max_allowed {
+            let key = key % &order;
+            let mut bytes = [0u8; 32];
+            let raw = key.to_bytes_be();
+            bytes[32 - raw.len()..].copy_from_slice(&raw);
+            return Felt::from_bytes_be(&bytes);
+        }
+        index += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockExchange;
+
+    #[tokio::test]
+    async fn test_l2_key_derivation_is_deterministic() {
+        let key = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let onboarding = Onboarding::new(key, OnboardingConfig::default()).unwrap();
+
+        let first = onboarding.derive_l2_private_key().await.unwrap().expose_felt();
+        let second = onboarding.derive_l2_private_key().await.unwrap().expose_felt();
+        assert_eq!(first, second);
+
+        let other = Onboarding::new(key, OnboardingConfig {
+            client_account_id: "sub".to_string(),
+            ..Default::default()
+        }).unwrap();
+        assert_ne!(first, other.derive_l2_private_key().await.unwrap().expose_felt());
+
+        let typed = onboarding.onboarding_typed_data(&starknet_crypto::get_public_key(&first)).unwrap();
+        assert_eq!(typed.primary_type, "Onboarding");
+    }
+
+    #[tokio::test]
+    async fn test_onboard_against_mock_exchange() {
+        let mock = MockExchange::start().await.unwrap();
+        let key = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let config = OnboardingConfig { base_url: mock.base_url(), ..Default::default() };
+        let onboarding = Onboarding::new(key, config.clone()).unwrap();
+
+        let client = onboarding.onboard().await.unwrap();
+        let account_id = client.account_id().unwrap();
+        assert!(client.get_open_orders(account_id).await.unwrap().is_empty());
+
+        // Re-running finds the registration and the account instead of failing.
+        let l2_key = onboarding.derive_l2_private_key().await.unwrap();
+        assert_eq!(onboarding.register(&l2_key).await.unwrap()["code"], ALREADY_REGISTERED);
+        assert_eq!(onboarding.onboard().await.unwrap().account_id(), Some(account_id));
+
+        // Another label derives another key and gets its own account.
+        let sub = Onboarding::new(key, OnboardingConfig { client_account_id: "sub".to_string(), ..config }).unwrap();
+        let sub_id = sub.onboard().await.unwrap().account_id().unwrap();
+        assert_ne!(sub_id, account_id);
+        assert_eq!(mock.auth_failures(), 0);
+    }
+}
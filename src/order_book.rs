@@ -0,0 +1,208 @@
+use crate::model::{DepthUpdate, OrderSide, PriceLevel};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum OrderBookError {
+    #[error("{field} is not a valid decimal: {value:?}")]
+    InvalidDecimal { field: &'static str, value: String },
+    #[error("Update for contract {got} applied to book of contract {expected}")]
+    WrongContract { expected: u64, got: u64 },
+}
+
+/// Local L2 book for one contract, built from `depth` snapshots and changes.
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    pub contract_id: u64,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+}
+
+impl OrderBook {
+    pub fn new(contract_id: u64) -> Self {
+        Self { contract_id, ..Default::default() }
+    }
+
+    pub fn apply(&mut self, update: &DepthUpdate) -> Result<(), OrderBookError> {
+        if update.contract_id != self.contract_id {
+            return Err(OrderBookError::WrongContract { expected: self.contract_id, got: update.contract_id });
+        }
+        // Parse everything first so a bad level leaves the book untouched.
+        let bids = parse_levels(&update.bids)?;
+        let asks = parse_levels(&update.asks)?;
+        if update.is_snapshot() {
+            self.bids.clear();
+            self.asks.clear();
+        }
+        for (book, levels) in [(&mut self.bids, bids), (&mut self.asks, asks)] {
+            for (price, size) in levels {
+                if size.is_zero() {
+                    book.remove(&price);
+                } else {
+                    book.insert(price, size);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn best_bid(&self) -> Option<(Decimal, Decimal)> {
+        self.bids.iter().next_back().map(|(p, s)| (*p, *s))
+    }
+
+    pub fn best_ask(&self) -> Option<(Decimal, Decimal)> {
+        self.asks.iter().next().map(|(p, s)| (*p, *s))
+    }
+
+    pub fn mid(&self) -> Option<Decimal> {
+        Some((self.best_bid()?.0 + self.best_ask()?.0) / Decimal::TWO)
+    }
+
+    /// Bids from best to worst.
+    pub fn bids(&self) -> impl Iterator<Item = (Decimal, Decimal)> + '_ {
+        self.bids.iter().rev().map(|(p, s)| (*p, *s))
+    }
+
+    /// Asks from best to worst.
+    pub fn asks(&self) -> impl Iterator<Item = (Decimal, Decimal)> + '_ {
+        self.asks.iter().map(|(p, s)| (*p, *s))
+    }
+
+    /// Resting size at `price` on `side` (bids for `Buy`).
+    pub fn size_at(&self, side: OrderSide, price: Decimal) -> Decimal {
+        let book = match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        };
+        book.get(&price).copied().unwrap_or_default()
+    }
+
+    /// Levels an aggressive order on `side` would trade against, best first,
+    /// stopping at `limit` if given.
+    pub fn opposite_levels(&self, side: OrderSide, limit: Option<Decimal>) -> Vec<(Decimal, Decimal)> {
+        let levels: Box<dyn Iterator<Item = (Decimal, Decimal)>> = match side {
+            OrderSide::Buy => Box::new(self.asks()),
+            OrderSide::Sell => Box::new(self.bids()),
+        };
+        levels
+            .take_while(|(price, _)| match (side, limit) {
+                (_, None) => true,
+                (OrderSide::Buy, Some(limit)) => *price <= limit,
+                (OrderSide::Sell, Some(limit)) => *price >= limit,
+            })
+            .collect()
+    }
+
+    /// Size available to an aggressive order on `side` up to `limit`.
+    pub fn available(&self, side: OrderSide, limit: Option<Decimal>) -> Decimal {
+        self.opposite_levels(side, limit).iter().map(|(_, size)| *size).sum()
+    }
+
+    /// Walks the opposite side for up to `size`, removing the liquidity taken.
+    /// Returns the `(price, size)` executions.
+    pub fn take(&mut self, side: OrderSide, limit: Option<Decimal>, size: Decimal) -> Vec<(Decimal, Decimal)> {
+        let mut remaining = size;
+        let mut executions = Vec::new();
+        for (price, level_size) in self.opposite_levels(side, limit) {
+            if remaining.is_zero() {
+                break;
+            }
+            let traded = remaining.min(level_size);
+            remaining -= traded;
+            executions.push((price, traded));
+            let book = match side {
+                OrderSide::Buy => &mut self.asks,
+                OrderSide::Sell => &mut self.bids,
+            };
+            if traded == level_size {
+                book.remove(&price);
+            } else {
+                book.insert(price, level_size - traded);
+            }
+        }
+        executions
+    }
+}
+
+fn parse_levels(levels: &[PriceLevel]) -> Result<Vec<(Decimal, Decimal)>, OrderBookError> {
+    levels.iter()
+        .map(|l| Ok((parse_decimal("price", &l.price)?, parse_decimal("size", &l.size)?)))
+        .collect()
+}
+
+fn parse_decimal(field: &'static str, value: &str) -> Result<Decimal, OrderBookError> {
+    Decimal::from_str(value.trim()).map_err(|_| OrderBookError::InvalidDecimal { field, value: value.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(depth_type: &str, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> DepthUpdate {
+        let levels = |levels: &[(&str, &str)]| {
+            levels.iter().map(|(price, size)| PriceLevel { price: price.to_string(), size: size.to_string() }).collect()
+        };
+        DepthUpdate { contract_id: 1, depth_type: depth_type.to_string(), bids: levels(bids), asks: levels(asks) }
+    }
+
+    fn levels(levels: impl Iterator<Item = (Decimal, Decimal)>) -> Vec<(String, String)> {
+        levels.map(|(price, size)| (price.to_string(), size.to_string())).collect()
+    }
+
+    #[test]
+    fn test_snapshots_and_changes() {
+        let mut book = OrderBook::new(1);
+        book.apply(&update("SNAPSHOT", &[("99", "1"), ("100", "2")], &[("101", "1"), ("102", "3")])).unwrap();
+        assert_eq!(book.best_bid(), Some((Decimal::from(100), Decimal::TWO)));
+        assert_eq!(book.mid(), Some(Decimal::new(1005, 1)));
+
+        // Changes update and remove single levels; a zero size removes.
+        book.apply(&update("CHANGED", &[("100", "0"), ("98", "4")], &[("101", "5")])).unwrap();
+        assert_eq!(levels(book.bids()), [("99".into(), "1".into()), ("98".into(), "4".into())]);
+        assert_eq!(levels(book.asks()), [("101".into(), "5".into()), ("102".into(), "3".into())]);
+        // Removing a level that isn't there is harmless.
+        book.apply(&update("CHANGED", &[("50", "0")], &[])).unwrap();
+        assert_eq!(book.bids().count(), 2);
+
+        // A snapshot replaces both sides.
+        book.apply(&update("SNAPSHOT", &[("90", "1")], &[])).unwrap();
+        assert_eq!(levels(book.bids()), [("90".into(), "1".into())]);
+        assert_eq!((book.best_ask(), book.mid()), (None, None));
+
+        // A bad level, wherever it is, leaves the book as it was.
+        book.apply(&update("SNAPSHOT", &[("95", "1")], &[("96", "x")])).unwrap_err();
+        assert_eq!(
+            book.apply(&update("CHANGED", &[("91", "1"), ("bad", "1")], &[])),
+            Err(OrderBookError::InvalidDecimal { field: "price", value: "bad".to_string() })
+        );
+        assert_eq!(levels(book.bids()), [("90".into(), "1".into())]);
+
+        let mut other = update("CHANGED", &[], &[]);
+        other.contract_id = 2;
+        assert_eq!(book.apply(&other), Err(OrderBookError::WrongContract { expected: 1, got: 2 }));
+    }
+
+    #[test]
+    fn test_take_walks_partial_levels() {
+        let mut book = OrderBook::new(1);
+        book.apply(&update("SNAPSHOT", &[("99", "2")], &[("101", "1"), ("102", "2"), ("103", "5")])).unwrap();
+        assert_eq!(book.available(OrderSide::Buy, Some(Decimal::from(102))), Decimal::from(3));
+
+        // Clears 101, takes part of 102, and leaves the rest resting.
+        let executions = book.take(OrderSide::Buy, None, Decimal::new(15, 1));
+        assert_eq!(executions, [(Decimal::from(101), Decimal::ONE), (Decimal::from(102), Decimal::new(5, 1))]);
+        assert_eq!(levels(book.asks()), [("102".into(), "1.5".into()), ("103".into(), "5".into())]);
+
+        // The limit stops the walk; what it can't reach stays.
+        let executions = book.take(OrderSide::Buy, Some(Decimal::from(102)), Decimal::from(10));
+        assert_eq!(executions, [(Decimal::from(102), Decimal::new(15, 1))]);
+        assert_eq!(levels(book.asks()), [("103".into(), "5".into())]);
+
+        // Sells walk the bids.
+        assert_eq!(book.take(OrderSide::Sell, Some(Decimal::from(100)), Decimal::ONE), []);
+        assert_eq!(book.take(OrderSide::Sell, None, Decimal::ONE), [(Decimal::from(99), Decimal::ONE)]);
+        assert_eq!(book.size_at(OrderSide::Buy, Decimal::from(99)), Decimal::ONE);
+    }
+}
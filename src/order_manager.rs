@@ -0,0 +1,627 @@
+use crate::client::{ClientError, EdgeXClient};
+use crate::model::{CreateOrderRequest, Fill, OpenOrder, OrderSide, OrderStatus};
+use crate::time_sync::local_millis;
+use crate::trading::response_order_id;
+use crate::websocket::AccountEvent;
+use futures_util::Stream;
+use rand::Rng;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+const CHANGE_CHANNEL_CAPACITY: usize = 1024;
+const DEFAULT_RECONCILE_GRACE: Duration = Duration::from_secs(10);
+const DEFAULT_TERMINAL_RETENTION: Duration = Duration::from_secs(10 * 60);
+// Inserts between sweeps of expired terminal orders, besides the one in `reconcile`.
+const PRUNE_INTERVAL: u64 = 1024;
+// Fills for orders not bound to an exchange id yet, oldest dropped first.
+const MAX_PENDING_FILLS: usize = 10_000;
+// Starts every tagged client order id. Ids from `new_client_order_id` are
+// plain hex and never do, and `_` doesn't occur in UUIDs either.
+const STRATEGY_TAG_PREFIX: &str = "t_";
+const MAX_STRATEGY_TAG_LEN: usize = 16;
+
+/// Client order id for orders that don't carry one.
+pub fn new_client_order_id() -> String {
+    format!("{:016x}", rand::thread_rng().r#gen::<u64>())
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Invalid strategy tag {0:?}: expected 1 to 16 ASCII letters or digits")]
+pub struct InvalidStrategyTag(pub String);
+
+/// Checks that `tag` can go in a client order id and be read back by `strategy_tag`.
+pub fn validate_strategy_tag(tag: &str) -> Result<(), InvalidStrategyTag> {
+    if is_valid_strategy_tag(tag) {
+        Ok(())
+    } else {
+        Err(InvalidStrategyTag(tag.to_string()))
+    }
+}
+
+fn is_valid_strategy_tag(tag: &str) -> bool {
+    (1..=MAX_STRATEGY_TAG_LEN).contains(&tag.len()) && tag.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Client order id carrying a strategy tag, as `t_{tag}_{random}`, so fills can
+/// be attributed with `strategy_tag`. `tag` should pass `validate_strategy_tag`.
+pub fn tagged_client_order_id(tag: &str) -> String {
+    format!("{}{}_{}", STRATEGY_TAG_PREFIX, tag, new_client_order_id())
+}
+
+/// The strategy tag of a client order id made by `tagged_client_order_id`;
+/// `None` for any other id.
+pub fn strategy_tag(client_order_id: &str) -> Option<&str> {
+    let (tag, random) = client_order_id.strip_prefix(STRATEGY_TAG_PREFIX)?.split_once('_')?;
+    let generated = random.len() == 16 && random.chars().all(|c| c.is_ascii_hexdigit());
+    (generated && is_valid_strategy_tag(tag)).then_some(tag)
+}
+
+/// Client-side view of one order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedOrder {
+    pub account_id: u64,
+    /// Exchange order id, known once the order is acknowledged.
+    pub order_id: Option<u64>,
+    pub client_order_id: Option<String>,
+    pub contract_id: u64,
+    pub side: OrderSide,
+    pub price: String,
+    pub size: String,
+    pub filled_size: String,
+    pub status: OrderStatus,
+    pub reject_reason: Option<String>,
+    pub created_at_ms: u64,
+    pub updated_at_ms: u64,
+}
+
+/// Emitted whenever an order's status or filled size changes.
+#[derive(Debug, Clone)]
+pub struct OrderChange {
+    pub order: TrackedOrder,
+    /// `None` for orders seen for the first time.
+    pub previous_status: Option<OrderStatus>,
+}
+
+struct Entry {
+    order: TrackedOrder,
+    fill_ids: HashSet<u64>,
+    filled_from_fills: Decimal,
+}
+
+#[derive(Default)]
+struct State {
+    next_key: u64,
+    entries: HashMap<u64, Entry>,
+    by_order_id: HashMap<u64, u64>,
+    by_client_id: HashMap<String, u64>,
+    /// Fills that arrived before their order's id was known.
+    pending_fills: VecDeque<Fill>,
+}
+
+/// Tracks every order submitted through an `EdgeXClient` through
+/// pending-new → open → partially filled → filled / cancelled / rejected / expired.
+///
+/// Updates can arrive in any order from the REST response, the private
+/// websocket and `get_open_orders` reconciliation. Terminal states are sticky
+/// and filled size only ever grows, so a stale update can't move an order backwards.
+pub struct OrderManager {
+    state: Mutex<State>,
+    changes: broadcast::Sender<OrderChange>,
+    reconcile_grace: Duration,
+    terminal_retention: Duration,
+}
+
+impl Default for OrderManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderManager {
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_CHANNEL_CAPACITY);
+        Self {
+            state: Mutex::new(State::default()),
+            changes,
+            reconcile_grace: DEFAULT_RECONCILE_GRACE,
+            terminal_retention: DEFAULT_TERMINAL_RETENTION,
+        }
+    }
+
+    /// Orders younger than this are left alone by `reconcile` when they are
+    /// missing from the open-orders snapshot, since the snapshot may predate them.
+    pub fn with_reconcile_grace(mut self, grace: Duration) -> Self {
+        self.reconcile_grace = grace;
+        self
+    }
+
+    /// How long filled, cancelled, rejected and expired orders stay queryable
+    /// before they are dropped (default 10 minutes).
+    pub fn with_terminal_retention(mut self, retention: Duration) -> Self {
+        self.terminal_retention = retention;
+        self
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OrderChange> {
+        self.changes.subscribe()
+    }
+
+    /// Change notifications as a `Stream`. A slow consumer skips what it missed.
+    pub fn changes(&self) -> impl Stream<Item = OrderChange> + use<> {
+        futures_util::stream::unfold(self.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(change) => return Some((change, rx)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Records an order about to be sent. Returns a handle for `record_ack` / `record_rejected`.
+    pub fn record_submitted(&self, req: &CreateOrderRequest) -> u64 {
+        let now = local_millis();
+        let order = TrackedOrder {
+            account_id: req.account_id,
+            order_id: None,
+            client_order_id: req.client_order_id.clone(),
+            contract_id: req.contract_id,
+            side: req.side,
+            price: req.price.clone(),
+            size: req.size.clone(),
+            filled_size: "0".to_string(),
+            status: OrderStatus::PendingNew,
+            reject_reason: None,
+            created_at_ms: now,
+            updated_at_ms: now,
+        };
+        let mut state = self.state.lock().unwrap();
+        let key = insert(&mut state, order.clone());
+        if key.is_multiple_of(PRUNE_INTERVAL) {
+            self.prune(&mut state);
+        }
+        drop(state);
+        let _ = self.changes.send(OrderChange { order, previous_status: None });
+        key
+    }
+
+    /// Applies the `createOrder` response: binds the exchange order id, or
+    /// rejects the order if the response carries a non-success `code`.
+    pub fn record_ack(&self, handle: u64, response: &Value) {
+        if let Some(code) = response["code"].as_str()
+            && code != "SUCCESS"
+        {
+            let reason = response["msg"].as_str().unwrap_or(code).to_string();
+            self.record_rejected(handle, &reason);
+            return;
+        }
+        let order_id = response_order_id(response);
+        let mut state = self.state.lock().unwrap();
+        if let Some(order_id) = order_id
+            && state.entries.contains_key(&handle)
+        {
+            state.by_order_id.insert(order_id, handle);
+            let entry = state.entries.get_mut(&handle).unwrap();
+            entry.order.order_id = Some(order_id);
+            let previous = entry.order.status;
+            let change = transition(entry, OrderStatus::Open, None);
+            let early_fills = take_pending_fills(&mut state, order_id);
+            drop(state);
+            self.notify(change, previous);
+            for fill in early_fills {
+                self.apply_fill(&fill);
+            }
+        }
+    }
+
+    pub fn record_rejected(&self, handle: u64, reason: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.entries.get_mut(&handle) {
+            let previous = entry.order.status;
+            if !previous.is_terminal() {
+                entry.order.reject_reason = Some(reason.to_string());
+            }
+            let change = transition(entry, OrderStatus::Rejected, None);
+            drop(state);
+            self.notify(change, previous);
+        }
+    }
+
+    /// Merges an order update for `account_id` from the websocket or REST.
+    pub fn apply_order_update(&self, account_id: u64, update: &OpenOrder) {
+        let status = match OrderStatus::from_exchange(&update.status) {
+            Some(status) => status,
+            None => return,
+        };
+        let filled = Decimal::from_str(&update.filled_size).ok();
+
+        let mut state = self.state.lock().unwrap();
+        let (key, inserted) = match find(&state, Some(update.order_id), update.client_order_id.as_deref()) {
+            Some(key) => (key, false),
+            None => {
+                let now = local_millis();
+                let key = insert(&mut state, TrackedOrder {
+                    account_id,
+                    order_id: Some(update.order_id),
+                    client_order_id: update.client_order_id.clone(),
+                    contract_id: update.contract_id,
+                    side: update.side,
+                    price: update.price.clone(),
+                    size: update.size.clone(),
+                    filled_size: "0".to_string(),
+                    status: OrderStatus::PendingNew,
+                    reject_reason: None,
+                    created_at_ms: now,
+                    updated_at_ms: now,
+                });
+                (key, true)
+            }
+        };
+        state.by_order_id.insert(update.order_id, key);
+        let entry = state.entries.get_mut(&key).unwrap();
+        entry.order.order_id = Some(update.order_id);
+        let previous = entry.order.status;
+        let change = transition(entry, status, filled);
+        let change = if inserted {
+            change.or_else(|| Some(entry.order.clone()))
+        } else {
+            change
+        };
+        let early_fills = take_pending_fills(&mut state, update.order_id);
+        drop(state);
+        match change {
+            Some(order) if inserted => {
+                let _ = self.changes.send(OrderChange { order, previous_status: None });
+            }
+            change => self.notify(change, previous),
+        }
+        for fill in early_fills {
+            self.apply_fill(&fill);
+        }
+    }
+
+    /// Counts a fill towards its order. Fills are de-duplicated by id. A fill
+    /// for an order id not seen yet (e.g. ahead of the REST ack) is held until
+    /// `record_ack`, an order update or `reconcile` binds that id.
+    pub fn apply_fill(&self, fill: &Fill) {
+        let size = match Decimal::from_str(&fill.size) {
+            Ok(size) => size,
+            Err(_) => return,
+        };
+        let mut state = self.state.lock().unwrap();
+        let key = match state.by_order_id.get(&fill.order_id) {
+            Some(key) => *key,
+            None => {
+                if state.pending_fills.len() >= MAX_PENDING_FILLS {
+                    state.pending_fills.pop_front();
+                }
+                state.pending_fills.push_back(fill.clone());
+                return;
+            }
+        };
+        let entry = state.entries.get_mut(&key).unwrap();
+        if !entry.fill_ids.insert(fill.id) {
+            return;
+        }
+        entry.filled_from_fills += size;
+        let previous = entry.order.status;
+        let filled = entry.filled_from_fills;
+        let change = transition(entry, OrderStatus::PartiallyFilled, Some(filled));
+        drop(state);
+        self.notify(change, previous);
+    }
+
+    /// Applies an event from `account_id`'s private stream.
+    pub fn apply_event(&self, account_id: u64, event: &AccountEvent) {
+        match event {
+            AccountEvent::Order(order) => self.apply_order_update(account_id, order),
+            AccountEvent::Fill(fill) => self.apply_fill(fill),
+        }
+    }
+
+    /// Reconciles against `account_id`'s full open-orders snapshot (`get_open_orders`).
+    ///
+    /// Orders in the snapshot are merged as updates. Live tracked orders of the
+    /// account missing from it (and older than the grace period) are closed: as
+    /// filled if their filled size covers the order size, as cancelled
+    /// otherwise, and as rejected if they were never acknowledged. Other
+    /// accounts' orders are left alone. Expired terminal orders are dropped.
+    pub fn reconcile(&self, account_id: u64, open_orders: &[OpenOrder]) {
+        for order in open_orders {
+            self.apply_order_update(account_id, order);
+        }
+
+        let live: HashSet<u64> = open_orders.iter().map(|o| o.order_id).collect();
+        let cutoff = local_millis().saturating_sub(self.reconcile_grace.as_millis() as u64);
+        let mut changes = Vec::new();
+        let mut state = self.state.lock().unwrap();
+        for entry in state.entries.values_mut() {
+            let order = &entry.order;
+            if order.account_id != account_id || order.status.is_terminal() || order.created_at_ms > cutoff {
+                continue;
+            }
+            let closed = match order.order_id {
+                Some(order_id) if live.contains(&order_id) => continue,
+                Some(_) => {
+                    let filled = Decimal::from_str(&order.filled_size).unwrap_or_default();
+                    let size = Decimal::from_str(&order.size).unwrap_or_default();
+                    if !size.is_zero() && filled >= size {
+                        OrderStatus::Filled
+                    } else {
+                        OrderStatus::Cancelled
+                    }
+                }
+                None => {
+                    entry.order.reject_reason = Some("never acknowledged".to_string());
+                    OrderStatus::Rejected
+                }
+            };
+            let previous = entry.order.status;
+            if let Some(order) = transition(entry, closed, None) {
+                changes.push(OrderChange { order, previous_status: Some(previous) });
+            }
+        }
+        self.prune(&mut state);
+        drop(state);
+        for change in changes {
+            let _ = self.changes.send(change);
+        }
+    }
+
+    /// Fetches open orders for `account_id` and reconciles against them.
+    pub async fn reconcile_with(&self, client: &EdgeXClient, account_id: u64) -> Result<(), ClientError> {
+        let open_orders = client.get_open_orders(account_id).await?;
+        self.reconcile(account_id, &open_orders);
+        Ok(())
+    }
+
+    pub fn get(&self, order_id: u64) -> Option<TrackedOrder> {
+        let state = self.state.lock().unwrap();
+        state.by_order_id.get(&order_id).map(|key| state.entries[key].order.clone())
+    }
+
+    pub fn get_by_client_order_id(&self, client_order_id: &str) -> Option<TrackedOrder> {
+        let state = self.state.lock().unwrap();
+        state.by_client_id.get(client_order_id).map(|key| state.entries[key].order.clone())
+    }
+
+    /// Strategy tag of the order behind `fill`, if the order is tracked and tagged.
+    pub fn fill_strategy_tag(&self, fill: &Fill) -> Option<String> {
+        self.get(fill.order_id)?.client_order_id.as_deref().and_then(strategy_tag).map(str::to_string)
+    }
+
+    /// Non-terminal orders of `account_id`, optionally for a single contract.
+    pub fn open_orders(&self, account_id: u64, contract_id: Option<u64>) -> Vec<TrackedOrder> {
+        self.filter(|o| o.account_id == account_id && !o.status.is_terminal() && contract_id.is_none_or(|c| c == o.contract_id))
+    }
+
+    pub fn all(&self) -> Vec<TrackedOrder> {
+        self.filter(|_| true)
+    }
+
+    fn filter(&self, pred: impl Fn(&TrackedOrder) -> bool) -> Vec<TrackedOrder> {
+        let state = self.state.lock().unwrap();
+        let mut orders: Vec<_> = state.entries.values()
+            .map(|e| &e.order)
+            .filter(|o| pred(o))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|o| o.created_at_ms);
+        orders
+    }
+
+    /// Drops terminal orders last updated more than `terminal_retention` ago.
+    fn prune(&self, state: &mut State) {
+        let cutoff = local_millis().saturating_sub(self.terminal_retention.as_millis() as u64);
+        let expired: Vec<u64> = state.entries.iter()
+            .filter(|(_, e)| e.order.status.is_terminal() && e.order.updated_at_ms <= cutoff)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            let order = state.entries.remove(&key).unwrap().order;
+            if let Some(order_id) = order.order_id {
+                state.by_order_id.remove(&order_id);
+            }
+            if let Some(client_id) = &order.client_order_id {
+                state.by_client_id.remove(client_id);
+            }
+        }
+    }
+
+    fn notify(&self, change: Option<TrackedOrder>, previous: OrderStatus) {
+        if let Some(order) = change {
+            let _ = self.changes.send(OrderChange { order, previous_status: Some(previous) });
+        }
+    }
+}
+
+fn insert(state: &mut State, order: TrackedOrder) -> u64 {
+    let key = state.next_key;
+    state.next_key += 1;
+    if let Some(order_id) = order.order_id {
+        state.by_order_id.insert(order_id, key);
+    }
+    if let Some(client_id) = &order.client_order_id {
+        state.by_client_id.insert(client_id.clone(), key);
+    }
+    state.entries.insert(key, Entry {
+        order,
+        fill_ids: HashSet::new(),
+        filled_from_fills: Decimal::ZERO,
+    });
+    key
+}
+
+fn take_pending_fills(state: &mut State, order_id: u64) -> Vec<Fill> {
+    if !state.pending_fills.iter().any(|f| f.order_id == order_id) {
+        return Vec::new();
+    }
+    let (matched, rest): (Vec<Fill>, Vec<Fill>) = std::mem::take(&mut state.pending_fills)
+        .into_iter()
+        .partition(|f| f.order_id == order_id);
+    state.pending_fills = rest.into();
+    matched
+}
+
+fn find(state: &State, order_id: Option<u64>, client_order_id: Option<&str>) -> Option<u64> {
+    order_id.and_then(|id| state.by_order_id.get(&id))
+        .or_else(|| client_order_id.and_then(|id| state.by_client_id.get(id)))
+        .copied()
+}
+
+/// Applies `status` / `filled` to an order, returning the new snapshot if anything changed.
+fn transition(entry: &mut Entry, status: OrderStatus, filled: Option<Decimal>) -> Option<TrackedOrder> {
+    let order = &mut entry.order;
+    if order.status.is_terminal() {
+        return None;
+    }
+
+    let old_filled = Decimal::from_str(&order.filled_size).unwrap_or_default();
+    let new_filled = filled.map_or(old_filled, |f| f.max(old_filled));
+    let size = Decimal::from_str(&order.size).unwrap_or_default();
+
+    let new_status = match status {
+        OrderStatus::PendingNew | OrderStatus::Open | OrderStatus::PartiallyFilled
+            if !size.is_zero() && new_filled >= size => OrderStatus::Filled,
+        OrderStatus::PendingNew | OrderStatus::Open | OrderStatus::PartiallyFilled
+            if !new_filled.is_zero() => OrderStatus::PartiallyFilled,
+        OrderStatus::PendingNew if order.status != OrderStatus::PendingNew => order.status,
+        // A fill for an order we still think is pending means it is live.
+        OrderStatus::PartiallyFilled => OrderStatus::Open,
+        other => other,
+    };
+
+    if new_status == order.status && new_filled == old_filled {
+        return None;
+    }
+    order.status = new_status;
+    order.filled_size = new_filled.normalize().to_string();
+    order.updated_at_ms = local_millis();
+    Some(order.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(client_order_id: &str) -> CreateOrderRequest {
+        let mut req = CreateOrderRequest::unsigned(1, 10, OrderSide::Buy, "100", "2");
+        req.client_order_id = Some(client_order_id.to_string());
+        req
+    }
+
+    fn update(order_id: u64, status: &str, filled: &str) -> OpenOrder {
+        OpenOrder {
+            order_id,
+            client_order_id: Some("c1".to_string()),
+            contract_id: 10,
+            price: "100".to_string(),
+            size: "2".to_string(),
+            side: OrderSide::Buy,
+            status: status.to_string(),
+            filled_size: filled.to_string(),
+            remaining_size: String::new(),
+        }
+    }
+
+    fn fill(id: u64, size: &str) -> Fill {
+        Fill {
+            id,
+            order_id: 42,
+            contract_id: 10,
+            price: "100".to_string(),
+            size: size.to_string(),
+            side: OrderSide::Buy,
+            time: 0,
+            fee: "0".to_string(),
+            fee_asset_id: 0,
+            direction: None,
+        }
+    }
+
+    #[test]
+    fn test_order_lifecycle() {
+        let manager = OrderManager::new().with_reconcile_grace(Duration::ZERO);
+        let mut changes = manager.subscribe();
+
+        let handle = manager.record_submitted(&request("c1"));
+        assert_eq!(manager.get_by_client_order_id("c1").unwrap().status, OrderStatus::PendingNew);
+
+        // The websocket can beat the REST ack.
+        manager.apply_order_update(1, &update(42, "OPEN", "0"));
+        manager.record_ack(handle, &serde_json::json!({ "code": "SUCCESS", "data": { "orderId": "42" } }));
+        assert_eq!(manager.get(42).unwrap().status, OrderStatus::Open);
+
+        manager.apply_fill(&fill(1, "0.5"));
+        manager.apply_fill(&fill(1, "0.5"));
+        let order = manager.get(42).unwrap();
+        assert_eq!((order.status, order.filled_size.as_str()), (OrderStatus::PartiallyFilled, "0.5"));
+
+        // Stale update doesn't move the order backwards.
+        manager.apply_order_update(1, &update(42, "OPEN", "0"));
+        assert_eq!(manager.get(42).unwrap().status, OrderStatus::PartiallyFilled);
+
+        manager.apply_fill(&fill(2, "1.5"));
+        assert_eq!(manager.get(42).unwrap().status, OrderStatus::Filled);
+        manager.apply_order_update(1, &update(42, "CANCELED", "2"));
+        assert_eq!(manager.get(42).unwrap().status, OrderStatus::Filled);
+        assert!(manager.open_orders(1, None).is_empty());
+
+        let statuses: Vec<_> = std::iter::from_fn(|| changes.try_recv().ok()).map(|c| c.order.status).collect();
+        assert_eq!(statuses, vec![
+            OrderStatus::PendingNew,
+            OrderStatus::Open,
+            OrderStatus::PartiallyFilled,
+            OrderStatus::Filled,
+        ]);
+
+        // Only ids made by `tagged_client_order_id` carry a tag.
+        assert_eq!(strategy_tag(&tagged_client_order_id("mm")), Some("mm"));
+        assert_eq!(strategy_tag("550e8400-e29b-41d4-a716-446655440000"), None);
+        assert_eq!(strategy_tag(&new_client_order_id()), None);
+        assert!(validate_strategy_tag("a-b").is_err() && validate_strategy_tag("").is_err());
+    }
+
+    #[test]
+    fn test_reconcile_closes_missing_orders() {
+        let manager = OrderManager::new().with_reconcile_grace(Duration::ZERO);
+        let acked = manager.record_submitted(&request("a"));
+        manager.record_ack(acked, &serde_json::json!({ "data": { "orderId": 7 } }));
+        manager.record_submitted(&request("b"));
+        let rejected = manager.record_submitted(&request("c"));
+        manager.record_ack(rejected, &serde_json::json!({ "code": "INSUFFICIENT_MARGIN", "msg": "no margin" }));
+
+        manager.reconcile(1, &[]);
+        assert_eq!(manager.get(7).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(manager.get_by_client_order_id("b").unwrap().status, OrderStatus::Rejected);
+        let c = manager.get_by_client_order_id("c").unwrap();
+        assert_eq!((c.status, c.reject_reason.as_deref()), (OrderStatus::Rejected, Some("no margin")));
+
+        // A fill that beats the ack is held, then counted once the id is bound.
+        let early = manager.record_submitted(&request("d"));
+        manager.apply_fill(&fill(3, "0.5"));
+        manager.record_ack(early, &serde_json::json!({ "data": { "orderId": 42 } }));
+        assert_eq!(manager.get(42).unwrap().filled_size, "0.5");
+
+        // Another account's orders are neither listed nor closed by this one's snapshot.
+        let mut other = request("e");
+        other.account_id = 2;
+        manager.record_submitted(&other);
+        manager.reconcile(1, &[]);
+        assert!(manager.open_orders(1, None).is_empty());
+        assert_eq!(manager.open_orders(2, None).len(), 1);
+
+        // Closed orders are dropped once past the retention period.
+        let manager = manager.with_terminal_retention(Duration::ZERO);
+        manager.reconcile(2, &[]);
+        assert!(manager.all().is_empty());
+        assert!(manager.get(7).is_none());
+    }
+}
@@ -0,0 +1,475 @@
+use crate::client::ClientError;
+use crate::model::{
+    CancelOrderRequest, CreateOrderRequest, DepthUpdate, Fill, LiquidityRole, MetaData, OpenOrder, OrderSide,
+    OrderType, Position, PublicTrade, TimeInForce,
+};
+use crate::order_book::OrderBook;
+use crate::order_manager::new_client_order_id;
+use crate::position::ContractPosition;
+use crate::time_sync::local_millis;
+use crate::trading::TradingApi;
+use crate::websocket::{parse_depth_updates, parse_public_trades, AccountEvent, WsMessage};
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use futures_util::{Stream, StreamExt};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+const FIRST_ORDER_ID: u64 = 1;
+
+#[derive(Debug, Clone, Default)]
+pub struct PaperConfig {
+    /// Overrides the contract's `defaultMakerFeeRate`.
+    pub maker_fee_rate: Option<Decimal>,
+    /// Overrides the contract's `defaultTakerFeeRate`.
+    pub taker_fee_rate: Option<Decimal>,
+    /// Fill resting orders on public trades *at* their price, not only through
+    /// it. Optimistic, since it assumes we were first in the queue.
+    pub fill_on_touch: bool,
+}
+
+struct PaperOrder {
+    order: OpenOrder,
+    price: Decimal,
+    size: Decimal,
+    filled: Decimal,
+}
+
+impl PaperOrder {
+    fn remaining(&self) -> Decimal {
+        self.size - self.filled
+    }
+
+    fn is_open(&self) -> bool {
+        matches!(self.order.status.as_str(), "OPEN" | "PARTIALLY_FILLED")
+    }
+}
+
+#[derive(Default)]
+struct PaperPosition {
+    position: ContractPosition,
+    fees: Decimal,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    books: HashMap<u64, OrderBook>,
+    orders: Vec<PaperOrder>,
+    fills: Vec<Fill>,
+    positions: HashMap<u64, PaperPosition>,
+}
+
+/// Simulated execution against the public order book.
+///
+/// Feed it public `depth` and `trades` messages (see `run`). Marketable orders
+/// take liquidity from the local book as taker; the rest rests and fills as
+/// maker when the book crosses it or public trades print through it, so
+/// partial fills happen naturally. Order and fill events have the same shape
+/// as the private websocket's.
+///
+/// One account only: requests for other account ids see nothing.
+pub struct PaperExchange {
+    account_id: u64,
+    meta: MetaData,
+    config: PaperConfig,
+    state: Mutex<State>,
+    events: broadcast::Sender<AccountEvent>,
+}
+
+impl PaperExchange {
+    pub fn new(account_id: u64, meta: MetaData) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            account_id,
+            meta,
+            config: PaperConfig::default(),
+            state: Mutex::new(State { next_id: FIRST_ORDER_ID, ..Default::default() }),
+            events,
+        }
+    }
+
+    pub fn with_config(mut self, config: PaperConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AccountEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn book(&self, contract_id: u64) -> Option<OrderBook> {
+        self.state.lock().unwrap().books.get(&contract_id).cloned()
+    }
+
+    /// Routes a public websocket message to `apply_depth` / `apply_trade`.
+    pub fn apply_message(&self, msg: &WsMessage) {
+        for update in parse_depth_updates(msg) {
+            self.apply_depth(&update);
+        }
+        for trade in parse_public_trades(msg) {
+            self.apply_trade(&trade);
+        }
+    }
+
+    /// Consumes a public message stream (e.g. `EdgeXWebSocket::messages`) until it ends.
+    pub async fn run<S, E>(&self, messages: S)
+    where
+        S: Stream<Item = Result<WsMessage, E>>,
+    {
+        let mut messages = std::pin::pin!(messages);
+        while let Some(msg) = messages.next().await {
+            if let Ok(msg) = msg {
+                self.apply_message(&msg);
+            }
+        }
+    }
+
+    /// Updates the book, then fills resting orders it now crosses.
+    pub fn apply_depth(&self, update: &DepthUpdate) {
+        let mut state = self.state.lock().unwrap();
+        let book = state.books.entry(update.contract_id).or_insert_with(|| OrderBook::new(update.contract_id));
+        if book.apply(update).is_err() {
+            return;
+        }
+
+        let mut events = Vec::new();
+        let State { books, orders, .. } = &mut *state;
+        let book = books.get_mut(&update.contract_id).unwrap();
+        let mut executions = Vec::new();
+        for (index, order) in orders.iter_mut().enumerate() {
+            if !order.is_open() || order.order.contract_id != update.contract_id {
+                continue;
+            }
+            // Crossed by the book: fill as maker at our own price.
+            let taken: Decimal = book.take(order.order.side, Some(order.price), order.remaining())
+                .iter()
+                .map(|(_, size)| *size)
+                .sum();
+            if !taken.is_zero() {
+                executions.push((index, order.price, taken));
+            }
+        }
+        for (index, price, size) in executions {
+            self.execute(&mut state, index, price, size, LiquidityRole::Maker, &mut events);
+        }
+        drop(state);
+        self.publish(events);
+    }
+
+    /// Fills resting orders that a public trade printed through.
+    pub fn apply_trade(&self, trade: &PublicTrade) {
+        let (Ok(trade_price), Ok(trade_size)) = (Decimal::from_str(&trade.price), Decimal::from_str(&trade.size)) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let mut events = Vec::new();
+        let mut available = trade_size;
+        let candidates: Vec<usize> = state.orders.iter().enumerate()
+            .filter(|(_, o)| o.is_open() && o.order.contract_id == trade.contract_id)
+            .filter(|(_, o)| {
+                let through = match o.order.side {
+                    OrderSide::Buy => trade_price < o.price,
+                    OrderSide::Sell => trade_price > o.price,
+                };
+                through || (self.config.fill_on_touch && trade_price == o.price)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        for index in candidates {
+            if available.is_zero() {
+                break;
+            }
+            let (price, size) = {
+                let order = &state.orders[index];
+                (order.price, order.remaining().min(available))
+            };
+            available -= size;
+            self.execute(&mut state, index, price, size, LiquidityRole::Maker, &mut events);
+        }
+        drop(state);
+        self.publish(events);
+    }
+
+    fn fee_rate(&self, contract_id: u64, role: LiquidityRole) -> Decimal {
+        let (configured, default) = match role {
+            LiquidityRole::Maker => (self.config.maker_fee_rate, self.meta.contract(contract_id).map(|c| &c.default_maker_fee_rate)),
+            LiquidityRole::Taker => (self.config.taker_fee_rate, self.meta.contract(contract_id).map(|c| &c.default_taker_fee_rate)),
+        };
+        configured
+            .or_else(|| default.and_then(|rate| Decimal::from_str(rate).ok()))
+            .unwrap_or_default()
+    }
+
+    /// Records one execution of `orders[index]` and queues its events.
+    fn execute(&self, state: &mut State, index: usize, price: Decimal, size: Decimal, role: LiquidityRole, events: &mut Vec<AccountEvent>) {
+        let fill_id = state.next_id;
+        state.next_id += 1;
+
+        let order = &mut state.orders[index];
+        order.filled += size;
+        order.order.filled_size = order.filled.normalize().to_string();
+        order.order.remaining_size = order.remaining().normalize().to_string();
+        order.order.status = if order.remaining().is_zero() { "FILLED" } else { "PARTIALLY_FILLED" }.to_string();
+        let side = order.order.side;
+        let contract_id = order.order.contract_id;
+        let order_id = order.order.order_id;
+        let order_event = AccountEvent::Order(order.order.clone());
+
+        let fee = size * price * self.fee_rate(contract_id, role);
+        let fill = Fill {
+            id: fill_id,
+            order_id,
+            contract_id,
+            price: price.normalize().to_string(),
+            size: size.normalize().to_string(),
+            side,
+            time: local_millis(),
+            fee: fee.normalize().to_string(),
+            fee_asset_id: self.meta.collateral().coin_id,
+            direction: Some(role),
+        };
+        let signed_size = if side == OrderSide::Buy { size } else { -size };
+        let position = state.positions.entry(contract_id).or_default();
+        position.position.trade(signed_size, price);
+        position.fees += fee;
+        state.fills.push(fill.clone());
+
+        events.push(order_event);
+        events.push(AccountEvent::Fill(fill));
+    }
+
+    fn publish(&self, events: Vec<AccountEvent>) {
+        for event in events {
+            let _ = self.events.send(event);
+        }
+    }
+}
+
+fn success(order_id: u64) -> Value {
+    json!({ "code": "SUCCESS", "data": { "orderId": order_id.to_string() } })
+}
+
+fn parse_decimal(field: &str, value: &str) -> Result<Decimal, ClientError> {
+    Decimal::from_str(value.trim()).map_err(|_| ClientError::ApiError(format!("{} is not a valid decimal: {:?}", field, value)))
+}
+
+#[async_trait]
+impl TradingApi for PaperExchange {
+    /// Nothing is signed; the L2 fields are ignored.
+    async fn place_order(&self, req: CreateOrderRequest, meta: &MetaData) -> Result<Value, ClientError> {
+        if req.account_id != self.account_id {
+            return Ok(json!({ "code": "ACCOUNT_NOT_FOUND", "msg": "unknown account" }));
+        }
+        if meta.contract(req.contract_id).is_none() && self.meta.contract(req.contract_id).is_none() {
+            return Ok(json!({ "code": "CONTRACT_NOT_FOUND", "msg": "unknown contract" }));
+        }
+        let price = parse_decimal("price", &req.price)?;
+        let size = parse_decimal("size", &req.size)?;
+        if size <= Decimal::ZERO {
+            return Ok(json!({ "code": "INVALID_ORDER", "msg": format!("size {} is not positive", size) }));
+        }
+        if price.is_sign_negative() || (price.is_zero() && req.r#type != OrderType::Market) {
+            return Ok(json!({ "code": "INVALID_ORDER", "msg": format!("price {} is not valid for a {:?} order", price, req.r#type) }));
+        }
+        // Market orders use their price as the worst acceptable one, if set.
+        let limit = (price > Decimal::ZERO).then_some(price);
+
+        let mut state = self.state.lock().unwrap();
+        let order_id = state.next_id;
+        state.next_id += 1;
+        state.orders.push(PaperOrder {
+            order: OpenOrder {
+                order_id,
+                client_order_id: Some(req.client_order_id.clone().unwrap_or_else(new_client_order_id)),
+                contract_id: req.contract_id,
+                price: req.price.clone(),
+                size: req.size.clone(),
+                side: req.side,
+                status: "OPEN".to_string(),
+                filled_size: "0".to_string(),
+                remaining_size: req.size.clone(),
+            },
+            price,
+            size,
+            filled: Decimal::ZERO,
+        });
+        let index = state.orders.len() - 1;
+
+        let mut events = Vec::new();
+        let book = state.books.entry(req.contract_id).or_insert_with(|| OrderBook::new(req.contract_id));
+        let fill_or_kill = req.time_in_force == TimeInForce::Fok;
+        let executions = if fill_or_kill && book.available(req.side, limit) < size {
+            Vec::new()
+        } else {
+            book.take(req.side, limit, size)
+        };
+        for (exec_price, exec_size) in executions {
+            self.execute(&mut state, index, exec_price, exec_size, LiquidityRole::Taker, &mut events);
+        }
+
+        let order = &mut state.orders[index];
+        let rests = req.r#type == OrderType::Limit && req.time_in_force == TimeInForce::Gtc;
+        if !order.remaining().is_zero() && !rests {
+            order.order.status = "CANCELED".to_string();
+        }
+        // The final state of the order always goes out, even without fills.
+        if events.is_empty() || order.order.status == "CANCELED" {
+            events.push(AccountEvent::Order(order.order.clone()));
+        }
+        drop(state);
+        self.publish(events);
+        Ok(success(order_id))
+    }
+
+    async fn cancel_order(&self, req: &CancelOrderRequest) -> Result<Value, ClientError> {
+        let mut state = self.state.lock().unwrap();
+        let order = state.orders.iter_mut().find(|o| {
+            req.account_id == self.account_id
+                && (Some(o.order.order_id) == req.order_id
+                    || (req.client_order_id.is_some() && o.order.client_order_id == req.client_order_id))
+        });
+        let Some(order) = order else {
+            return Ok(json!({ "code": "ORDER_NOT_FOUND", "msg": "order not found" }));
+        };
+        if !order.is_open() {
+            return Ok(json!({ "code": "ORDER_NOT_OPEN", "msg": "order is not open" }));
+        }
+        order.order.status = "CANCELED".to_string();
+        let event = AccountEvent::Order(order.order.clone());
+        let order_id = order.order.order_id;
+        drop(state);
+        self.publish(vec![event]);
+        Ok(success(order_id))
+    }
+
+    async fn get_open_orders(&self, account_id: u64) -> Result<Vec<OpenOrder>, ClientError> {
+        if account_id != self.account_id {
+            return Ok(Vec::new());
+        }
+        let state = self.state.lock().unwrap();
+        Ok(state.orders.iter().filter(|o| o.is_open()).map(|o| o.order.clone()).collect())
+    }
+
+    async fn get_fills(&self, account_id: u64) -> Result<Vec<Fill>, ClientError> {
+        if account_id != self.account_id {
+            return Ok(Vec::new());
+        }
+        Ok(self.state.lock().unwrap().fills.clone())
+    }
+
+    async fn get_positions(&self, account_id: u64) -> Result<Vec<Position>, ClientError> {
+        if account_id != self.account_id {
+            return Ok(Vec::new());
+        }
+        let state = self.state.lock().unwrap();
+        let mut positions: Vec<_> = state.positions.iter()
+            .filter(|(_, p)| !p.position.net_size.is_zero())
+            .map(|(contract_id, p)| Position {
+                contract_id: *contract_id,
+                open_size: p.position.net_size.normalize().to_string(),
+                open_value: (p.position.net_size * p.position.avg_entry_price).normalize().to_string(),
+                open_fee: p.fees.normalize().to_string(),
+                funding_fee: "0".to_string(),
+            })
+            .collect();
+        positions.sort_by_key(|p| p.contract_id);
+        Ok(positions)
+    }
+
+    async fn account_events(&self, account_id: u64) -> Result<BoxStream<'static, AccountEvent>, ClientError> {
+        if account_id != self.account_id {
+            return Err(ClientError::ApiError(format!("paper exchange has no account {}", account_id)));
+        }
+        let events = futures_util::stream::unfold(self.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(events.boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::default_metadata;
+    use crate::model::PriceLevel;
+
+    const CONTRACT: u64 = 10000001;
+
+    fn level(price: &str, size: &str) -> PriceLevel {
+        PriceLevel { price: price.to_string(), size: size.to_string() }
+    }
+
+    fn buy(price: &str, size: &str) -> CreateOrderRequest {
+        CreateOrderRequest::unsigned(1, CONTRACT, OrderSide::Buy, price, size)
+    }
+
+    #[tokio::test]
+    async fn test_paper_fills() {
+        let meta = default_metadata();
+        let paper = PaperExchange::new(1, meta.clone());
+        let api: &dyn TradingApi = &paper;
+        let mut events = api.account_events(1).await.unwrap();
+        paper.apply_depth(&DepthUpdate {
+            contract_id: CONTRACT,
+            depth_type: "SNAPSHOT".to_string(),
+            bids: vec![level("99", "1")],
+            asks: vec![level("100", "0.5"), level("101", "1")],
+        });
+
+        // A negative size or a zero-priced limit order never reaches the book.
+        assert_eq!(api.place_order(buy("100", "-1"), &meta).await.unwrap()["code"], "INVALID_ORDER");
+        assert_eq!(api.place_order(buy("0", "1"), &meta).await.unwrap()["code"], "INVALID_ORDER");
+        assert!(api.get_open_orders(1).await.unwrap().is_empty());
+
+        // Sweeps 0.5 @ 100 as taker; 101 is above the limit, so 0.5 rests.
+        api.place_order(buy("100", "1"), &meta).await.unwrap();
+        let fills = api.get_fills(1).await.unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].direction, Some(LiquidityRole::Taker));
+        assert_eq!(fills[0].fee, "0.025");
+        let open = api.get_open_orders(1).await.unwrap();
+        assert_eq!(open[0].status, "PARTIALLY_FILLED");
+        assert_eq!(open[0].remaining_size, "0.5");
+
+        // A trade through our price fills part of the rest as maker.
+        paper.apply_trade(&PublicTrade {
+            ticket_id: 1,
+            contract_id: CONTRACT,
+            price: "99.5".to_string(),
+            size: "0.2".to_string(),
+            is_buyer_maker: true,
+            time: 0,
+        });
+        // The ask side dropping to our price fills the remainder.
+        paper.apply_depth(&DepthUpdate {
+            contract_id: CONTRACT,
+            depth_type: "CHANGED".to_string(),
+            bids: vec![],
+            asks: vec![level("100", "5")],
+        });
+        assert!(api.get_open_orders(1).await.unwrap().is_empty());
+        let positions = api.get_positions(1).await.unwrap();
+        assert_eq!(positions[0].open_size, "1");
+        assert_eq!(positions[0].open_value, "100");
+
+        let mut fill_sizes = Vec::new();
+        while fill_sizes.len() < 3 {
+            if let Some(AccountEvent::Fill(fill)) = events.next().await {
+                fill_sizes.push(fill.size);
+            }
+        }
+        assert_eq!(fill_sizes, ["0.5", "0.2", "0.3"]);
+    }
+}
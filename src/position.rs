@@ -0,0 +1,305 @@
+use crate::client::{ClientError, EdgeXClient};
+use crate::model::{Fill, FundingPayment, OrderSide, Position};
+use crate::websocket::AccountEvent;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Mutex;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum PositionError {
+    #[error("{field} is not a valid decimal: {value:?}")]
+    InvalidDecimal { field: &'static str, value: String },
+}
+
+/// Locally computed position for one contract.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContractPosition {
+    pub contract_id: u64,
+    /// Signed: positive for long, negative for short.
+    pub net_size: Decimal,
+    /// Average entry price of the open position; zero when flat.
+    pub avg_entry_price: Decimal,
+    pub realized_pnl: Decimal,
+    /// Trading fees paid (rebates are negative).
+    pub fees_paid: Decimal,
+    /// Funding paid (received funding is negative).
+    pub funding_paid: Decimal,
+    pub traded_volume: Decimal,
+}
+
+impl ContractPosition {
+    pub fn unrealized_pnl(&self, mark_price: Decimal) -> Decimal {
+        self.net_size * (mark_price - self.avg_entry_price)
+    }
+
+    /// Realized PnL after fees and funding.
+    pub fn net_realized_pnl(&self) -> Decimal {
+        self.realized_pnl - self.fees_paid - self.funding_paid
+    }
+
+    /// Applies a trade of `size` at `price`. `size` is signed (positive buys).
+    pub(crate) fn trade(&mut self, size: Decimal, price: Decimal) {
+        self.traded_volume += size.abs() * price;
+        let same_direction = self.net_size.is_zero() || self.net_size.is_sign_positive() == size.is_sign_positive();
+        if same_direction {
+            let total = self.net_size.abs() + size.abs();
+            self.avg_entry_price = (self.net_size.abs() * self.avg_entry_price + size.abs() * price) / total;
+            self.net_size += size;
+            return;
+        }
+
+        let closed = size.abs().min(self.net_size.abs());
+        let direction = if self.net_size.is_sign_positive() { Decimal::ONE } else { Decimal::NEGATIVE_ONE };
+        self.realized_pnl += closed * (price - self.avg_entry_price) * direction;
+        self.net_size += size;
+        if self.net_size.is_zero() {
+            self.avg_entry_price = Decimal::ZERO;
+        } else if self.net_size.is_sign_positive() != direction.is_sign_positive() {
+            // Flipped through flat: the remainder opens at the trade price.
+            self.avg_entry_price = price;
+        }
+    }
+}
+
+/// A contract where the local position disagrees with the exchange.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionDiff {
+    pub contract_id: u64,
+    pub local_size: Decimal,
+    pub exchange_size: Decimal,
+    pub local_avg_entry_price: Decimal,
+    pub exchange_avg_entry_price: Decimal,
+}
+
+#[derive(Default)]
+struct State {
+    positions: HashMap<u64, ContractPosition>,
+    mark_prices: HashMap<u64, Decimal>,
+    seen_fills: HashSet<u64>,
+}
+
+/// Builds per-contract positions and PnL from fills.
+///
+/// Fills may be fed from both REST (`get_fills`) and the private websocket;
+/// they are de-duplicated by fill id.
+pub struct PositionTracker {
+    state: Mutex<State>,
+    entry_price_tolerance: Decimal,
+}
+
+impl Default for PositionTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            entry_price_tolerance: Decimal::new(1, 6),
+        }
+    }
+
+    /// Relative entry-price difference tolerated by `reconcile` (default 1e-6).
+    pub fn with_entry_price_tolerance(mut self, tolerance: Decimal) -> Self {
+        self.entry_price_tolerance = tolerance;
+        self
+    }
+
+    pub fn apply_fill(&self, fill: &Fill) -> Result<(), PositionError> {
+        let price = parse_decimal("price", &fill.price)?;
+        let size = parse_decimal("size", &fill.size)?;
+        let fee = if fill.fee.is_empty() { Decimal::ZERO } else { parse_decimal("fee", &fill.fee)? };
+        let signed_size = match fill.side {
+            OrderSide::Buy => size,
+            OrderSide::Sell => -size,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if !state.seen_fills.insert(fill.id) {
+            return Ok(());
+        }
+        let position = position_mut(&mut state, fill.contract_id);
+        position.trade(signed_size, price);
+        position.fees_paid += fee;
+        Ok(())
+    }
+
+    pub fn apply_fills(&self, fills: &[Fill]) -> Result<(), PositionError> {
+        let mut fills: Vec<_> = fills.iter().collect();
+        fills.sort_by_key(|f| (f.time, f.id));
+        fills.into_iter().try_for_each(|f| self.apply_fill(f))
+    }
+
+    pub fn apply_event(&self, event: &AccountEvent) -> Result<(), PositionError> {
+        match event {
+            AccountEvent::Fill(fill) => self.apply_fill(fill),
+            AccountEvent::Order(_) => Ok(()),
+        }
+    }
+
+    /// Records a funding settlement. Like `FundingPayment.amount`, positive
+    /// `amount` is received and negative is paid.
+    pub fn apply_funding(&self, contract_id: u64, amount: Decimal) {
+        let mut state = self.state.lock().unwrap();
+        position_mut(&mut state, contract_id).funding_paid -= amount;
+    }
+
+    pub fn apply_funding_payment(&self, payment: &FundingPayment) -> Result<(), PositionError> {
+        self.apply_funding(payment.contract_id, parse_decimal("amount", &payment.amount)?);
+        Ok(())
+    }
+
+    pub fn set_mark_price(&self, contract_id: u64, mark_price: Decimal) {
+        self.state.lock().unwrap().mark_prices.insert(contract_id, mark_price);
+    }
+
+    pub fn position(&self, contract_id: u64) -> Option<ContractPosition> {
+        self.state.lock().unwrap().positions.get(&contract_id).cloned()
+    }
+
+    pub fn positions(&self) -> Vec<ContractPosition> {
+        let mut positions: Vec<_> = self.state.lock().unwrap().positions.values().cloned().collect();
+        positions.sort_by_key(|p| p.contract_id);
+        positions
+    }
+
+    /// Unrealized PnL at the last mark price; `None` without a position or mark.
+    pub fn unrealized_pnl(&self, contract_id: u64) -> Option<Decimal> {
+        let state = self.state.lock().unwrap();
+        let position = state.positions.get(&contract_id)?;
+        let mark = state.mark_prices.get(&contract_id)?;
+        Some(position.unrealized_pnl(*mark))
+    }
+
+    /// Sum of unrealized PnL over contracts that have a mark price.
+    pub fn total_unrealized_pnl(&self) -> Decimal {
+        let state = self.state.lock().unwrap();
+        state.positions.values()
+            .filter_map(|p| state.mark_prices.get(&p.contract_id).map(|m| p.unrealized_pnl(*m)))
+            .sum()
+    }
+
+    /// Compares local positions with the exchange's and returns every mismatch
+    /// in size or average entry price. Contracts flat on both sides are skipped.
+    pub fn reconcile(&self, exchange: &[Position]) -> Result<Vec<PositionDiff>, PositionError> {
+        let mut remote = HashMap::new();
+        for p in exchange {
+            let size = parse_decimal("openSize", &p.open_size)?;
+            let value = parse_decimal("openValue", &p.open_value)?;
+            let avg = if size.is_zero() { Decimal::ZERO } else { (value / size).abs() };
+            remote.insert(p.contract_id, (size, avg));
+        }
+
+        let state = self.state.lock().unwrap();
+        let mut contract_ids: Vec<u64> = state.positions.keys().chain(remote.keys()).copied().collect();
+        contract_ids.sort_unstable();
+        contract_ids.dedup();
+
+        let mut diffs = Vec::new();
+        for contract_id in contract_ids {
+            let local = state.positions.get(&contract_id).cloned().unwrap_or_default();
+            let (exchange_size, exchange_avg) = remote.get(&contract_id).copied().unwrap_or_default();
+            let price_mismatch = !exchange_size.is_zero()
+                && (local.avg_entry_price - exchange_avg).abs() > exchange_avg * self.entry_price_tolerance;
+            if local.net_size != exchange_size || price_mismatch {
+                diffs.push(PositionDiff {
+                    contract_id,
+                    local_size: local.net_size,
+                    exchange_size,
+                    local_avg_entry_price: local.avg_entry_price,
+                    exchange_avg_entry_price: exchange_avg,
+                });
+            }
+        }
+        Ok(diffs)
+    }
+
+    /// Fetches positions for `account_id` and reconciles against them.
+    pub async fn reconcile_with(&self, client: &EdgeXClient, account_id: u64) -> Result<Vec<PositionDiff>, ClientError> {
+        let positions = client.get_positions(account_id).await?;
+        Ok(self.reconcile(&positions)?)
+    }
+}
+
+fn position_mut(state: &mut State, contract_id: u64) -> &mut ContractPosition {
+    state.positions.entry(contract_id).or_insert_with(|| ContractPosition {
+        contract_id,
+        ..Default::default()
+    })
+}
+
+fn parse_decimal(field: &'static str, value: &str) -> Result<Decimal, PositionError> {
+    Decimal::from_str(value.trim()).map_err(|_| PositionError::InvalidDecimal { field, value: value.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(id: u64, side: OrderSide, size: &str, price: &str, fee: &str) -> Fill {
+        Fill {
+            id,
+            order_id: id,
+            contract_id: 1,
+            price: price.to_string(),
+            size: size.to_string(),
+            side,
+            time: id,
+            fee: fee.to_string(),
+            fee_asset_id: 0,
+            direction: None,
+        }
+    }
+
+    #[test]
+    fn test_position_from_fills() {
+        let tracker = PositionTracker::new();
+        tracker.apply_fills(&[
+            fill(1, OrderSide::Buy, "1", "100", "0.1"),
+            fill(2, OrderSide::Buy, "1", "110", "0.1"),
+            fill(3, OrderSide::Sell, "1.5", "120", "0.2"),
+            // Flips short through flat.
+            fill(4, OrderSide::Sell, "1", "100", "-0.05"),
+            fill(4, OrderSide::Sell, "1", "100", "-0.05"),
+        ]).unwrap();
+        tracker.apply_funding(1, Decimal::new(-3, 1));
+
+        let p = tracker.position(1).unwrap();
+        assert_eq!(p.net_size, Decimal::new(-5, 1));
+        assert_eq!(p.avg_entry_price, Decimal::from(100));
+        assert_eq!(p.realized_pnl, Decimal::from(20));
+        assert_eq!(p.fees_paid, Decimal::new(35, 2));
+        assert_eq!(p.net_realized_pnl(), Decimal::new(1935, 2));
+
+        // Funding history uses the same sign: positive is received.
+        tracker.apply_funding_payment(&FundingPayment {
+            id: 1,
+            contract_id: 1,
+            funding_rate: "-0.0001".to_string(),
+            position_size: "-0.5".to_string(),
+            oracle_price: String::new(),
+            amount: "0.05".to_string(),
+            time: 0,
+        }).unwrap();
+        assert_eq!(tracker.position(1).unwrap().funding_paid, Decimal::new(25, 2));
+
+        tracker.set_mark_price(1, Decimal::from(90));
+        assert_eq!(tracker.unrealized_pnl(1), Some(Decimal::from(5)));
+
+        let exchange = |size: &str, value: &str| Position {
+            contract_id: 1,
+            open_size: size.to_string(),
+            open_value: value.to_string(),
+            open_fee: String::new(),
+            funding_fee: String::new(),
+        };
+        assert!(tracker.reconcile(&[exchange("-0.5", "-50")]).unwrap().is_empty());
+        let diffs = tracker.reconcile(&[exchange("-0.4", "-40")]).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].exchange_size, Decimal::new(-4, 1));
+    }
+}
@@ -0,0 +1,508 @@
+use crate::model::{CancelOrderRequest, CreateOrderRequest, MetaData, OpenOrder, OrderSide, OrderStatus};
+use crate::order_manager::{strategy_tag, tagged_client_order_id, validate_strategy_tag, InvalidStrategyTag};
+use crate::trading::{check_response, TradingApi};
+use crate::websocket::AccountEvent;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// One resting quote of a ladder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuoteLevel {
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+/// The quotes wanted on one contract. Levels with the same price on a side
+/// are merged; zero-size levels are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ladder {
+    pub bids: Vec<QuoteLevel>,
+    pub asks: Vec<QuoteLevel>,
+}
+
+impl Ladder {
+    fn side(&self, side: OrderSide) -> BTreeMap<Decimal, Decimal> {
+        let levels = match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        };
+        let mut merged = BTreeMap::new();
+        for level in levels.iter().filter(|l| l.size > Decimal::ZERO) {
+            *merged.entry(level.price).or_insert(Decimal::ZERO) += level.size;
+        }
+        merged
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuoteAction {
+    Place { side: OrderSide, price: Decimal, size: Decimal },
+    Cancel { order_id: u64, side: OrderSide, price: Decimal },
+    /// Cancel `order_id`, then place the new quote once the cancel is accepted.
+    Replace { order_id: u64, side: OrderSide, old_price: Decimal, price: Decimal, size: Decimal },
+}
+
+impl QuoteAction {
+    /// Requests the action costs against the rate limit.
+    fn cost(&self) -> f64 {
+        match self {
+            QuoteAction::Replace { .. } => 2.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuoterConfig {
+    /// Sustained requests per second.
+    pub max_requests_per_sec: f64,
+    /// Requests that may be sent at once after a quiet period.
+    pub burst: u32,
+    /// A live quote is kept if its remaining size is within this fraction of
+    /// the target, so small partial fills don't cause requotes.
+    pub size_tolerance: Decimal,
+    /// In-flight actions with no confirmation after this long are forgotten.
+    pub in_flight_timeout: Duration,
+    /// Strategy tag of the quoter's client order ids (see
+    /// `tagged_client_order_id`). Open orders with another tag, or none, are
+    /// left alone but still count for self-trade checks.
+    pub strategy_tag: String,
+}
+
+impl Default for QuoterConfig {
+    fn default() -> Self {
+        Self {
+            max_requests_per_sec: 10.0,
+            burst: 20,
+            size_tolerance: Decimal::ZERO,
+            in_flight_timeout: Duration::from_secs(5),
+            strategy_tag: "quoter".to_string(),
+        }
+    }
+}
+
+/// What one `update` did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuoteReport {
+    pub placed: usize,
+    pub cancelled: usize,
+    pub replaced: usize,
+    /// Actions held back by the rate limit; the next `update` retries them.
+    pub deferred: usize,
+    /// Quotes not placed because they would cross the account's resting
+    /// orders or our in-flight quotes on the other side.
+    pub self_trade_blocked: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct LiveQuote {
+    order_id: u64,
+    side: OrderSide,
+    price: Decimal,
+    remaining: Decimal,
+}
+
+struct PendingPlace {
+    side: OrderSide,
+    price: Decimal,
+    sent_at: Instant,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    capacity: f64,
+    rate: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: u32) -> Self {
+        Self { tokens: burst as f64, capacity: burst as f64, rate, refilled_at: Instant::now() }
+    }
+
+    fn try_take(&mut self, cost: f64, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.refilled_at = now;
+        if self.tokens + 1e-9 >= cost {
+            self.tokens -= cost;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Keeps the account's quotes on one contract in line with a target `Ladder`.
+///
+/// Each `update` diffs the ladder against the quoter's live orders and sends
+/// only what changed: cancels first, then replaces, then new quotes nearest
+/// the touch. A price level with an action in flight is left alone until the
+/// account stream confirms it (feed events to `apply_event`), so a level is
+/// never quoted twice.
+pub struct Quoter {
+    api: Arc<dyn TradingApi>,
+    meta: MetaData,
+    account_id: u64,
+    contract_id: u64,
+    config: QuoterConfig,
+    live: BTreeMap<u64, LiveQuote>,
+    /// The account's other open orders on the contract, by order id.
+    others: HashMap<u64, (OrderSide, Decimal)>,
+    /// By client order id.
+    pending_places: HashMap<String, PendingPlace>,
+    /// By order id.
+    pending_cancels: HashMap<u64, Instant>,
+    limiter: TokenBucket,
+}
+
+impl Quoter {
+    pub fn new(api: Arc<dyn TradingApi>, meta: MetaData, account_id: u64, contract_id: u64) -> Self {
+        let config = QuoterConfig::default();
+        Self {
+            api,
+            meta,
+            account_id,
+            contract_id,
+            limiter: TokenBucket::new(config.max_requests_per_sec, config.burst),
+            config,
+            live: BTreeMap::new(),
+            others: HashMap::new(),
+            pending_places: HashMap::new(),
+            pending_cancels: HashMap::new(),
+        }
+    }
+
+    pub fn with_config(mut self, config: QuoterConfig) -> Result<Self, InvalidStrategyTag> {
+        validate_strategy_tag(&config.strategy_tag)?;
+        self.limiter = TokenBucket::new(config.max_requests_per_sec, config.burst);
+        self.config = config;
+        Ok(self)
+    }
+
+    /// Replaces the live view with `open_orders`, e.g. from `get_open_orders`
+    /// after a reconnect. Places that show up and cancels of orders that are
+    /// gone are settled; other in-flight actions are kept.
+    pub fn reconcile(&mut self, open_orders: &[OpenOrder]) {
+        self.live.clear();
+        self.others.clear();
+        for order in open_orders {
+            self.apply_order(order);
+        }
+        let live = &self.live;
+        self.pending_cancels.retain(|order_id, _| live.contains_key(order_id));
+    }
+
+    pub fn apply_event(&mut self, event: &AccountEvent) {
+        if let AccountEvent::Order(order) = event {
+            self.apply_order(order);
+        }
+    }
+
+    pub fn apply_order(&mut self, order: &OpenOrder) {
+        if order.contract_id != self.contract_id {
+            return;
+        }
+        let terminal = OrderStatus::from_exchange(&order.status).is_some_and(OrderStatus::is_terminal);
+        let ours = order.client_order_id.as_deref().and_then(strategy_tag) == Some(self.config.strategy_tag.as_str());
+        if !ours {
+            match Decimal::from_str(&order.price) {
+                Ok(price) if !terminal && remaining_size(order).is_some_and(|r| r > Decimal::ZERO) => {
+                    self.others.insert(order.order_id, (order.side, price));
+                }
+                _ => {
+                    self.others.remove(&order.order_id);
+                }
+            }
+            return;
+        }
+        if let Some(client_order_id) = &order.client_order_id {
+            self.pending_places.remove(client_order_id);
+        }
+        let (Ok(price), Some(remaining)) = (Decimal::from_str(&order.price), remaining_size(order)) else {
+            return;
+        };
+        if terminal || remaining.is_zero() {
+            self.live.remove(&order.order_id);
+            self.pending_cancels.remove(&order.order_id);
+        } else {
+            self.live.insert(order.order_id, LiveQuote { order_id: order.order_id, side: order.side, price, remaining });
+        }
+    }
+
+    /// Whether any action is still waiting for confirmation.
+    pub fn has_in_flight(&self) -> bool {
+        !self.pending_places.is_empty() || !self.pending_cancels.is_empty()
+    }
+
+    /// The minimal actions that turn the live quotes into `ladder`, ignoring
+    /// the rate limit and self-trade checks.
+    pub fn diff(&self, ladder: &Ladder) -> Vec<QuoteAction> {
+        let mut cancels = Vec::new();
+        let mut replaces = Vec::new();
+        let mut places = Vec::new();
+
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            let targets = ladder.side(side);
+            let busy: HashSet<Decimal> = self.pending_places.values()
+                .filter(|p| p.side == side)
+                .map(|p| p.price)
+                .chain(self.live.values()
+                    .filter(|q| q.side == side && self.pending_cancels.contains_key(&q.order_id))
+                    .map(|q| q.price))
+                .collect();
+            let mut by_price: BTreeMap<Decimal, Vec<&LiveQuote>> = BTreeMap::new();
+            for quote in self.live.values().filter(|q| q.side == side && !self.pending_cancels.contains_key(&q.order_id)) {
+                by_price.entry(quote.price).or_default().push(quote);
+            }
+
+            let mut side_cancels = Vec::new();
+            let mut side_places = Vec::new();
+            for (price, quotes) in &by_price {
+                if busy.contains(price) {
+                    continue;
+                }
+                let keep = targets.get(price).and_then(|target| {
+                    quotes.iter().position(|q| within_tolerance(q.remaining, *target, self.config.size_tolerance))
+                });
+                for (i, quote) in quotes.iter().enumerate() {
+                    if Some(i) != keep {
+                        side_cancels.push((*price, quote.order_id));
+                    }
+                }
+                if let (None, Some(size)) = (keep, targets.get(price)) {
+                    side_places.push((*price, *size));
+                }
+            }
+            for (price, size) in &targets {
+                if !by_price.contains_key(price) && !busy.contains(price) {
+                    side_places.push((*price, *size));
+                }
+            }
+
+            // Nearest the touch first: highest bids, lowest asks.
+            let closer = |a: &Decimal, b: &Decimal| match side {
+                OrderSide::Buy => b.cmp(a),
+                OrderSide::Sell => a.cmp(b),
+            };
+            side_cancels.sort_by(|a, b| closer(&a.0, &b.0));
+            side_places.sort_by(|a, b| closer(&a.0, &b.0));
+
+            // Pair cancels with places so each moved quote is one replace.
+            let pairs = side_cancels.len().min(side_places.len());
+            for ((old_price, order_id), (price, size)) in side_cancels.drain(..pairs).zip(side_places.drain(..pairs)) {
+                replaces.push(QuoteAction::Replace { order_id, side, old_price, price, size });
+            }
+            cancels.extend(side_cancels.into_iter().map(|(price, order_id)| QuoteAction::Cancel { order_id, side, price }));
+            places.extend(side_places.into_iter().map(|(price, size)| QuoteAction::Place { side, price, size }));
+        }
+
+        cancels.into_iter().chain(replaces).chain(places).collect()
+    }
+
+    /// Sends what `diff` returns, within the rate limit and without crossing
+    /// our own quotes.
+    pub async fn update(&mut self, ladder: &Ladder) -> QuoteReport {
+        let now = Instant::now();
+        self.expire_in_flight(now);
+        let mut report = QuoteReport::default();
+
+        for action in self.diff(ladder) {
+            let place = match &action {
+                QuoteAction::Place { side, price, .. } | QuoteAction::Replace { side, price, .. } => Some((*side, *price)),
+                QuoteAction::Cancel { .. } => None,
+            };
+            if let Some((side, price)) = place
+                && self.crosses_own(side, price)
+            {
+                report.self_trade_blocked += 1;
+                continue;
+            }
+            if !self.limiter.try_take(action.cost(), now) {
+                report.deferred += 1;
+                continue;
+            }
+            match action {
+                QuoteAction::Cancel { order_id, .. } => {
+                    if self.cancel(order_id, &mut report).await {
+                        report.cancelled += 1;
+                    }
+                }
+                QuoteAction::Place { side, price, size } => {
+                    if self.place(side, price, size, &mut report).await {
+                        report.placed += 1;
+                    }
+                }
+                QuoteAction::Replace { order_id, side, price, size, .. } => {
+                    if self.cancel(order_id, &mut report).await && self.place(side, price, size, &mut report).await {
+                        report.replaced += 1;
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Cancels every quote the quoter knows about, ignoring the rate limit.
+    pub async fn cancel_all(&mut self) -> QuoteReport {
+        let mut report = QuoteReport::default();
+        let order_ids: Vec<u64> = self.live.keys().copied().collect();
+        for order_id in order_ids {
+            if self.cancel(order_id, &mut report).await {
+                report.cancelled += 1;
+            }
+        }
+        report
+    }
+
+    /// Would a quote at `price` on `side` trade against any of the account's
+    /// orders? Quotes being cancelled still count until the cancel is confirmed.
+    fn crosses_own(&self, side: OrderSide, price: Decimal) -> bool {
+        let crosses = |other: Decimal| match side {
+            OrderSide::Buy => other <= price,
+            OrderSide::Sell => other >= price,
+        };
+        self.live.values().any(|q| q.side != side && crosses(q.price))
+            || self.pending_places.values().any(|p| p.side != side && crosses(p.price))
+            || self.others.values().any(|(other_side, other_price)| *other_side != side && crosses(*other_price))
+    }
+
+    async fn place(&mut self, side: OrderSide, price: Decimal, size: Decimal, report: &mut QuoteReport) -> bool {
+        let mut req = match CreateOrderRequest::limit(&self.meta, self.account_id, self.contract_id, side, price, size) {
+            Ok(req) => req,
+            Err(e) => {
+                report.errors.push(e.to_string());
+                return false;
+            }
+        };
+        let client_order_id = tagged_client_order_id(&self.config.strategy_tag);
+        req.client_order_id = Some(client_order_id.clone());
+        self.pending_places.insert(client_order_id.clone(), PendingPlace { side, price, sent_at: Instant::now() });
+        match self.api.place_order(req, &self.meta).await.and_then(|res| check_response(&res)) {
+            Ok(()) => true,
+            Err(e) => {
+                self.pending_places.remove(&client_order_id);
+                report.errors.push(e.to_string());
+                false
+            }
+        }
+    }
+
+    async fn cancel(&mut self, order_id: u64, report: &mut QuoteReport) -> bool {
+        self.pending_cancels.insert(order_id, Instant::now());
+        let req = CancelOrderRequest::by_order_id(self.account_id, self.contract_id, order_id);
+        match self.api.cancel_order(&req).await.and_then(|res| check_response(&res)) {
+            Ok(()) => true,
+            Err(e) => {
+                self.pending_cancels.remove(&order_id);
+                report.errors.push(e.to_string());
+                false
+            }
+        }
+    }
+
+    fn expire_in_flight(&mut self, now: Instant) {
+        let timeout = self.config.in_flight_timeout;
+        self.pending_places.retain(|_, p| now.saturating_duration_since(p.sent_at) < timeout);
+        self.pending_cancels.retain(|_, sent_at| now.saturating_duration_since(*sent_at) < timeout);
+    }
+}
+
+fn within_tolerance(remaining: Decimal, target: Decimal, tolerance: Decimal) -> bool {
+    (remaining - target).abs() <= target * tolerance
+}
+
+fn remaining_size(order: &OpenOrder) -> Option<Decimal> {
+    if let Ok(remaining) = Decimal::from_str(&order.remaining_size) {
+        return Some(remaining);
+    }
+    let size = Decimal::from_str(&order.size).ok()?;
+    Some(size - Decimal::from_str(&order.filled_size).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::default_metadata;
+    use crate::paper::PaperExchange;
+    use crate::trading::TradingApi;
+
+    const CONTRACT: u64 = 10000001;
+
+    fn level(price: i64, size: i64) -> QuoteLevel {
+        QuoteLevel { price: Decimal::from(price), size: Decimal::from(size) }
+    }
+
+    fn ladder(bids: &[i64], asks: &[i64]) -> Ladder {
+        Ladder {
+            bids: bids.iter().map(|p| level(*p, 1)).collect(),
+            asks: asks.iter().map(|p| level(*p, 1)).collect(),
+        }
+    }
+
+    async fn sync(quoter: &mut Quoter, paper: &PaperExchange) {
+        quoter.reconcile(&paper.get_open_orders(1).await.unwrap());
+        assert!(!quoter.has_in_flight());
+    }
+
+    #[tokio::test]
+    async fn test_quoter_diffs_ladder() {
+        let paper = Arc::new(PaperExchange::new(1, default_metadata()));
+        let mut quoter = Quoter::new(paper.clone(), default_metadata(), 1, CONTRACT).with_config(QuoterConfig {
+            burst: 5,
+            max_requests_per_sec: 0.0,
+            ..Default::default()
+        }).unwrap();
+
+        let report = quoter.update(&ladder(&[100, 99], &[101, 102])).await;
+        assert_eq!((report.placed, report.deferred), (4, 0), "{:?}", report.errors);
+        // Unconfirmed quotes are in flight: nothing is sent twice.
+        assert!(quoter.diff(&ladder(&[100, 99], &[101, 102])).is_empty());
+
+        sync(&mut quoter, &paper).await;
+        assert!(quoter.diff(&ladder(&[100, 99], &[101, 102])).is_empty());
+
+        // Moving one bid is a single replace; dropping an ask a cancel.
+        let actions = quoter.diff(&ladder(&[100, 98], &[101]));
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(actions[0], QuoteAction::Cancel { side: OrderSide::Sell, .. }));
+        let bid_99 = quoter.live.values().find(|q| q.price == Decimal::from(99)).unwrap().order_id;
+        assert_eq!(actions[1], QuoteAction::Replace {
+            order_id: bid_99,
+            side: OrderSide::Buy,
+            old_price: Decimal::from(99),
+            price: Decimal::from(98),
+            size: Decimal::ONE,
+        });
+
+        // One token left after four places: the cancel goes, the replace waits.
+        let report = quoter.update(&ladder(&[100, 98], &[101])).await;
+        assert_eq!((report.cancelled, report.replaced, report.deferred), (1, 0, 1));
+
+        // A bid through our own ask is held back.
+        sync(&mut quoter, &paper).await;
+        quoter.limiter = TokenBucket::new(0.0, 10);
+        let report = quoter.update(&ladder(&[101, 100, 99], &[101])).await;
+        assert_eq!(report.self_trade_blocked, 1);
+        assert_eq!(paper.get_open_orders(1).await.unwrap().len(), 3);
+
+        // So is an ask through another strategy's bid on the account, which
+        // the quoter never touches.
+        let mut req = CreateOrderRequest::limit(&default_metadata(), 1, CONTRACT, OrderSide::Buy, Decimal::new(1005, 1), Decimal::ONE).unwrap();
+        req.client_order_id = Some(tagged_client_order_id("other"));
+        paper.place_order(req, &default_metadata()).await.unwrap();
+        sync(&mut quoter, &paper).await;
+        let mut target = ladder(&[100, 99], &[101]);
+        target.asks.push(QuoteLevel { price: Decimal::new(1005, 1), size: Decimal::ONE });
+        let report = quoter.update(&target).await;
+        assert_eq!((report.self_trade_blocked, report.cancelled), (1, 0));
+        assert_eq!(paper.get_open_orders(1).await.unwrap().len(), 4);
+        assert!(Quoter::new(paper.clone(), default_metadata(), 1, CONTRACT)
+            .with_config(QuoterConfig { strategy_tag: "q-".to_string(), ..Default::default() })
+            .is_err());
+    }
+}
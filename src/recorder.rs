@@ -0,0 +1,471 @@
+//! Records websocket traffic to disk and plays it back.
+//!
+//! Files are gzip-compressed and rotate by size and age. Two layouts are
+//! supported:
+//!
+//! * `jsonl.gz` — one JSON object per line with the receive time, the raw
+//!   frame and its parsed `WsMessage`, greppable after `zcat`.
+//! * `bin.gz` — length-prefixed records holding only the receive time and the
+//!   raw frame; roughly half the size, parsed again on replay.
+
+use crate::client::ClientError;
+use crate::time_sync::local_millis;
+use crate::websocket::{parse_message, WsMessage};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+const JSON_LINES_EXT: &str = "jsonl.gz";
+const BINARY_EXT: &str = "bin.gz";
+
+#[derive(Error, Debug)]
+pub enum RecorderError {
+    #[error("I/O error on {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Corrupt record in {path}: {reason}")]
+    Corrupt { path: PathBuf, reason: String },
+    #[error("Not a recording: {0}")]
+    UnknownFormat(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordFormat {
+    #[default]
+    JsonLines,
+    Binary,
+}
+
+impl RecordFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::JsonLines => JSON_LINES_EXT,
+            RecordFormat::Binary => BINARY_EXT,
+        }
+    }
+
+    fn of_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(JSON_LINES_EXT) {
+            Some(RecordFormat::JsonLines)
+        } else if name.ends_with(BINARY_EXT) {
+            Some(RecordFormat::Binary)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    pub dir: PathBuf,
+    /// File names are `{prefix}-{opened_ms}-{seq}.{ext}`, so they sort by time.
+    pub prefix: String,
+    pub format: RecordFormat,
+    /// Rotate once this many uncompressed bytes are written (default 256 MiB).
+    pub max_file_bytes: u64,
+    /// Rotate once a file has been open this long (default 1h).
+    pub max_file_age: Duration,
+}
+
+impl RecorderConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: "edgex".to_string(),
+            format: RecordFormat::default(),
+            max_file_bytes: 256 << 20,
+            max_file_age: Duration::from_secs(3600),
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn with_format(mut self, format: RecordFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_rotation(mut self, max_file_bytes: u64, max_file_age: Duration) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self.max_file_age = max_file_age;
+        self
+    }
+}
+
+/// One recorded websocket frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedMessage {
+    /// Local clock when the frame was received.
+    pub received_ms: u64,
+    pub raw: String,
+    /// `None` when the frame wasn't valid JSON. Always `None` when read back
+    /// from binary files; use `parse` instead.
+    #[serde(default)]
+    pub message: Option<WsMessage>,
+}
+
+impl RecordedMessage {
+    /// Parses the raw frame exactly as the live stream would.
+    pub fn parse(&self) -> Result<WsMessage, ClientError> {
+        parse_message(&self.raw)
+    }
+}
+
+struct OpenFile {
+    path: PathBuf,
+    writer: BufWriter<GzEncoder<File>>,
+    opened_ms: u64,
+    bytes: u64,
+}
+
+impl OpenFile {
+    fn finish(self) -> Result<PathBuf, RecorderError> {
+        let path = self.path;
+        let io_err = |source| RecorderError::Io { path: path.clone(), source };
+        let encoder = self.writer.into_inner().map_err(|e| io_err(e.into_error()))?;
+        encoder.finish().map_err(io_err)?;
+        Ok(path)
+    }
+}
+
+/// Writes websocket frames to rotating compressed files.
+pub struct Recorder {
+    config: RecorderConfig,
+    current: Option<OpenFile>,
+    seq: u64,
+    closed: Vec<PathBuf>,
+}
+
+impl Recorder {
+    /// Creates `config.dir` if needed. Files are opened lazily on the first record.
+    pub fn new(config: RecorderConfig) -> Result<Self, RecorderError> {
+        std::fs::create_dir_all(&config.dir)
+            .map_err(|source| RecorderError::Io { path: config.dir.clone(), source })?;
+        Ok(Self { config, current: None, seq: 0, closed: Vec::new() })
+    }
+
+    /// Files finished so far, oldest first. The file being written isn't included.
+    pub fn closed_files(&self) -> &[PathBuf] {
+        &self.closed
+    }
+
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current.as_ref().map(|f| f.path.as_path())
+    }
+
+    pub fn record(&mut self, received_ms: u64, raw: &str) -> Result<(), RecorderError> {
+        let bytes = match self.config.format {
+            RecordFormat::JsonLines => {
+                let record = RecordedMessage {
+                    received_ms,
+                    raw: raw.to_string(),
+                    message: parse_message(raw).ok(),
+                };
+                // Serializing our own struct can't fail.
+                let mut line = serde_json::to_vec(&record).unwrap_or_default();
+                line.push(b'\n');
+                line
+            }
+            RecordFormat::Binary => {
+                let mut buf = Vec::with_capacity(12 + raw.len());
+                buf.extend_from_slice(&received_ms.to_le_bytes());
+                buf.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+                buf.extend_from_slice(raw.as_bytes());
+                buf
+            }
+        };
+
+        let file = self.file_for(received_ms)?;
+        file.writer.write_all(&bytes)
+            .map_err(|source| RecorderError::Io { path: file.path.clone(), source })?;
+        file.bytes += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Pushes buffered data through the compressor so readers see it.
+    pub fn flush(&mut self) -> Result<(), RecorderError> {
+        if let Some(file) = &mut self.current {
+            file.writer.flush().map_err(|source| RecorderError::Io { path: file.path.clone(), source })?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the current file; the next record starts a new one.
+    pub fn rotate(&mut self) -> Result<(), RecorderError> {
+        if let Some(file) = self.current.take() {
+            self.closed.push(file.finish()?);
+        }
+        Ok(())
+    }
+
+    /// Finishes the current file and returns every file written.
+    pub fn close(mut self) -> Result<Vec<PathBuf>, RecorderError> {
+        self.rotate()?;
+        Ok(std::mem::take(&mut self.closed))
+    }
+
+    /// Records each frame of a `EdgeXWebSocket::text_messages` stream as it
+    /// passes through, yielding the parsed messages like `EdgeXWebSocket::messages`.
+    /// Write failures are yielded as errors; the stream keeps going.
+    pub fn tap(
+        self,
+        frames: impl Stream<Item = Result<String, ClientError>> + Unpin,
+    ) -> impl Stream<Item = Result<WsMessage, ClientError>> {
+        stream::unfold((self, frames), |(mut recorder, mut frames)| async move {
+            let item = match frames.next().await? {
+                Ok(raw) => match recorder.record(local_millis(), &raw) {
+                    Ok(()) => parse_message(&raw),
+                    Err(e) => Err(e.into()),
+                },
+                Err(e) => Err(e),
+            };
+            Some((item, (recorder, frames)))
+        })
+    }
+
+    fn file_for(&mut self, received_ms: u64) -> Result<&mut OpenFile, RecorderError> {
+        let max_age_ms = self.config.max_file_age.as_millis() as u64;
+        if let Some(file) = &self.current
+            && (file.bytes >= self.config.max_file_bytes || received_ms >= file.opened_ms + max_age_ms)
+        {
+            self.rotate()?;
+        }
+        if self.current.is_none() {
+            let opened_ms = local_millis();
+            let name = format!("{}-{:013}-{:04}.{}", self.config.prefix, opened_ms, self.seq, self.config.format.extension());
+            let path = self.config.dir.join(name);
+            let file = File::create(&path).map_err(|source| RecorderError::Io { path: path.clone(), source })?;
+            self.seq += 1;
+            self.current = Some(OpenFile {
+                path,
+                writer: BufWriter::new(GzEncoder::new(file, Compression::default())),
+                opened_ms: opened_ms.min(received_ms),
+                bytes: 0,
+            });
+        }
+        Ok(self.current.as_mut().unwrap())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Best effort: without the gzip trailer the tail of the file is unreadable.
+        let _ = self.rotate();
+    }
+}
+
+/// Reads the records of one file, in order.
+pub struct RecordReader {
+    path: PathBuf,
+    format: RecordFormat,
+    reader: BufReader<MultiGzDecoder<File>>,
+}
+
+impl RecordReader {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, RecorderError> {
+        let path = path.into();
+        let format = RecordFormat::of_path(&path).ok_or_else(|| RecorderError::UnknownFormat(path.clone()))?;
+        let file = File::open(&path).map_err(|source| RecorderError::Io { path: path.clone(), source })?;
+        Ok(Self { path, format, reader: BufReader::new(MultiGzDecoder::new(file)) })
+    }
+
+    fn read_json_line(&mut self) -> Result<Option<RecordedMessage>, RecorderError> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let n = self.reader.read_line(&mut line).map_err(|e| self.io_error(e))?;
+            if n == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+        serde_json::from_str(&line)
+            .map(Some)
+            .map_err(|e| RecorderError::Corrupt { path: self.path.clone(), reason: e.to_string() })
+    }
+
+    fn read_binary(&mut self) -> Result<Option<RecordedMessage>, RecorderError> {
+        let mut header = [0u8; 12];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(self.io_error(e)),
+        }
+        let received_ms = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let len = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+        let mut raw = vec![0u8; len];
+        self.reader.read_exact(&mut raw).map_err(|e| self.io_error(e))?;
+        let raw = String::from_utf8(raw)
+            .map_err(|e| RecorderError::Corrupt { path: self.path.clone(), reason: e.to_string() })?;
+        Ok(Some(RecordedMessage { received_ms, raw, message: None }))
+    }
+
+    fn io_error(&self, source: io::Error) -> RecorderError {
+        RecorderError::Io { path: self.path.clone(), source }
+    }
+}
+
+impl Iterator for RecordReader {
+    type Item = Result<RecordedMessage, RecorderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.format {
+            RecordFormat::JsonLines => self.read_json_line(),
+            RecordFormat::Binary => self.read_binary(),
+        };
+        record.transpose()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplaySpeed {
+    /// Keep the recorded gaps between messages.
+    #[default]
+    Original,
+    /// Divide the recorded gaps by this factor.
+    Accelerated(f64),
+    /// No waiting at all.
+    AsFastAsPossible,
+}
+
+/// Plays recordings back as the same message stream the live websocket gives.
+pub struct Replayer {
+    files: Vec<PathBuf>,
+    speed: ReplaySpeed,
+}
+
+impl Replayer {
+    /// Files are played in the order given.
+    pub fn new(files: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self { files: files.into_iter().collect(), speed: ReplaySpeed::default() }
+    }
+
+    /// Every recording in `dir`, in file name (and so recording) order.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, RecorderError> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|source| RecorderError::Io { path: dir.to_path_buf(), source })?;
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| RecordFormat::of_path(path).is_some())
+            .collect();
+        files.sort();
+        Ok(Self::new(files))
+    }
+
+    pub fn with_speed(mut self, speed: ReplaySpeed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// All records without any pacing. Reading stops at the first error.
+    pub fn records(&self) -> impl Iterator<Item = Result<RecordedMessage, RecorderError>> + use<> {
+        let mut failed = false;
+        self.files.clone().into_iter()
+            .flat_map(|path| -> Box<dyn Iterator<Item = _>> {
+                match RecordReader::open(path) {
+                    Ok(reader) => Box::new(reader),
+                    Err(e) => Box::new(std::iter::once(Err(e))),
+                }
+            })
+            .take_while(move |record| {
+                let keep = !failed;
+                failed |= record.is_err();
+                keep
+            })
+    }
+
+    /// Records paced according to the replay speed, relative to the first one.
+    pub fn record_stream(self) -> impl Stream<Item = Result<RecordedMessage, RecorderError>> {
+        let speed = self.speed;
+        let start = tokio::time::Instant::now();
+        let mut first_ms = None;
+        stream::iter(self.records()).then(move |record| {
+            let deadline = record.as_ref().ok().and_then(|r| {
+                let first = *first_ms.get_or_insert(r.received_ms);
+                let gap = Duration::from_millis(r.received_ms.saturating_sub(first));
+                match speed {
+                    ReplaySpeed::Original => Some(start + gap),
+                    ReplaySpeed::Accelerated(factor) if factor > 0.0 => Some(start + gap.div_f64(factor)),
+                    ReplaySpeed::Accelerated(_) | ReplaySpeed::AsFastAsPossible => None,
+                }
+            });
+            async move {
+                if let Some(deadline) = deadline {
+                    tokio::time::sleep_until(deadline).await;
+                }
+                record
+            }
+        })
+    }
+
+    /// Drop-in for `EdgeXWebSocket::messages` on a live connection.
+    pub fn stream(self) -> impl Stream<Item = Result<WsMessage, ClientError>> {
+        self.record_stream().map(|record| record.map_err(ClientError::from).and_then(|r| r.parse()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        std::env::temp_dir().join(format!("edgex-{}-{}-{}", name, std::process::id(), nanos))
+    }
+
+    #[tokio::test]
+    async fn test_record_rotate_and_replay() {
+        for format in [RecordFormat::JsonLines, RecordFormat::Binary] {
+            let dir = temp_dir("recorder");
+            let config = RecorderConfig::new(&dir)
+                .with_format(format)
+                .with_rotation(200, Duration::from_secs(3600));
+            let mut recorder = Recorder::new(config).unwrap();
+            for i in 0..10u64 {
+                let raw = format!(r#"{{"type":"quote-event","channel":"ticker.10000001","time":{},"content":{{"seq":{}}}}}"#, i, i);
+                recorder.record(1_000 + i, &raw).unwrap();
+            }
+            recorder.record(1_010, "not json").unwrap();
+            let files = recorder.close().unwrap();
+            assert!(files.len() > 1, "{:?} should have rotated", format);
+
+            let records: Vec<_> = Replayer::from_dir(&dir).unwrap().records().collect::<Result<_, _>>().unwrap();
+            assert_eq!(records.len(), 11);
+            assert_eq!(records[3].received_ms, 1_003);
+            assert_eq!(records[0].message.is_some(), format == RecordFormat::JsonLines);
+
+            let messages: Vec<_> = Replayer::from_dir(&dir).unwrap()
+                .with_speed(ReplaySpeed::AsFastAsPossible)
+                .stream()
+                .collect()
+                .await;
+            let seqs: Vec<_> = messages[..10].iter()
+                .map(|m| m.as_ref().unwrap().payload["content"]["seq"].as_u64().unwrap())
+                .collect();
+            assert_eq!(seqs, (0..10).collect::<Vec<_>>());
+            assert_eq!(messages[2].as_ref().unwrap().channel.as_deref(), Some("ticker.10000001"));
+            // Unparseable frames fail on replay just as they did live.
+            assert!(messages[10].is_err());
+
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use crate::model::{Fill, FundingPayment, LiquidityRole, OrderSide};
+use crate::position::ContractPosition;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::str::FromStr;
+use thiserror::Error;
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ReportError {
+    #[error("{field} is not a valid decimal: {value:?}")]
+    InvalidDecimal { field: &'static str, value: String },
+    #[error("JSON error: {0}")]
+    JsonError(String),
+}
+
+/// Activity of one contract on one UTC day.
+///
+/// Fees and funding are in collateral units. `realized_pnl` is gross of fees
+/// and funding, as in `ContractPosition`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailySummary {
+    /// `YYYY-MM-DD`, UTC.
+    pub date: String,
+    pub contract_id: u64,
+    pub fill_count: u64,
+    pub traded_volume: Decimal,
+    pub maker_fees: Decimal,
+    /// Includes fees of fills that don't say whether they were maker or taker.
+    pub taker_fees: Decimal,
+    pub funding_paid: Decimal,
+    pub funding_received: Decimal,
+    pub realized_pnl: Decimal,
+}
+
+impl DailySummary {
+    /// Realized PnL minus fees, plus net funding.
+    pub fn net_pnl(&self) -> Decimal {
+        self.realized_pnl - self.maker_fees - self.taker_fees - self.funding_paid + self.funding_received
+    }
+}
+
+/// Aggregates fills and funding payments into daily per-contract summaries.
+///
+/// Realized PnL is computed by replaying fills in time order from a flat
+/// position, so feed the complete fill history, not just the reporting window.
+/// Duplicate fills and payments (by id) are ignored.
+#[derive(Default)]
+pub struct ReportBuilder {
+    fills: Vec<Fill>,
+    funding: Vec<FundingPayment>,
+    seen_fills: HashSet<u64>,
+    seen_funding: HashSet<u64>,
+}
+
+impl ReportBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_fills(&mut self, fills: &[Fill]) -> &mut Self {
+        for fill in fills {
+            if self.seen_fills.insert(fill.id) {
+                self.fills.push(fill.clone());
+            }
+        }
+        self
+    }
+
+    pub fn add_funding(&mut self, payments: &[FundingPayment]) -> &mut Self {
+        for payment in payments {
+            if self.seen_funding.insert(payment.id) {
+                self.funding.push(payment.clone());
+            }
+        }
+        self
+    }
+
+    /// Summaries sorted by date, then contract.
+    pub fn build(&self) -> Result<Vec<DailySummary>, ReportError> {
+        let mut days: BTreeMap<(String, u64), DailySummary> = BTreeMap::new();
+        let mut positions: HashMap<u64, ContractPosition> = HashMap::new();
+
+        let mut fills: Vec<_> = self.fills.iter().collect();
+        fills.sort_by_key(|f| (f.time, f.id));
+        for fill in fills {
+            let price = parse_decimal("price", &fill.price)?;
+            let size = parse_decimal("size", &fill.size)?;
+            let fee = if fill.fee.is_empty() { Decimal::ZERO } else { parse_decimal("fee", &fill.fee)? };
+            let signed_size = match fill.side {
+                OrderSide::Buy => size,
+                OrderSide::Sell => -size,
+            };
+
+            let position = positions.entry(fill.contract_id).or_default();
+            let realized_before = position.realized_pnl;
+            position.trade(signed_size, price);
+
+            let day = summary_mut(&mut days, fill.time, fill.contract_id);
+            day.fill_count += 1;
+            day.traded_volume += size * price;
+            day.realized_pnl += position.realized_pnl - realized_before;
+            match fill.direction {
+                Some(LiquidityRole::Maker) => day.maker_fees += fee,
+                Some(LiquidityRole::Taker) | None => day.taker_fees += fee,
+            }
+        }
+
+        for payment in &self.funding {
+            let amount = parse_decimal("amount", &payment.amount)?;
+            let day = summary_mut(&mut days, payment.time, payment.contract_id);
+            if amount.is_sign_negative() {
+                day.funding_paid -= amount;
+            } else {
+                day.funding_received += amount;
+            }
+        }
+        Ok(days.into_values().collect())
+    }
+}
+
+/// Renders summaries as CSV with a header row.
+pub fn to_csv(summaries: &[DailySummary]) -> String {
+    let mut out = String::from(
+        "date,contract_id,fill_count,traded_volume,maker_fees,taker_fees,funding_paid,funding_received,realized_pnl,net_pnl\n",
+    );
+    for s in summaries {
+        // Every field is a date or a number, so nothing needs quoting.
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{}",
+            s.date,
+            s.contract_id,
+            s.fill_count,
+            s.traded_volume.normalize(),
+            s.maker_fees.normalize(),
+            s.taker_fees.normalize(),
+            s.funding_paid.normalize(),
+            s.funding_received.normalize(),
+            s.realized_pnl.normalize(),
+            s.net_pnl().normalize(),
+        );
+    }
+    out
+}
+
+/// Renders summaries as a pretty-printed JSON array; decimals are strings.
+pub fn to_json(summaries: &[DailySummary]) -> Result<String, ReportError> {
+    serde_json::to_string_pretty(summaries).map_err(|e| ReportError::JsonError(e.to_string()))
+}
+
+fn summary_mut(days: &mut BTreeMap<(String, u64), DailySummary>, time_ms: u64, contract_id: u64) -> &mut DailySummary {
+    let date = utc_date(time_ms);
+    days.entry((date.clone(), contract_id)).or_insert_with(|| DailySummary {
+        date,
+        contract_id,
+        ..Default::default()
+    })
+}
+
+/// `YYYY-MM-DD` for a Unix millisecond timestamp, using the days-to-civil
+/// algorithm so we don't need a date crate.
+pub(crate) fn utc_date(time_ms: u64) -> String {
+    let days = (time_ms / MILLIS_PER_DAY) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn parse_decimal(field: &'static str, value: &str) -> Result<Decimal, ReportError> {
+    Decimal::from_str(value.trim()).map_err(|_| ReportError::InvalidDecimal { field, value: value.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_1: u64 = 1_700_000_000_000; // 2023-11-14T22:13:20Z
+
+    fn fill(id: u64, time: u64, side: OrderSide, size: &str, price: &str, fee: &str, direction: LiquidityRole) -> Fill {
+        Fill {
+            id,
+            order_id: id,
+            contract_id: 1,
+            price: price.to_string(),
+            size: size.to_string(),
+            side,
+            time,
+            fee: fee.to_string(),
+            fee_asset_id: 0,
+            direction: Some(direction),
+        }
+    }
+
+    #[test]
+    fn test_daily_report() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(DAY_1), "2023-11-14");
+        assert_eq!(utc_date(951_782_400_000), "2000-02-29");
+
+        let day_2 = DAY_1 + MILLIS_PER_DAY;
+        let mut builder = ReportBuilder::new();
+        builder
+            .add_fills(&[
+                fill(1, DAY_1, OrderSide::Buy, "2", "100", "0.04", LiquidityRole::Maker),
+                fill(2, day_2, OrderSide::Sell, "1", "110", "0.055", LiquidityRole::Taker),
+                fill(2, day_2, OrderSide::Sell, "1", "110", "0.055", LiquidityRole::Taker),
+            ])
+            .add_funding(&[FundingPayment {
+                id: 1,
+                contract_id: 1,
+                funding_rate: "0.0001".to_string(),
+                position_size: "2".to_string(),
+                oracle_price: "105".to_string(),
+                amount: "-0.021".to_string(),
+                time: day_2,
+            }]);
+        let report = builder.build().unwrap();
+
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].traded_volume, Decimal::from(200));
+        assert_eq!(report[0].maker_fees, Decimal::new(4, 2));
+        assert_eq!(report[1].date, "2023-11-15");
+        assert_eq!(report[1].realized_pnl, Decimal::from(10));
+        assert_eq!(report[1].funding_paid, Decimal::new(21, 3));
+        assert_eq!(report[1].net_pnl(), Decimal::new(9924, 3));
+
+        let csv = to_csv(&report);
+        assert_eq!(csv.lines().nth(2), Some("2023-11-15,1,1,110,0,0.055,0.021,0,10,9.924"));
+        let json: serde_json::Value = serde_json::from_str(&to_json(&report).unwrap()).unwrap();
+        assert_eq!(json[1]["fundingPaid"], "0.021");
+    }
+}
@@ -0,0 +1,323 @@
+use crate::model::{ContractMeta, CreateOrderRequest, OrderSide, OrderType};
+use crate::order_manager::OrderManager;
+use crate::position::PositionTracker;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use thiserror::Error;
+
+/// Why the pre-trade checks refused an order. Surfaced as
+/// `ClientError::RiskRejected` so it can be told apart from exchange errors.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RiskViolation {
+    #[error("Kill switch is active")]
+    KillSwitchActive,
+    #[error("Order notional {notional} exceeds limit {limit}")]
+    MaxOrderNotional { notional: Decimal, limit: Decimal },
+    #[error("Order size {size} exceeds limit {limit}")]
+    MaxOrderSize { size: Decimal, limit: Decimal },
+    #[error("Price {price} is more than {max_deviation} away from reference {reference}")]
+    PriceBand { price: Decimal, reference: Decimal, max_deviation: Decimal },
+    #[error("No reference price for contract {0}")]
+    MissingReferencePrice(u64),
+    #[error("{open} open orders on contract {contract_id}, limit {limit}")]
+    MaxOpenOrders { contract_id: u64, open: usize, limit: usize },
+    #[error("Projected position {projected} on contract {contract_id} exceeds limit {limit}")]
+    MaxPosition { contract_id: u64, projected: Decimal, limit: Decimal },
+    #[error("Projected account position notional {projected} exceeds limit {limit}")]
+    MaxAccountPosition { projected: Decimal, limit: Decimal },
+    #[error("Price {price} is not a multiple of tick size {tick_size}")]
+    TickSize { price: Decimal, tick_size: Decimal },
+    #[error("Size {size} is not a multiple of step size {step_size}")]
+    StepSize { size: Decimal, step_size: Decimal },
+    #[error("{field} is not a valid decimal: {value:?}")]
+    InvalidDecimal { field: &'static str, value: String },
+}
+
+/// Limits for one contract. `None` disables the check.
+#[derive(Debug, Clone, Default)]
+pub struct RiskLimits {
+    pub max_order_notional: Option<Decimal>,
+    pub max_order_size: Option<Decimal>,
+    /// Maximum relative distance from the reference (mark/index) price, e.g. `0.05`.
+    pub max_price_deviation: Option<Decimal>,
+    pub max_open_orders: Option<usize>,
+    /// Maximum absolute net position, in contract size.
+    pub max_position: Option<Decimal>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RiskConfig {
+    /// Applied to contracts without an entry in `per_contract`.
+    pub default_limits: RiskLimits,
+    pub per_contract: HashMap<u64, RiskLimits>,
+    /// Maximum sum of `|position| * reference price` over all contracts.
+    pub max_account_position_notional: Option<Decimal>,
+    /// Reject prices and sizes that don't conform to the contract's tick and step.
+    pub enforce_tick_step: bool,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            default_limits: RiskLimits::default(),
+            per_contract: HashMap::new(),
+            max_account_position_notional: None,
+            enforce_tick_step: true,
+        }
+    }
+}
+
+impl RiskConfig {
+    pub fn limits(&self, contract_id: u64) -> &RiskLimits {
+        self.per_contract.get(&contract_id).unwrap_or(&self.default_limits)
+    }
+}
+
+/// Pre-trade checks run by `EdgeXClient::place_order` before an order is signed.
+///
+/// Open-order and position limits need an `OrderManager` / `PositionTracker`;
+/// without them those checks are skipped.
+pub struct RiskManager {
+    config: RwLock<RiskConfig>,
+    kill_switch: AtomicBool,
+    reference_prices: Mutex<HashMap<u64, Decimal>>,
+    orders: Option<Arc<OrderManager>>,
+    positions: Option<Arc<PositionTracker>>,
+}
+
+impl RiskManager {
+    pub fn new(config: RiskConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            kill_switch: AtomicBool::new(false),
+            reference_prices: Mutex::new(HashMap::new()),
+            orders: None,
+            positions: None,
+        }
+    }
+
+    pub fn with_order_manager(mut self, orders: Arc<OrderManager>) -> Self {
+        self.orders = Some(orders);
+        self
+    }
+
+    pub fn with_position_tracker(mut self, positions: Arc<PositionTracker>) -> Self {
+        self.positions = Some(positions);
+        self
+    }
+
+    pub fn config(&self) -> RiskConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: RiskConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Blocks every new order until `deactivate_kill_switch` is called.
+    pub fn activate_kill_switch(&self) {
+        self.kill_switch.store(true, Ordering::SeqCst);
+    }
+
+    pub fn deactivate_kill_switch(&self) {
+        self.kill_switch.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_kill_switch_active(&self) -> bool {
+        self.kill_switch.load(Ordering::SeqCst)
+    }
+
+    /// Current mark or index price used for the price band and account notional.
+    pub fn set_reference_price(&self, contract_id: u64, price: Decimal) {
+        self.reference_prices.lock().unwrap().insert(contract_id, price);
+    }
+
+    pub fn reference_price(&self, contract_id: u64) -> Option<Decimal> {
+        self.reference_prices.lock().unwrap().get(&contract_id).copied()
+    }
+
+    pub fn check_kill_switch(&self) -> Result<(), RiskViolation> {
+        if self.is_kill_switch_active() {
+            return Err(RiskViolation::KillSwitchActive);
+        }
+        Ok(())
+    }
+
+    /// Runs every configured check against `req`. Tick/step checks need `contract`.
+    pub fn check(&self, req: &CreateOrderRequest, contract: Option<&ContractMeta>) -> Result<(), RiskViolation> {
+        self.check_kill_switch()?;
+
+        let config = self.config.read().unwrap();
+        let limits = config.limits(req.contract_id);
+        let price = parse_decimal("price", &req.price)?;
+        let size = parse_decimal("size", &req.size)?;
+        let notional = price * size;
+
+        if let Some(limit) = limits.max_order_size
+            && size > limit
+        {
+            return Err(RiskViolation::MaxOrderSize { size, limit });
+        }
+        if let Some(limit) = limits.max_order_notional
+            && notional > limit
+        {
+            return Err(RiskViolation::MaxOrderNotional { notional, limit });
+        }
+
+        if config.enforce_tick_step
+            && let Some(contract) = contract
+        {
+            let tick_size = parse_decimal("tickSize", &contract.tick_size)?;
+            let step_size = parse_decimal("stepSize", &contract.step_size)?;
+            if req.r#type == OrderType::Limit && !is_multiple(price, tick_size) {
+                return Err(RiskViolation::TickSize { price, tick_size });
+            }
+            if !is_multiple(size, step_size) {
+                return Err(RiskViolation::StepSize { size, step_size });
+            }
+        }
+
+        if let Some(max_deviation) = limits.max_price_deviation {
+            let reference = self.reference_price(req.contract_id)
+                .ok_or(RiskViolation::MissingReferencePrice(req.contract_id))?;
+            if (price - reference).abs() > reference * max_deviation {
+                return Err(RiskViolation::PriceBand { price, reference, max_deviation });
+            }
+        }
+
+        if let (Some(limit), Some(orders)) = (limits.max_open_orders, &self.orders) {
+            let open = orders.open_orders(req.account_id, Some(req.contract_id)).len();
+            if open >= limit {
+                return Err(RiskViolation::MaxOpenOrders { contract_id: req.contract_id, open, limit });
+            }
+        }
+
+        let signed_size = match req.side {
+            OrderSide::Buy => size,
+            OrderSide::Sell => -size,
+        };
+        if let Some(positions) = &self.positions {
+            if let Some(limit) = limits.max_position {
+                let current = positions.position(req.contract_id).map(|p| p.net_size).unwrap_or_default();
+                let projected = current + signed_size + self.resting_size(req.account_id, req.contract_id, req.side);
+                if projected.abs() > limit {
+                    return Err(RiskViolation::MaxPosition { contract_id: req.contract_id, projected, limit });
+                }
+            }
+            if let Some(limit) = config.max_account_position_notional {
+                let prices = self.reference_prices.lock().unwrap();
+                let mut projected = Decimal::ZERO;
+                let mut counted_order = false;
+                for p in positions.positions() {
+                    let mut net = p.net_size;
+                    if p.contract_id == req.contract_id {
+                        net += signed_size;
+                        counted_order = true;
+                    }
+                    let mark = prices.get(&p.contract_id).copied().unwrap_or(p.avg_entry_price);
+                    projected += net.abs() * mark;
+                }
+                if !counted_order {
+                    projected += notional;
+                }
+                if projected > limit {
+                    return Err(RiskViolation::MaxAccountPosition { projected, limit });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Signed remaining size of resting orders on `side`, which would add to
+    /// the position if they filled.
+    fn resting_size(&self, account_id: u64, contract_id: u64, side: OrderSide) -> Decimal {
+        let Some(orders) = &self.orders else {
+            return Decimal::ZERO;
+        };
+        orders.open_orders(account_id, Some(contract_id)).iter()
+            .filter(|o| o.side == side)
+            .map(|o| {
+                let size = Decimal::from_str(&o.size).unwrap_or_default();
+                let filled = Decimal::from_str(&o.filled_size).unwrap_or_default();
+                let remaining = (size - filled).max(Decimal::ZERO);
+                if side == OrderSide::Buy { remaining } else { -remaining }
+            })
+            .sum()
+    }
+}
+
+fn is_multiple(value: Decimal, unit: Decimal) -> bool {
+    unit.is_zero() || (value % unit).is_zero()
+}
+
+fn parse_decimal(field: &'static str, value: &str) -> Result<Decimal, RiskViolation> {
+    Decimal::from_str(value.trim()).map_err(|_| RiskViolation::InvalidDecimal { field, value: value.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract() -> ContractMeta {
+        ContractMeta {
+            contract_id: 1,
+            contract_name: "BTCUSDT".to_string(),
+            tick_size: "0.5".to_string(),
+            step_size: "0.001".to_string(),
+            stark_ex_synthetic_asset_id: "0x1".to_string(),
+            stark_ex_resolution: "0x2540be400".to_string(),
+            default_maker_fee_rate: "0.0002".to_string(),
+            default_taker_fee_rate: "0.0005".to_string(),
+            risk_tier_list: Vec::new(),
+        }
+    }
+
+    fn order(price: &str, size: &str) -> CreateOrderRequest {
+        CreateOrderRequest::unsigned(1, 1, OrderSide::Buy, price, size)
+    }
+
+    #[test]
+    fn test_risk_checks() {
+        let positions = Arc::new(PositionTracker::new());
+        let risk = RiskManager::new(RiskConfig {
+            default_limits: RiskLimits {
+                max_order_notional: Some(Decimal::from(10_000)),
+                max_order_size: Some(Decimal::from(1)),
+                max_price_deviation: Some(Decimal::new(5, 2)),
+                max_open_orders: None,
+                max_position: Some(Decimal::new(15, 1)),
+            },
+            ..Default::default()
+        }).with_position_tracker(positions.clone());
+        let contract = contract();
+
+        assert_eq!(risk.check(&order("100", "0.5"), Some(&contract)), Err(RiskViolation::MissingReferencePrice(1)));
+        risk.set_reference_price(1, Decimal::from(100));
+
+        assert!(risk.check(&order("100", "0.5"), Some(&contract)).is_ok());
+        assert!(matches!(risk.check(&order("100", "2"), Some(&contract)), Err(RiskViolation::MaxOrderSize { .. })));
+        assert!(matches!(risk.check(&order("100.2", "0.5"), Some(&contract)), Err(RiskViolation::TickSize { .. })));
+        assert!(matches!(risk.check(&order("100", "0.0005"), Some(&contract)), Err(RiskViolation::StepSize { .. })));
+        assert!(matches!(risk.check(&order("110", "0.5"), Some(&contract)), Err(RiskViolation::PriceBand { .. })));
+
+        positions.apply_fill(&crate::model::Fill {
+            id: 1,
+            order_id: 1,
+            contract_id: 1,
+            price: "100".to_string(),
+            size: "1".to_string(),
+            side: OrderSide::Buy,
+            time: 0,
+            fee: "0".to_string(),
+            fee_asset_id: 0,
+            direction: None,
+        }).unwrap();
+        assert!(matches!(risk.check(&order("100", "1"), Some(&contract)), Err(RiskViolation::MaxPosition { .. })));
+
+        risk.activate_kill_switch();
+        assert_eq!(risk.check(&order("100", "0.1"), Some(&contract)), Err(RiskViolation::KillSwitchActive));
+    }
+}
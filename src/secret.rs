@@ -0,0 +1,148 @@
+use crate::signature::SignatureError;
+use starknet_types_core::felt::Felt;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Order of the Stark curve, big-endian. L2 private keys must be in `[1, EC_ORDER)`.
+pub(crate) const EC_ORDER: [u8; 32] = [
+    0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xb7, 0x81, 0x12, 0x6d, 0xca, 0xe7, 0xb2, 0x32, 0x1e, 0x66, 0xa2, 0x41, 0xad, 0xc6, 0x4d, 0x2f,
+];
+
+/// L2 (Stark) private key.
+///
+/// The key bytes are wiped when the value is dropped. It deliberately implements
+/// neither `Debug`, `Display`, `Clone` nor `Serialize`, so it can't be copied
+/// around or end up in a log line by accident.
+pub struct StarkSecretKey {
+    bytes: Zeroizing<[u8; 32]>,
+}
+
+impl StarkSecretKey {
+    /// Parses a hex key, with or without `0x`.
+    pub fn from_hex(hex_key: &str) -> Result<Self, SignatureError> {
+        let digits = hex_key.trim().trim_start_matches("0x");
+        if digits.is_empty() || digits.len() > 64 {
+            return Err(SignatureError::FeltError);
+        }
+        let padded = Zeroizing::new(format!("{:0>64}", digits));
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        hex::decode_to_slice(padded.as_str(), bytes.as_mut())?;
+        Self::from_bytes(bytes)
+    }
+
+    /// Reads a hex key from the environment variable `var`.
+    pub fn from_env(var: &str) -> Result<Self, SignatureError> {
+        let value = Zeroizing::new(std::env::var(var)
+            .map_err(|e| SignatureError::KeyLoadError(format!("{}: {}", var, e)))?);
+        Self::from_hex(&value)
+    }
+
+    /// Reads a hex key from a file. On Unix the file must not be accessible by
+    /// group or others (i.e. mode `0600` or stricter).
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SignatureError> {
+        let path = path.as_ref();
+        check_file_permissions(path)?;
+        let contents = Zeroizing::new(std::fs::read_to_string(path)
+            .map_err(|e| SignatureError::KeyLoadError(format!("{}: {}", path.display(), e)))?);
+        Self::from_hex(&contents)
+    }
+
+    /// Decrypts an Ethereum-style JSON keystore (scrypt or PBKDF2, AES-128-CTR).
+    pub fn from_keystore(path: impl AsRef<Path>, password: &str) -> Result<Self, SignatureError> {
+        let raw = Zeroizing::new(eth_keystore::decrypt_key(path, password)
+            .map_err(|e| SignatureError::KeyLoadError(e.to_string()))?);
+        if raw.len() != 32 {
+            return Err(SignatureError::KeyLoadError(format!("expected a 32 byte key, got {}", raw.len())));
+        }
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        bytes.copy_from_slice(&raw);
+        Self::from_bytes(bytes)
+    }
+
+    /// Encrypts the key into a JSON keystore under `dir`, returning the file name.
+    pub fn to_keystore(&self, dir: impl AsRef<Path>, password: &str, name: Option<&str>) -> Result<String, SignatureError> {
+        eth_keystore::encrypt_key(dir, &mut rand::thread_rng(), self.bytes.as_ref(), password, name)
+            .map_err(|e| SignatureError::KeyLoadError(e.to_string()))
+    }
+
+    pub(crate) fn from_felt(felt: &Felt) -> Result<Self, SignatureError> {
+        Self::from_bytes(Zeroizing::new(felt.to_bytes_be()))
+    }
+
+    fn from_bytes(bytes: Zeroizing<[u8; 32]>) -> Result<Self, SignatureError> {
+        if bytes.iter().all(|b| *b == 0) {
+            return Err(SignatureError::KeyLoadError("key is zero".to_string()));
+        }
+        // Both are big-endian, so byte order is numeric order.
+        if *bytes >= EC_ORDER {
+            return Err(SignatureError::KeyLoadError("key is not below the Stark curve order".to_string()));
+        }
+        Ok(Self { bytes })
+    }
+
+    /// Materializes the key as a field element for a single signing operation.
+    /// `Felt` is `Copy`, so callers should keep the result as short-lived as possible.
+    pub(crate) fn expose_felt(&self) -> Felt {
+        Felt::from_bytes_be(&self.bytes)
+    }
+}
+
+#[cfg(unix)]
+fn check_file_permissions(path: &Path) -> Result<(), SignatureError> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| SignatureError::KeyLoadError(format!("{}: {}", path.display(), e)))?;
+    let mode = metadata.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(SignatureError::InsecureKeyFile(format!(
+            "{} has mode {:o}, expected 600 or stricter", path.display(), mode & 0o777
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_file_permissions(_path: &Path) -> Result<(), SignatureError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_key_loading() {
+        let key = "0x0234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let expected = StarkSecretKey::from_hex(key).unwrap().expose_felt();
+
+        assert!(StarkSecretKey::from_hex("0x0").is_err());
+        assert!(StarkSecretKey::from_hex(&hex::encode(EC_ORDER)).is_err());
+        assert!(StarkSecretKey::from_hex(&format!("0x{}", "f".repeat(64))).is_err());
+        let mut largest = EC_ORDER;
+        largest[31] -= 1;
+        assert!(StarkSecretKey::from_hex(&hex::encode(largest)).is_ok());
+        assert!(StarkSecretKey::from_hex(&format!("0x{}", "f".repeat(65))).is_err());
+
+        let dir = std::env::temp_dir().join(format!("edgex-secret-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("stark.key");
+        std::fs::write(&path, key).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(matches!(StarkSecretKey::from_file(&path), Err(SignatureError::InsecureKeyFile(_))));
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        assert_eq!(StarkSecretKey::from_file(&path).unwrap().expose_felt(), expected);
+
+        let name = StarkSecretKey::from_hex(key).unwrap().to_keystore(&dir, "hunter2", None).unwrap();
+        let loaded = StarkSecretKey::from_keystore(dir.join(&name), "hunter2").unwrap();
+        assert_eq!(loaded.expose_felt(), expected);
+        assert!(StarkSecretKey::from_keystore(dir.join(&name), "wrong").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
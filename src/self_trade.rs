@@ -0,0 +1,192 @@
+//! Client-side self-trade prevention, run by `EdgeXClient::place_order` when
+//! configured with `with_self_trade_prevention`.
+//!
+//! Strategies sharing an account can cross each other. Once an order is
+//! signed, and before it is sent, it is compared with the account's open
+//! orders on the same contract, and the configured mode decides what gives way.
+
+use crate::model::{CreateOrderRequest, OpenOrder, OrderSide, OrderType};
+use crate::order_manager::{OrderManager, TrackedOrder};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTradeMode {
+    /// Drop the incoming order, failing `place_order` with
+    /// `ClientError::SelfTradePrevented`; resting orders stay.
+    CancelNewest,
+    /// Cancel the resting orders it would trade against, then send it.
+    CancelOldest,
+    /// Cancel the resting orders and drop the incoming one, as `CancelNewest` does.
+    CancelBoth,
+    /// Fail `place_order` with `ClientError::SelfTradeRejected`.
+    Reject,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{side:?} order on contract {contract_id} would trade against own orders {resting:?}")]
+pub struct SelfTradeViolation {
+    pub contract_id: u64,
+    pub side: OrderSide,
+    /// Exchange ids of the open orders it crosses.
+    pub resting: Vec<u64>,
+}
+
+/// An open order of the account, as far as the check needs it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestingOrder {
+    pub order_id: u64,
+    pub contract_id: u64,
+    pub side: OrderSide,
+    pub price: Decimal,
+}
+
+impl RestingOrder {
+    pub fn from_open_order(order: &OpenOrder) -> Option<Self> {
+        Some(Self {
+            order_id: order.order_id,
+            contract_id: order.contract_id,
+            side: order.side,
+            price: Decimal::from_str(&order.price).ok()?,
+        })
+    }
+
+    /// `None` for orders not acknowledged yet.
+    pub fn from_tracked(order: &TrackedOrder) -> Option<Self> {
+        Some(Self {
+            order_id: order.order_id?,
+            contract_id: order.contract_id,
+            side: order.side,
+            price: Decimal::from_str(&order.price).ok()?,
+        })
+    }
+}
+
+/// What to do with a new order: cancel these resting orders first, then send
+/// it or not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTradeResolution {
+    pub cancel: Vec<u64>,
+    pub send: bool,
+}
+
+pub struct SelfTradePrevention {
+    mode: SelfTradeMode,
+    orders: Option<Arc<OrderManager>>,
+}
+
+impl SelfTradePrevention {
+    pub fn new(mode: SelfTradeMode) -> Self {
+        Self { mode, orders: None }
+    }
+
+    /// Checks against the orders tracked by `orders` instead of fetching the
+    /// account's open orders before every submission.
+    pub fn with_order_manager(mut self, orders: Arc<OrderManager>) -> Self {
+        self.orders = Some(orders);
+        self
+    }
+
+    pub fn mode(&self) -> SelfTradeMode {
+        self.mode
+    }
+
+    pub fn order_manager(&self) -> Option<&Arc<OrderManager>> {
+        self.orders.as_ref()
+    }
+
+    /// Decides what happens to `req` given the account's open orders. Market
+    /// orders without a worst price are taken to cross every opposite order.
+    pub fn check(&self, req: &CreateOrderRequest, open_orders: &[RestingOrder]) -> Result<SelfTradeResolution, SelfTradeViolation> {
+        let price = Decimal::from_str(&req.price).ok()
+            .filter(|p| !(req.r#type == OrderType::Market && p.is_zero()));
+        let resting: Vec<u64> = open_orders.iter()
+            .filter(|o| o.contract_id == req.contract_id && o.side != req.side)
+            .filter(|o| match (req.side, price) {
+                (_, None) => true,
+                (OrderSide::Buy, Some(price)) => o.price <= price,
+                (OrderSide::Sell, Some(price)) => o.price >= price,
+            })
+            .map(|o| o.order_id)
+            .collect();
+        if resting.is_empty() {
+            return Ok(SelfTradeResolution { cancel: Vec::new(), send: true });
+        }
+        match self.mode {
+            SelfTradeMode::CancelNewest => Ok(SelfTradeResolution { cancel: Vec::new(), send: false }),
+            SelfTradeMode::CancelOldest => Ok(SelfTradeResolution { cancel: resting, send: true }),
+            SelfTradeMode::CancelBoth => Ok(SelfTradeResolution { cancel: resting, send: false }),
+            SelfTradeMode::Reject => Err(SelfTradeViolation { contract_id: req.contract_id, side: req.side, resting }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientError, EdgeXClient};
+    use crate::mock::MockExchange;
+    use crate::order_manager::strategy_tag;
+    use crate::signature::SignatureManager;
+
+    const KEY: &str = "0x1";
+    const ACCOUNT_ID: u64 = 1;
+    const CONTRACT: u64 = 10000001;
+
+    #[tokio::test]
+    async fn test_self_trade_modes_against_mock_exchange() {
+        let mock = MockExchange::start().await.unwrap();
+        mock.register_account(ACCOUNT_ID, SignatureManager::new(KEY).unwrap().public_key());
+        let meta = mock.metadata().clone();
+        let client = |mode| {
+            EdgeXClient::new(KEY, Some(mock.base_url())).unwrap()
+                .with_account_id(ACCOUNT_ID)
+                .with_strategy_tag("mm").unwrap()
+                .with_self_trade_prevention(Arc::new(SelfTradePrevention::new(mode)))
+        };
+        let order = |side, price: i64| {
+            CreateOrderRequest::limit(&meta, ACCOUNT_ID, CONTRACT, side, Decimal::from(price), Decimal::new(1, 2)).unwrap()
+        };
+
+        let reject = client(SelfTradeMode::Reject);
+        reject.time_sync().sync().await.unwrap();
+        reject.place_order(order(OrderSide::Sell, 50_000), &meta).await.unwrap();
+        // Below the resting sell: no conflict.
+        reject.place_order(order(OrderSide::Buy, 49_000), &meta).await.unwrap();
+        assert!(matches!(
+            reject.place_order(order(OrderSide::Buy, 50_000), &meta).await,
+            Err(ClientError::SelfTradeRejected(SelfTradeViolation { ref resting, .. })) if resting.len() == 1
+        ));
+        let open = mock.orders(ACCOUNT_ID);
+        assert_eq!(open.len(), 2);
+        assert!(open.iter().all(|o| o.client_order_id.as_deref().and_then(strategy_tag) == Some("mm")));
+
+        // Cancel-newest drops the new order and reports it as not placed.
+        assert!(matches!(
+            client(SelfTradeMode::CancelNewest).place_order(order(OrderSide::Buy, 51_000), &meta).await,
+            Err(ClientError::SelfTradePrevented { ref cancelled }) if cancelled.is_empty()
+        ));
+        assert_eq!(mock.orders(ACCOUNT_ID).len(), 2);
+
+        // Cancel-oldest pulls the resting sell, then sends the buy.
+        let response = client(SelfTradeMode::CancelOldest).place_order(order(OrderSide::Buy, 51_000), &meta).await.unwrap();
+        assert_eq!(response["code"], "SUCCESS");
+        let mut statuses: Vec<_> = mock.orders(ACCOUNT_ID).iter().map(|o| (o.side, o.status.clone())).collect();
+        statuses.sort_by_key(|(_, status)| status.clone());
+        assert_eq!(statuses, [
+            (OrderSide::Sell, "CANCELED".to_string()),
+            (OrderSide::Buy, "OPEN".to_string()),
+            (OrderSide::Buy, "OPEN".to_string()),
+        ]);
+
+        // Cancel-both pulls both resting buys and drops the sell.
+        assert!(matches!(
+            client(SelfTradeMode::CancelBoth).place_order(order(OrderSide::Sell, 48_000), &meta).await,
+            Err(ClientError::SelfTradePrevented { ref cancelled }) if cancelled.len() == 2
+        ));
+        assert!(mock.orders(ACCOUNT_ID).iter().all(|o| o.status == "CANCELED"));
+        assert!(EdgeXClient::new(KEY, Some(mock.base_url())).unwrap().with_strategy_tag("market-maker").is_err());
+    }
+}
@@ -3,9 +3,11 @@ use ethers::signers::WalletError; // Keep if using WalletError wrapping, or remo
 // Let's remove WalletError dependency if possible or keep for compat.
 // But we should use starknet types.
 use starknet_types_core::felt::Felt;
-use starknet_crypto::{pedersen_hash, sign};
+use starknet_crypto::{sign, verify, Signature};
+use starknet_types_core::curve::{AffinePoint, ProjectivePoint};
 use thiserror::Error;
-use std::str::FromStr;
+use crate::secret::StarkSecretKey;
+use crate::stark_order::{parse_asset_id, OrderHashError, StarkLimitOrder};
 
 #[derive(Error, Debug)]
 pub enum SignatureError {
@@ -17,6 +19,18 @@ pub enum SignatureError {
     FeltError,
     #[error("Signing error")]
     SigningError,
+    #[error("Invalid signature format: {0}")]
+    InvalidSignatureFormat(String),
+    #[error("Verification error: {0}")]
+    VerifyError(String),
+    #[error("Remote signer error: {0}")]
+    RemoteSignerError(String),
+    #[error("Key load error: {0}")]
+    KeyLoadError(String),
+    #[error("Insecure key file: {0}")]
+    InsecureKeyFile(String),
+    #[error("Order hash error: {0}")]
+    OrderHashError(#[from] OrderHashError),
 }
 
 // StarkNet Prime (2^251 + 17 * 2^192 + 1)
@@ -24,20 +38,39 @@ pub enum SignatureError {
 // which matches the Stark curve prime.
 
 pub struct SignatureManager {
-    private_key: Felt, // L2 Private Key (Stark Key)
+    private_key: StarkSecretKey, // L2 Private Key (Stark Key)
     // We might also need L1 wallet for onboarding, but for L2 actions we need L2 key.
 }
 
 impl SignatureManager {
     pub fn new(l2_private_key_hex: &str) -> Result<Self, SignatureError> {
-        let key_str = l2_private_key_hex.trim_start_matches("0x");
-        let private_key = Felt::from_hex(key_str)
-            .map_err(|_| SignatureError::FeltError)?;
+        let private_key = StarkSecretKey::from_hex(l2_private_key_hex)?;
         Ok(Self { private_key })
     }
 
+    /// Builds a manager from an already loaded key (env var, file or keystore).
+    pub fn from_secret(private_key: StarkSecretKey) -> Self {
+        Self { private_key }
+    }
+
+    /// Stark public key (x-coordinate), i.e. the `l2Key` registered with the exchange.
+    pub fn public_key(&self) -> Felt {
+        starknet_crypto::get_public_key(&self.private_key.expose_felt())
+    }
+
+    /// Full public key point `(x, y)`.
+    pub fn public_key_point(&self) -> AffinePoint {
+        public_key_point(&self.private_key.expose_felt())
+    }
+
+    /// Verifies a signature produced by this manager's key.
+    pub fn verify(&self, hash: &Felt, signature: &Signature) -> Result<bool, SignatureError> {
+        verify_signature(&self.public_key(), hash, signature)
+    }
+
     /// Calculates the Pedersen hash for a limit order (Order with fees).
     /// Replicates the logic from EdgeX Python SDK `calc_limit_order_hash`.
+    #[allow(clippy::too_many_arguments)]
     pub fn calc_limit_order_hash(
         &self,
         synthetic_asset_id: &str,
@@ -51,80 +84,29 @@ impl SignatureManager {
         account_id: u64,
         expire_time: u64,
     ) -> Result<Felt, SignatureError> {
-        // Parse Asset IDs
-        let syn_id = Felt::from_hex(synthetic_asset_id.trim_start_matches("0x"))
-            .map_err(|_| SignatureError::FeltError)?;
-        let col_id = Felt::from_hex(collateral_asset_id.trim_start_matches("0x"))
-            .map_err(|_| SignatureError::FeltError)?;
-        let fee_id = Felt::from_hex(fee_asset_id.trim_start_matches("0x"))
-            .map_err(|_| SignatureError::FeltError)?;
-
-        let (asset_id_sell, asset_id_buy, amount_sell, amount_buy) = if is_buy {
-            (col_id, syn_id, amount_collateral, amount_synthetic)
-        } else {
-            (syn_id, col_id, amount_synthetic, amount_collateral)
+        // Kept for callers that already have the raw fields; prefer building a
+        // `StarkLimitOrder` so the amounts can't be swapped silently.
+        let order = StarkLimitOrder {
+            synthetic_asset_id: parse_asset_id(synthetic_asset_id)?,
+            collateral_asset_id: parse_asset_id(collateral_asset_id)?,
+            fee_asset_id: parse_asset_id(fee_asset_id)?,
+            is_buy,
+            amount_synthetic,
+            amount_collateral,
+            amount_fee,
+            nonce,
+            position_id: account_id,
+            expiration_hours: expire_time,
         };
-
-        // First hash: hash(asset_id_sell, asset_id_buy)
-        let msg = pedersen_hash(&asset_id_sell, &asset_id_buy);
-
-        // Second hash: hash(msg, asset_id_fee)
-        let msg = pedersen_hash(&msg, &fee_id);
-
-        // Pack message 0
-        // packed_message0 = amount_sell * 2^64 + amount_buy * 2^64 + max_amount_fee * 2^32 + nonce
-        // Note: Felt doesn't support '<<' directly for non-Felt inputs easily unless we convert.
-        // But we can construct BigUint or perform check.
-        // Since we are using Felt which is 252 bits, we can try to compose it.
-        // The python code does: val = (val << 64) + next_val.
-        
-        // Helper to shift and add
-        let shift_add = |acc: Felt, val: u64, shift: u32| -> Felt {
-            // acc * 2^shift + val
-            // Felt::pow takes u128.
-            let shift_multiplier = Felt::from(2u64).pow(shift as u128);
-            (acc * shift_multiplier) + Felt::from(val)
-        };
-        
-        // Wait, does Felt implement std::ops::Add etc? Yes usually.
-
-        let pm0 = Felt::from(amount_sell);
-        let pm0 = shift_add(pm0, amount_buy, 64);
-        let pm0 = shift_add(pm0, amount_fee, 64);
-        let pm0 = shift_add(pm0, nonce, 32);
-        // implicit modulo prime is handled by Felt arithmetic
-
-        // Third hash: hash(msg, packed_message0)
-        let msg = pedersen_hash(&msg, &pm0);
-
-        // Pack message 1
-        // packed_message1 = LIMIT_ORDER_WITH_FEE_TYPE * 2^64 + account_id * 2^64 + account_id * 2^64 + account_id * 2^32 + expiration_timestamp * 2^17
-        // Python:
-        // packed_message1 = LIMIT_ORDER_WITH_FEE_TYPE  # 3
-        // packed_message1 = (packed_message1 << 64) + account_id
-        // packed_message1 = (packed_message1 << 64) + account_id
-        // packed_message1 = (packed_message1 << 64) + account_id
-        // packed_message1 = (packed_message1 << 32) + expire_time
-        // packed_message1 = packed_message1 << 17
-        
-        let limit_order_type = 3u64;
-        let pm1 = Felt::from(limit_order_type);
-        let pm1 = shift_add(pm1, account_id, 64);
-        let pm1 = shift_add(pm1, account_id, 64);
-        let pm1 = shift_add(pm1, account_id, 64);
-        let pm1 = shift_add(pm1, expire_time, 32);
-        
-        // Final shift by 17 (padding)
-        let shift_17 = Felt::from(2u64).pow(17u128);
-        let pm1 = pm1 * shift_17;
-
-        // Final hash: hash(msg, packed_message1)
-        let msg = pedersen_hash(&msg, &pm1);
-
-        Ok(msg)
+        Ok(order.hash()?)
     }
 
     pub fn sign_l2_action(&self, hash: Felt) -> Result<String, SignatureError> {
+        Ok(format_signature(&self.sign_hash(&hash)?))
+    }
+
+    /// Signs a message hash, returning the raw `(r, s)` pair.
+    pub fn sign_hash(&self, hash: &Felt) -> Result<Signature, SignatureError> {
         // Sign with k value (randomness). API often expects standard ECDSA signature (r, s).
         // starknet_crypto::sign usage: sign(private_key, message_hash, k)
         // We need a random k.
@@ -164,51 +146,73 @@ impl SignatureManager {
         
         let k = Felt::from_bytes_be(&bytes);
         
-        let signature = sign(&self.private_key, &hash, &k).map_err(|_| SignatureError::SigningError)?;
-        
-        // Format: r, s. Usually hex strings.
-        // API expects... "l2Signature".
-        // Often formatted as `r` and `s` or concatenated.
-        // EdgeX docs say "l2Signature": "0x..."
-        // I will return r and s packed or check doc again.
-        // Docs usually want: r, s as hex strings, or packed 0x{r}{s}.
-        // Common Starknet format is often JSON `[r, s]`.
-        // Let's assume standard hex concatenation for now given "0x..." string type.
-        // 0x + r_hex + s_hex
-        
-        let r_hex = format!("{:064x}", signature.r);
-        let s_hex = format!("{:064x}", signature.s);
-        Ok(format!("0x{}{}", r_hex, s_hex))
+        let signature = sign(&self.private_key.expose_felt(), hash, &k).map_err(|_| SignatureError::SigningError)?;
+        Ok(signature.into())
     }
     
-    // Kept for Header signing if different keys are used
-    pub async fn sign_message(&self, _message: &str) -> Result<String, SignatureError> {
-        // This likely needs L1 key if it is 'ethers' style. 
-        // The L2 key is a Felt, not compatible with 'LocalWallet' (Secp256k1).
-        // If 'X-edgeX-Api-Signature' is also L2 key based, we need to sign the hash of the generic message.
-        // But headers usually use the L2 key with generic hash?
-        // IF L2 key is used for headers, we hash the message (keccak or pedersen?) and sign.
-        // Docs said: "The signature generated using the private key and request details".
-        // If it's the L2 key, it must be Stark curve.
-        
-        // Assume Header signature also uses Stark key on Pedersen hash of the string?
-        // Or Keccak hash of string?
-        // "Method + Path + Body" -> usually Keccak or SHA256. 
-        // StarkEx usually uses Pedersen for L2 data (Orders), but REST headers might be standard.
-        // Let's assume one key for everything for now, but watch out.
-        
-        Err(SignatureError::SigningError) // Placeholder
+    /// Signs the `X-edgeX-Api-Signature` content with the L2 key.
+    /// The message is keccak256-hashed and truncated to 250 bits (same as
+    /// `starknet_keccak`) so it is a valid Stark message hash.
+    pub async fn sign_message(&self, message: &str) -> Result<String, SignatureError> {
+        self.sign_l2_action(message_hash(message))
     }
 }
 
+/// Computes `private_key * G` on the Stark curve.
+pub(crate) fn public_key_point(private_key: &Felt) -> AffinePoint {
+    let generator = ProjectivePoint::from_affine(
+        starknet_curve::curve_params::GENERATOR.x(),
+        starknet_curve::curve_params::GENERATOR.y(),
+    ).unwrap();
+    (&generator * *private_key).to_affine().unwrap()
+}
+
+/// Verifies `(r, s)` over `hash` against a Stark public key (x-coordinate).
+pub fn verify_signature(public_key: &Felt, hash: &Felt, signature: &Signature) -> Result<bool, SignatureError> {
+    verify(public_key, hash, &signature.r, &signature.s)
+        .map_err(|e| SignatureError::VerifyError(e.to_string()))
+}
+
+/// Formats `(r, s)` as `0x{r}{s}`, each component zero-padded to 32 bytes.
+/// This is the `l2Signature` / `X-edgeX-Api-Signature` wire format.
+pub fn format_signature(signature: &Signature) -> String {
+    format!("0x{:064x}{:064x}", signature.r, signature.s)
+}
+
+/// Parses the `0x{r}{s}` string emitted by `sign_l2_action` back into `(r, s)`.
+pub fn parse_signature(signature: &str) -> Result<Signature, SignatureError> {
+    let hex_str = signature.trim_start_matches("0x");
+    if hex_str.len() != 128 {
+        return Err(SignatureError::InvalidSignatureFormat(format!(
+            "expected 128 hex chars, got {}", hex_str.len()
+        )));
+    }
+    let bytes = hex::decode(hex_str)?;
+    let mut r = [0u8; 32];
+    let mut s = [0u8; 32];
+    r.copy_from_slice(&bytes[..32]);
+    s.copy_from_slice(&bytes[32..]);
+    Ok(Signature {
+        r: Felt::from_bytes_be(&r),
+        s: Felt::from_bytes_be(&s),
+    })
+}
+
+/// keccak256 of `message`, masked to 250 bits.
+pub fn message_hash(message: &str) -> Felt {
+    let mut digest = ethers::utils::keccak256(message.as_bytes());
+    digest[0] &= 0x03;
+    Felt::from_bytes_be(&digest)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_signature_generation() {
-        // Dummy key (valid hex)
-        let key = "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        // Dummy key (below the curve order)
+        let key = "0x0234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
         let manager = SignatureManager::new(key).unwrap();
 
         // Test limit order hash calculation
@@ -225,4 +229,20 @@ mod tests {
         assert!(signature.starts_with("0x"));
         assert_eq!(signature.len(), 2 + 64 + 64); // 0x + r(64) + s(64)
     }
+
+    #[test]
+    fn test_signature_verification() {
+        let key = "0x0234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let manager = SignatureManager::new(key).unwrap();
+
+        let point = manager.public_key_point();
+        assert_eq!(point.x(), manager.public_key());
+
+        let hash = message_hash("1700000000000GET/api/v1/private/order/getFillsaccountId=1");
+        let signature = parse_signature(&manager.sign_l2_action(hash).unwrap()).unwrap();
+        assert!(manager.verify(&hash, &signature).unwrap());
+        assert!(!verify_signature(&manager.public_key(), &(hash + Felt::ONE), &signature).unwrap());
+
+        assert!(matches!(parse_signature("0x1234"), Err(SignatureError::InvalidSignatureFormat(_))));
+    }
 }
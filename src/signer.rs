@@ -0,0 +1,272 @@
+use crate::signature::{SignatureError, SignatureManager};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use starknet_crypto::Signature;
+use starknet_types_core::felt::Felt;
+
+const SIGN_PATH: &str = "/v1/sign";
+const PUBLIC_KEY_PATH: &str = "/v1/public-key";
+
+/// Anything that can produce Stark signatures for an L2 key.
+///
+/// `EdgeXClient` only talks to its key through this trait, for both L2 action
+/// signatures and `X-edgeX-Api-Signature` headers, so the key itself can live
+/// in an HSM, a KMS or a separate signing service.
+#[async_trait]
+pub trait StarkSigner: Send + Sync {
+    /// Stark public key (x-coordinate).
+    async fn public_key(&self) -> Result<Felt, SignatureError>;
+
+    /// Signs a message hash, returning `(r, s)`.
+    async fn sign_hash(&self, hash: &Felt) -> Result<Signature, SignatureError>;
+}
+
+/// In-memory signer: the key is held by this process.
+#[async_trait]
+impl StarkSigner for SignatureManager {
+    async fn public_key(&self) -> Result<Felt, SignatureError> {
+        Ok(SignatureManager::public_key(self))
+    }
+
+    async fn sign_hash(&self, hash: &Felt) -> Result<Signature, SignatureError> {
+        SignatureManager::sign_hash(self, hash)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteSignRequest {
+    pub key_id: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteSignResponse {
+    pub r: String,
+    pub s: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RemotePublicKeyResponse {
+    pub public_key: String,
+}
+
+/// Signer backed by a remote signing service speaking a small JSON-over-HTTP protocol:
+///
+/// - `POST /v1/sign` with `{"keyId", "hash"}` returns `{"r", "s"}`
+/// - `GET /v1/public-key?keyId=...` returns `{"publicKey"}`
+///
+/// Every returned signature is verified against the service's public key before
+/// it is handed back, so a misbehaving signer can't get a bad signature onto the wire.
+pub struct RemoteSigner {
+    http: Client,
+    endpoint: String,
+    key_id: String,
+    auth_token: Option<String>,
+    public_key: tokio::sync::OnceCell<Felt>,
+}
+
+impl RemoteSigner {
+    pub fn new(endpoint: &str, key_id: &str) -> Result<Self, SignatureError> {
+        let http = Client::builder()
+            .build()
+            .map_err(|e| SignatureError::RemoteSignerError(e.to_string()))?;
+        Ok(Self {
+            http,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            key_id: key_id.to_string(),
+            auth_token: None,
+            public_key: tokio::sync::OnceCell::new(),
+        })
+    }
+
+    /// Sends `Authorization: Bearer <token>` with every call.
+    pub fn with_auth_token(mut self, token: &str) -> Self {
+        self.auth_token = Some(token.to_string());
+        self
+    }
+
+    fn authorize(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.auth_token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    async fn fetch_public_key(&self) -> Result<Felt, SignatureError> {
+        let url = format!("{}{}", self.endpoint, PUBLIC_KEY_PATH);
+        let res = self.authorize(self.http.get(&url).query(&[("keyId", &self.key_id)]))
+            .send()
+            .await
+            .map_err(|e| SignatureError::RemoteSignerError(e.to_string()))?;
+        let body: RemotePublicKeyResponse = read_remote(res).await?;
+        parse_felt(&body.public_key)
+    }
+}
+
+#[async_trait]
+impl StarkSigner for RemoteSigner {
+    async fn public_key(&self) -> Result<Felt, SignatureError> {
+        self.public_key
+            .get_or_try_init(|| self.fetch_public_key())
+            .await
+            .copied()
+    }
+
+    async fn sign_hash(&self, hash: &Felt) -> Result<Signature, SignatureError> {
+        let url = format!("{}{}", self.endpoint, SIGN_PATH);
+        let req = RemoteSignRequest {
+            key_id: self.key_id.clone(),
+            hash: format!("{:#x}", hash),
+        };
+        let res = self.authorize(self.http.post(&url).json(&req))
+            .send()
+            .await
+            .map_err(|e| SignatureError::RemoteSignerError(e.to_string()))?;
+        let body: RemoteSignResponse = read_remote(res).await?;
+        let signature = Signature {
+            r: parse_felt(&body.r)?,
+            s: parse_felt(&body.s)?,
+        };
+
+        let public_key = self.public_key().await?;
+        if !crate::signature::verify_signature(&public_key, hash, &signature)? {
+            return Err(SignatureError::RemoteSignerError(
+                "remote signer returned a signature that does not verify".to_string(),
+            ));
+        }
+        Ok(signature)
+    }
+}
+
+async fn read_remote<T: serde::de::DeserializeOwned>(res: reqwest::Response) -> Result<T, SignatureError> {
+    let status = res.status();
+    if !status.is_success() {
+        let text = res.text().await.unwrap_or_default();
+        return Err(SignatureError::RemoteSignerError(format!("Status: {}, Body: {}", status, text)));
+    }
+    res.json().await.map_err(|e| SignatureError::RemoteSignerError(e.to_string()))
+}
+
+fn parse_felt(hex_str: &str) -> Result<Felt, SignatureError> {
+    Felt::from_hex(hex_str).map_err(|_| SignatureError::FeltError)
+}
+
+#[cfg(any(test, feature = "mock-server"))]
+pub use stub::SignerStub;
+
+#[cfg(any(test, feature = "mock-server"))]
+mod stub {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+
+    /// Local stand-in for a remote signing service, serving the `RemoteSigner`
+    /// protocol on `127.0.0.1` from an in-memory key. Meant for tests and local development.
+    pub struct SignerStub {
+        addr: SocketAddr,
+        shutdown: Option<oneshot::Sender<()>>,
+    }
+
+    impl SignerStub {
+        /// Starts the stub on an ephemeral port. Any `keyId` is accepted.
+        pub async fn start(manager: SignatureManager) -> Result<Self, SignatureError> {
+            let manager = Arc::new(manager);
+            let make_svc = make_service_fn(move |_| {
+                let manager = manager.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req| stub_handle(manager.clone(), req)))
+                }
+            });
+
+            let server = Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+                .map_err(|e| SignatureError::RemoteSignerError(e.to_string()))?
+                .serve(make_svc);
+            let addr = server.local_addr();
+            let (tx, rx) = oneshot::channel();
+            tokio::spawn(server.with_graceful_shutdown(async {
+                let _ = rx.await;
+            }));
+
+            Ok(Self { addr, shutdown: Some(tx) })
+        }
+
+        pub fn endpoint(&self) -> String {
+            format!("http://{}", self.addr)
+        }
+    }
+
+    impl Drop for SignerStub {
+        fn drop(&mut self) {
+            if let Some(tx) = self.shutdown.take() {
+                let _ = tx.send(());
+            }
+        }
+    }
+
+    async fn stub_handle(manager: Arc<SignatureManager>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let json = |status: StatusCode, body: String| {
+            Response::builder()
+                .status(status)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        match (req.method(), req.uri().path()) {
+            (&Method::GET, PUBLIC_KEY_PATH) => {
+                let body = RemotePublicKeyResponse {
+                    public_key: format!("{:#x}", manager.public_key()),
+                };
+                Ok(json(StatusCode::OK, serde_json::to_string(&body).unwrap()))
+            }
+            (&Method::POST, SIGN_PATH) => {
+                let bytes = match hyper::body::to_bytes(req.into_body()).await {
+                    Ok(bytes) => bytes,
+                    Err(e) => return Ok(json(StatusCode::BAD_REQUEST, e.to_string())),
+                };
+                let signed = serde_json::from_slice::<RemoteSignRequest>(&bytes)
+                    .map_err(|e| e.to_string())
+                    .and_then(|r| parse_felt(&r.hash).map_err(|e| e.to_string()))
+                    .and_then(|hash| manager.sign_hash(&hash).map_err(|e| e.to_string()));
+                match signed {
+                    Ok(signature) => {
+                        let body = RemoteSignResponse {
+                            r: format!("{:#x}", signature.r),
+                            s: format!("{:#x}", signature.s),
+                        };
+                        Ok(json(StatusCode::OK, serde_json::to_string(&body).unwrap()))
+                    }
+                    Err(e) => Ok(json(StatusCode::BAD_REQUEST, e)),
+                }
+            }
+            _ => Ok(json(StatusCode::NOT_FOUND, String::new())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_remote_signer_against_stub() {
+        let key = "0x0234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let expected_public_key = SignatureManager::new(key).unwrap().public_key();
+        let stub = SignerStub::start(SignatureManager::new(key).unwrap()).await.unwrap();
+
+        let signer = RemoteSigner::new(&stub.endpoint(), "test-key").unwrap();
+        assert_eq!(StarkSigner::public_key(&signer).await.unwrap(), expected_public_key);
+
+        let hash = crate::signature::message_hash("remote signing");
+        let signature = StarkSigner::sign_hash(&signer, &hash).await.unwrap();
+        assert!(crate::signature::verify_signature(&expected_public_key, &hash, &signature).unwrap());
+    }
+}
@@ -0,0 +1,424 @@
+use crate::model::{CreateOrderRequest, MetaData, OrderSide, TransferRequest, WithdrawRequest};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use starknet_crypto::pedersen_hash;
+use starknet_types_core::felt::Felt;
+use std::str::FromStr;
+use thiserror::Error;
+
+const LIMIT_ORDER_WITH_FEE_TYPE: u64 = 3;
+const TRANSFER_TYPE: u64 = 4;
+const WITHDRAWAL_TO_ADDRESS_TYPE: u64 = 7;
+const MILLIS_PER_HOUR: u64 = 60 * 60 * 1000;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum OrderHashError {
+    #[error("{field} = {value} does not fit in {bits} bits")]
+    FieldOverflow { field: &'static str, value: String, bits: u32 },
+    #[error("{field} is not a valid decimal: {value:?}")]
+    InvalidDecimal { field: &'static str, value: String },
+    #[error("{field} must not be negative: {value}")]
+    NegativeAmount { field: &'static str, value: String },
+    #[error("Invalid asset id: {0:?}")]
+    InvalidAssetId(String),
+    #[error("Unknown contract: {0}")]
+    UnknownContract(u64),
+    #[error("{field} is not a valid field element: {value:?}")]
+    InvalidFelt { field: &'static str, value: String },
+}
+
+/// The StarkEx limit-order-with-fees message, with every field named and typed.
+///
+/// Amounts are in asset quantums (already multiplied by the asset resolution).
+/// `nonce` and `expiration_hours` are packed into 32 bits and checked by `hash()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StarkLimitOrder {
+    pub synthetic_asset_id: Felt,
+    pub collateral_asset_id: Felt,
+    pub fee_asset_id: Felt,
+    pub is_buy: bool,
+    pub amount_synthetic: u64,
+    pub amount_collateral: u64,
+    pub amount_fee: u64,
+    pub nonce: u64,
+    /// StarkEx position id, i.e. the EdgeX account id.
+    pub position_id: u64,
+    /// Expiration as hours since the Unix epoch.
+    pub expiration_hours: u64,
+}
+
+impl StarkLimitOrder {
+    /// Builds the L2 order from the human-readable `l2Size` / `l2Value` / `l2LimitFee`
+    /// fields of a request and the asset ids and resolutions from the metadata.
+    ///
+    /// Collateral is rounded against the trader (up on buys, down on sells) and
+    /// the fee is always rounded up, matching the exchange's own rounding.
+    pub fn from_request(req: &CreateOrderRequest, meta: &MetaData) -> Result<Self, OrderHashError> {
+        let contract = meta.contract(req.contract_id)
+            .ok_or(OrderHashError::UnknownContract(req.contract_id))?;
+        let collateral = meta.collateral();
+        let is_buy = matches!(req.side, OrderSide::Buy);
+
+        let synthetic_resolution = parse_resolution("starkExResolution", &contract.stark_ex_resolution)?;
+        let collateral_resolution = parse_resolution("collateral starkExResolution", &collateral.stark_ex_resolution)?;
+
+        let size = parse_decimal("l2Size", &req.l2_size)?;
+        let value = parse_decimal("l2Value", &req.l2_value)?;
+        let fee = parse_decimal("l2LimitFee", &req.l2_limit_fee)?;
+
+        let collateral_rounding = if is_buy {
+            RoundingStrategy::AwayFromZero
+        } else {
+            RoundingStrategy::ToZero
+        };
+
+        Ok(Self {
+            synthetic_asset_id: parse_asset_id(&contract.stark_ex_synthetic_asset_id)?,
+            collateral_asset_id: parse_asset_id(&collateral.stark_ex_asset_id)?,
+            fee_asset_id: parse_asset_id(&collateral.stark_ex_asset_id)?,
+            is_buy,
+            amount_synthetic: to_quantums("amountSynthetic", size, synthetic_resolution, RoundingStrategy::ToZero)?,
+            amount_collateral: to_quantums("amountCollateral", value, collateral_resolution, collateral_rounding)?,
+            amount_fee: to_quantums("amountFee", fee, collateral_resolution, RoundingStrategy::AwayFromZero)?,
+            nonce: req.l2_nonce,
+            position_id: req.account_id,
+            expiration_hours: req.l2_expire_time.div_ceil(MILLIS_PER_HOUR),
+        })
+    }
+
+    /// Checks that every packed field fits its slot in the hashed message.
+    pub fn validate(&self) -> Result<(), OrderHashError> {
+        check_bits("nonce", self.nonce, 32)?;
+        check_bits("expirationHours", self.expiration_hours, 32)?;
+        Ok(())
+    }
+
+    /// Pedersen hash of the order, as signed into `l2Signature`.
+    /// Replicates the logic from EdgeX Python SDK `calc_limit_order_hash`.
+    pub fn hash(&self) -> Result<Felt, OrderHashError> {
+        self.validate()?;
+
+        let (asset_id_sell, asset_id_buy, amount_sell, amount_buy) = if self.is_buy {
+            (self.collateral_asset_id, self.synthetic_asset_id, self.amount_collateral, self.amount_synthetic)
+        } else {
+            (self.synthetic_asset_id, self.collateral_asset_id, self.amount_synthetic, self.amount_collateral)
+        };
+
+        // hash(hash(asset_id_sell, asset_id_buy), asset_id_fee)
+        let msg = pedersen_hash(&asset_id_sell, &asset_id_buy);
+        let msg = pedersen_hash(&msg, &self.fee_asset_id);
+
+        // packed_message0 = amount_sell | amount_buy (64) | max_amount_fee (64) | nonce (32)
+        let pm0 = Felt::from(amount_sell);
+        let pm0 = shift_add(pm0, amount_buy, 64);
+        let pm0 = shift_add(pm0, self.amount_fee, 64);
+        let pm0 = shift_add(pm0, self.nonce, 32);
+        let msg = pedersen_hash(&msg, &pm0);
+
+        // packed_message1 = type | position_id (64) x3 | expiration (32), then padded by 17 bits
+        let pm1 = Felt::from(LIMIT_ORDER_WITH_FEE_TYPE);
+        let pm1 = shift_add(pm1, self.position_id, 64);
+        let pm1 = shift_add(pm1, self.position_id, 64);
+        let pm1 = shift_add(pm1, self.position_id, 64);
+        let pm1 = shift_add(pm1, self.expiration_hours, 32);
+        let pm1 = pm1 * Felt::from(2u64).pow(17u128);
+
+        Ok(pedersen_hash(&msg, &pm1))
+    }
+}
+
+/// The StarkEx perpetual transfer message. Amounts are in collateral quantums.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StarkTransfer {
+    pub asset_id: Felt,
+    pub fee_asset_id: Felt,
+    pub receiver_public_key: Felt,
+    pub sender_position_id: u64,
+    pub receiver_position_id: u64,
+    pub fee_position_id: u64,
+    pub amount: u64,
+    pub max_amount_fee: u64,
+    pub nonce: u64,
+    pub expiration_hours: u64,
+}
+
+impl StarkTransfer {
+    /// Builds the L2 transfer from a request. Transfers carry no fee, which is
+    /// charged to the sender's own position.
+    pub fn from_request(req: &TransferRequest, meta: &MetaData) -> Result<Self, OrderHashError> {
+        let collateral = meta.collateral();
+        let resolution = parse_resolution("collateral starkExResolution", &collateral.stark_ex_resolution)?;
+        let asset_id = parse_asset_id(&collateral.stark_ex_asset_id)?;
+        let amount = parse_decimal("amount", &req.amount)?;
+        Ok(Self {
+            asset_id,
+            fee_asset_id: asset_id,
+            receiver_public_key: parse_felt("receiverL2Key", &req.receiver_l2_key)?,
+            sender_position_id: req.account_id,
+            receiver_position_id: req.receiver_account_id,
+            fee_position_id: req.account_id,
+            amount: to_quantums("amount", amount, resolution, RoundingStrategy::ToZero)?,
+            max_amount_fee: 0,
+            nonce: req.l2_nonce,
+            expiration_hours: req.l2_expire_time.div_ceil(MILLIS_PER_HOUR),
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), OrderHashError> {
+        check_bits("nonce", self.nonce, 32)?;
+        check_bits("expirationHours", self.expiration_hours, 32)?;
+        Ok(())
+    }
+
+    /// Pedersen hash of the transfer, as signed into `l2Signature`.
+    pub fn hash(&self) -> Result<Felt, OrderHashError> {
+        self.validate()?;
+
+        let msg = pedersen_hash(&self.asset_id, &self.fee_asset_id);
+        let msg = pedersen_hash(&msg, &self.receiver_public_key);
+
+        // packed_message0 = sender_position_id | receiver_position_id (64) | fee_position_id (64) | nonce (32)
+        let pm0 = Felt::from(self.sender_position_id);
+        let pm0 = shift_add(pm0, self.receiver_position_id, 64);
+        let pm0 = shift_add(pm0, self.fee_position_id, 64);
+        let pm0 = shift_add(pm0, self.nonce, 32);
+        let msg = pedersen_hash(&msg, &pm0);
+
+        // packed_message1 = type | amount (64) | max_amount_fee (64) | expiration (32), then padded by 81 bits
+        let pm1 = Felt::from(TRANSFER_TYPE);
+        let pm1 = shift_add(pm1, self.amount, 64);
+        let pm1 = shift_add(pm1, self.max_amount_fee, 64);
+        let pm1 = shift_add(pm1, self.expiration_hours, 32);
+        let pm1 = pm1 * Felt::from(2u64).pow(81u128);
+
+        Ok(pedersen_hash(&msg, &pm1))
+    }
+}
+
+/// The StarkEx perpetual withdrawal-to-address message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StarkWithdrawal {
+    pub collateral_asset_id: Felt,
+    /// L1 recipient, as a field element.
+    pub eth_address: Felt,
+    pub position_id: u64,
+    pub amount: u64,
+    pub nonce: u64,
+    pub expiration_hours: u64,
+}
+
+impl StarkWithdrawal {
+    pub fn from_request(req: &WithdrawRequest, meta: &MetaData) -> Result<Self, OrderHashError> {
+        let collateral = meta.collateral();
+        let resolution = parse_resolution("collateral starkExResolution", &collateral.stark_ex_resolution)?;
+        let amount = parse_decimal("amount", &req.amount)?;
+        Ok(Self {
+            collateral_asset_id: parse_asset_id(&collateral.stark_ex_asset_id)?,
+            eth_address: parse_felt("ethAddress", &req.eth_address)?,
+            position_id: req.account_id,
+            amount: to_quantums("amount", amount, resolution, RoundingStrategy::ToZero)?,
+            nonce: req.l2_nonce,
+            expiration_hours: req.l2_expire_time.div_ceil(MILLIS_PER_HOUR),
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), OrderHashError> {
+        check_bits("nonce", self.nonce, 32)?;
+        check_bits("expirationHours", self.expiration_hours, 32)?;
+        Ok(())
+    }
+
+    /// Pedersen hash of the withdrawal, as signed into `l2Signature`.
+    pub fn hash(&self) -> Result<Felt, OrderHashError> {
+        self.validate()?;
+
+        // packed_message = type | position_id (64) | nonce (32) | amount (64) | expiration (32), then padded by 49 bits
+        let pm = Felt::from(WITHDRAWAL_TO_ADDRESS_TYPE);
+        let pm = shift_add(pm, self.position_id, 64);
+        let pm = shift_add(pm, self.nonce, 32);
+        let pm = shift_add(pm, self.amount, 64);
+        let pm = shift_add(pm, self.expiration_hours, 32);
+        let pm = pm * Felt::from(2u64).pow(49u128);
+
+        let msg = pedersen_hash(&self.collateral_asset_id, &self.eth_address);
+        Ok(pedersen_hash(&msg, &pm))
+    }
+}
+
+// acc * 2^shift + val; modulo the field prime is handled by Felt arithmetic.
+fn shift_add(acc: Felt, val: u64, shift: u32) -> Felt {
+    (acc * Felt::from(2u64).pow(shift as u128)) + Felt::from(val)
+}
+
+fn check_bits(field: &'static str, value: u64, bits: u32) -> Result<(), OrderHashError> {
+    if bits < 64 && value >> bits != 0 {
+        return Err(OrderHashError::FieldOverflow { field, value: value.to_string(), bits });
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_asset_id(asset_id: &str) -> Result<Felt, OrderHashError> {
+    Felt::from_hex(asset_id.trim_start_matches("0x"))
+        .map_err(|_| OrderHashError::InvalidAssetId(asset_id.to_string()))
+}
+
+fn parse_felt(field: &'static str, value: &str) -> Result<Felt, OrderHashError> {
+    Felt::from_hex(value.trim().trim_start_matches("0x"))
+        .map_err(|_| OrderHashError::InvalidFelt { field, value: value.to_string() })
+}
+
+fn parse_decimal(field: &'static str, value: &str) -> Result<Decimal, OrderHashError> {
+    Decimal::from_str(value.trim())
+        .map_err(|_| OrderHashError::InvalidDecimal { field, value: value.to_string() })
+}
+
+/// Resolutions come back either as `0x`-prefixed hex or as plain decimal strings.
+fn parse_resolution(field: &'static str, value: &str) -> Result<Decimal, OrderHashError> {
+    match value.strip_prefix("0x") {
+        Some(hex_str) => u64::from_str_radix(hex_str, 16)
+            .map(Decimal::from)
+            .map_err(|_| OrderHashError::InvalidDecimal { field, value: value.to_string() }),
+        None => parse_decimal(field, value),
+    }
+}
+
+/// Decimal places of the collateral resolution (6 for `0xf4240`).
+pub(crate) fn collateral_decimals(meta: &MetaData) -> Result<u32, OrderHashError> {
+    let resolution = parse_resolution("collateral starkExResolution", &meta.collateral().stark_ex_resolution)?;
+    Ok(resolution.to_u64().unwrap_or(1).max(1).ilog10())
+}
+
+fn to_quantums(field: &'static str, amount: Decimal, resolution: Decimal, rounding: RoundingStrategy) -> Result<u64, OrderHashError> {
+    if amount.is_sign_negative() && !amount.is_zero() {
+        return Err(OrderHashError::NegativeAmount { field, value: amount.to_string() });
+    }
+    let quantums = amount.checked_mul(resolution)
+        .ok_or(OrderHashError::FieldOverflow { field, value: format!("{} * {}", amount, resolution), bits: 64 })?;
+    let rounded = quantums.round_dp_with_strategy(0, rounding);
+    rounded.to_u64()
+        .ok_or(OrderHashError::FieldOverflow { field, value: rounded.to_string(), bits: 64 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{CollateralMeta, ContractMeta, GlobalMeta, OrderType, TimeInForce};
+
+    fn meta() -> MetaData {
+        MetaData {
+            global: GlobalMeta {
+                stark_ex_collateral_coin: CollateralMeta {
+                    coin_id: 1000,
+                    coin_name: "USDT".to_string(),
+                    stark_ex_asset_id: "0x2".to_string(),
+                    stark_ex_resolution: "0xf4240".to_string(),
+                },
+            },
+            contract_list: vec![ContractMeta {
+                contract_id: 10000001,
+                contract_name: "BTCUSDT".to_string(),
+                tick_size: "0.1".to_string(),
+                step_size: "0.001".to_string(),
+                stark_ex_synthetic_asset_id: "0x1".to_string(),
+                stark_ex_resolution: "0x2540be400".to_string(),
+                default_maker_fee_rate: "0.0002".to_string(),
+                default_taker_fee_rate: "0.0005".to_string(),
+                risk_tier_list: Vec::new(),
+            }],
+        }
+    }
+
+    fn request() -> CreateOrderRequest {
+        CreateOrderRequest {
+            price: "50000".to_string(),
+            size: "0.01".to_string(),
+            r#type: OrderType::Limit,
+            time_in_force: TimeInForce::Gtc,
+            account_id: 1,
+            contract_id: 10000001,
+            side: OrderSide::Buy,
+            client_order_id: None,
+            l2_nonce: 123,
+            l2_value: "500.0000001".to_string(),
+            l2_size: "0.01".to_string(),
+            l2_limit_fee: "0.25".to_string(),
+            l2_expire_time: 3_600_000 * 500_000 + 1,
+            l2_signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_limit_order_from_request() {
+        let order = StarkLimitOrder::from_request(&request(), &meta()).unwrap();
+        assert_eq!(order.amount_synthetic, 100_000_000);
+        // Buys round collateral up.
+        assert_eq!(order.amount_collateral, 500_000_001);
+        assert_eq!(order.amount_fee, 250_000);
+        assert_eq!(order.expiration_hours, 500_001);
+        order.hash().unwrap();
+
+        let mut sell = request();
+        sell.side = OrderSide::Sell;
+        assert_eq!(StarkLimitOrder::from_request(&sell, &meta()).unwrap().amount_collateral, 500_000_000);
+    }
+
+    #[test]
+    fn test_limit_order_overflow() {
+        let mut req = request();
+        req.l2_size = "0.0.1".to_string();
+        assert!(matches!(
+            StarkLimitOrder::from_request(&req, &meta()),
+            Err(OrderHashError::InvalidDecimal { .. })
+        ));
+
+        req.l2_size = "10000000000".to_string();
+        assert!(matches!(
+            StarkLimitOrder::from_request(&req, &meta()),
+            Err(OrderHashError::FieldOverflow { field: "amountSynthetic", bits: 64, .. })
+        ));
+
+        let mut order = StarkLimitOrder::from_request(&request(), &meta()).unwrap();
+        order.nonce = 1 << 32;
+        assert!(matches!(order.hash(), Err(OrderHashError::FieldOverflow { field: "nonce", bits: 32, .. })));
+    }
+
+    #[test]
+    fn test_transfer_and_withdrawal_hash() {
+        let mut transfer = TransferRequest {
+            account_id: 1,
+            coin_id: 1000,
+            amount: "12.3456789".to_string(),
+            receiver_account_id: 2,
+            receiver_l2_key: "0x3ab".to_string(),
+            client_transfer_id: None,
+            l2_nonce: 7,
+            l2_expire_time: 3_600_000 * 500_000,
+            l2_signature: String::new(),
+        };
+        let stark = StarkTransfer::from_request(&transfer, &meta()).unwrap();
+        assert_eq!(stark.amount, 12_345_678);
+        assert_eq!(stark.fee_position_id, 1);
+        let hash = stark.hash().unwrap();
+        transfer.receiver_account_id = 3;
+        assert_ne!(StarkTransfer::from_request(&transfer, &meta()).unwrap().hash().unwrap(), hash);
+        transfer.receiver_l2_key = "not hex".to_string();
+        assert!(matches!(
+            StarkTransfer::from_request(&transfer, &meta()),
+            Err(OrderHashError::InvalidFelt { field: "receiverL2Key", .. })
+        ));
+
+        let withdraw = WithdrawRequest {
+            account_id: 1,
+            coin_id: 1000,
+            amount: "5".to_string(),
+            eth_address: "0x00000000000000000000000000000000000000aa".to_string(),
+            client_withdraw_id: None,
+            l2_nonce: 7,
+            l2_expire_time: 3_600_000 * 500_000,
+            l2_signature: String::new(),
+        };
+        let stark = StarkWithdrawal::from_request(&withdraw, &meta()).unwrap();
+        assert_eq!((stark.amount, stark.eth_address), (5_000_000, Felt::from(0xaau64)));
+        stark.hash().unwrap();
+    }
+}
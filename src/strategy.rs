@@ -0,0 +1,612 @@
+use crate::client::ClientError;
+use crate::model::{CancelOrderRequest, CreateOrderRequest, Fill, MetaData, OpenOrder, OrderStatus, PublicTrade};
+use crate::order_book::OrderBook;
+use crate::order_manager::{tagged_client_order_id, validate_strategy_tag, InvalidStrategyTag};
+use crate::trading::TradingApi;
+use crate::websocket::{parse_depth_updates, parse_public_trades, AccountEvent, EdgeXWebSocket, WsMessage};
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use futures_util::{FutureExt, StreamExt};
+use serde_json::Value;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+const EVENT_CHANNEL_CAPACITY: usize = 4096;
+
+#[derive(Error, Debug)]
+pub enum StrategyError {
+    #[error("Strategy panicked in {hook}: {message}")]
+    Panicked { hook: &'static str, message: String },
+    #[error("Client error: {0}")]
+    Client(#[from] ClientError),
+    #[error("{0}")]
+    InvalidTag(#[from] InvalidStrategyTag),
+}
+
+/// Trading logic driven by a `StrategyEngine`. Every hook has a no-op default.
+///
+/// Hooks run one at a time on the engine's task, so a slow hook delays the
+/// events behind it. Unless `cancel_on_stop` is off, the account's open
+/// orders are cancelled when the engine stops.
+#[async_trait]
+pub trait Strategy: Send {
+    async fn on_start(&mut self, _ctx: &mut StrategyContext) {}
+
+    /// The book of a subscribed contract changed.
+    async fn on_book(&mut self, _ctx: &mut StrategyContext, _book: &OrderBook) {}
+
+    async fn on_trade(&mut self, _ctx: &mut StrategyContext, _trade: &PublicTrade) {}
+
+    /// One of the account's orders changed; `ctx.open_orders()` already reflects it.
+    async fn on_order_update(&mut self, _ctx: &mut StrategyContext, _order: &OpenOrder) {}
+
+    async fn on_fill(&mut self, _ctx: &mut StrategyContext, _fill: &Fill) {}
+
+    /// Called every `StrategyConfig::timer_interval`.
+    async fn on_timer(&mut self, _ctx: &mut StrategyContext) {}
+
+    /// Called once on shutdown, before open orders are cancelled.
+    async fn on_stop(&mut self, _ctx: &mut StrategyContext) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct StrategyConfig {
+    /// Public websocket to subscribe on; the quote host when `None`.
+    pub ws_url: Option<String>,
+    /// Levels requested on `depth.{contractId}.{level}`.
+    pub depth_level: u32,
+    pub timer_interval: Duration,
+    /// Wait before reconnecting a dropped websocket.
+    pub reconnect_delay: Duration,
+    /// Cancel the account's open orders on shutdown.
+    pub cancel_on_stop: bool,
+    /// Strategy tag put in the client order ids of orders placed through the
+    /// context, for attributing fills when strategies share an account.
+    /// Checked with `order_manager::validate_strategy_tag` when the engine starts.
+    pub tag: Option<String>,
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        Self {
+            ws_url: None,
+            depth_level: 15,
+            timer_interval: Duration::from_secs(1),
+            reconnect_delay: Duration::from_secs(1),
+            cancel_on_stop: true,
+            tag: None,
+        }
+    }
+}
+
+/// What a strategy sees and acts through.
+pub struct StrategyContext {
+    api: Arc<dyn TradingApi>,
+    meta: MetaData,
+    account_id: u64,
+    books: HashMap<u64, OrderBook>,
+    open_orders: BTreeMap<u64, OpenOrder>,
+    tag: Option<String>,
+    stop_requested: bool,
+}
+
+impl StrategyContext {
+    pub fn account_id(&self) -> u64 {
+        self.account_id
+    }
+
+    pub fn metadata(&self) -> &MetaData {
+        &self.meta
+    }
+
+    pub fn api(&self) -> &Arc<dyn TradingApi> {
+        &self.api
+    }
+
+    pub fn book(&self, contract_id: u64) -> Option<&OrderBook> {
+        self.books.get(&contract_id)
+    }
+
+    /// The account's open orders as last reported by the account stream, and
+    /// refetched whenever the stream reconnects.
+    pub fn open_orders(&self) -> impl Iterator<Item = &OpenOrder> {
+        self.open_orders.values()
+    }
+
+    /// Places `req`, giving it a client order id tagged with `StrategyConfig::tag` if it has none.
+    pub async fn place_order(&self, mut req: CreateOrderRequest) -> Result<Value, ClientError> {
+        if let Some(tag) = &self.tag
+            && req.client_order_id.is_none()
+        {
+            req.client_order_id = Some(tagged_client_order_id(tag));
+        }
+        self.api.place_order(req, &self.meta).await
+    }
+
+    pub async fn cancel_order(&self, contract_id: u64, order_id: u64) -> Result<Value, ClientError> {
+        self.api.cancel_order(&CancelOrderRequest::by_order_id(self.account_id, contract_id, order_id)).await
+    }
+
+    /// Asks the engine to shut down once the current hook returns.
+    pub fn stop(&mut self) {
+        self.stop_requested = true;
+    }
+
+    /// Replaces the open orders with a fresh `get_open_orders`.
+    fn resync(&mut self, open_orders: &[OpenOrder]) {
+        self.open_orders.clear();
+        for order in open_orders {
+            self.apply_order(order);
+        }
+    }
+
+    fn apply_order(&mut self, order: &OpenOrder) {
+        let terminal = OrderStatus::from_exchange(&order.status).is_some_and(OrderStatus::is_terminal);
+        if terminal {
+            self.open_orders.remove(&order.order_id);
+        } else {
+            self.open_orders.insert(order.order_id, order.clone());
+        }
+    }
+}
+
+/// Stops a running engine from another task, e.g. a Ctrl-C handler.
+#[derive(Clone)]
+pub struct ShutdownHandle(watch::Sender<bool>);
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        let _ = self.0.send(true);
+    }
+}
+
+enum EngineEvent {
+    Market(WsMessage),
+    Account(AccountEvent),
+    /// The account's open orders, fetched after the account stream reconnected.
+    Resync(Vec<OpenOrder>),
+    /// A caller-supplied market stream ran out.
+    MarketEnded,
+}
+
+/// Runs a `Strategy` against any `TradingApi` (the live client or a
+/// `PaperExchange`), wiring up market data, the account stream and a timer.
+///
+/// Live market data is read from the public websocket, subscribed to `depth`
+/// and `trades` for each contract given to `with_contracts`, and reconnected
+/// when it drops. A panic in a hook stops the engine: open orders are still
+/// cancelled, and `run` returns `StrategyError::Panicked` instead of unwinding
+/// into the caller.
+pub struct StrategyEngine {
+    api: Arc<dyn TradingApi>,
+    meta: MetaData,
+    account_id: u64,
+    config: StrategyConfig,
+    contracts: Vec<u64>,
+    market_data: Option<BoxStream<'static, WsMessage>>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl StrategyEngine {
+    pub fn new(api: Arc<dyn TradingApi>, meta: MetaData, account_id: u64) -> Self {
+        let (shutdown, _) = watch::channel(false);
+        Self {
+            api,
+            meta,
+            account_id,
+            config: StrategyConfig::default(),
+            contracts: Vec::new(),
+            market_data: None,
+            shutdown,
+        }
+    }
+
+    pub fn with_config(mut self, config: StrategyConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Contracts whose book and trades the strategy receives.
+    pub fn with_contracts(mut self, contracts: impl IntoIterator<Item = u64>) -> Self {
+        self.contracts = contracts.into_iter().collect();
+        self
+    }
+
+    /// Uses `messages` (e.g. a `Replayer` stream) instead of the live
+    /// websocket. The engine stops when it ends.
+    pub fn with_market_data(mut self, messages: BoxStream<'static, WsMessage>) -> Self {
+        self.market_data = Some(messages);
+        self
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle(self.shutdown.clone())
+    }
+
+    /// Drives `strategy` until it calls `ctx.stop()`, the shutdown handle
+    /// fires, a supplied market stream ends, or a hook panics.
+    pub async fn run<S: Strategy>(mut self, strategy: &mut S) -> Result<(), StrategyError> {
+        if let Some(tag) = &self.config.tag {
+            validate_strategy_tag(tag)?;
+        }
+        let mut ctx = StrategyContext {
+            api: self.api.clone(),
+            meta: self.meta.clone(),
+            account_id: self.account_id,
+            books: self.contracts.iter().map(|&id| (id, OrderBook::new(id))).collect(),
+            open_orders: BTreeMap::new(),
+            tag: self.config.tag.clone(),
+            stop_requested: false,
+        };
+        ctx.resync(&self.api.get_open_orders(self.account_id).await?);
+
+        // Subscribe before `on_start` so updates to the first orders aren't missed.
+        let (tx, mut rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let account_events = self.api.account_events(self.account_id).await?;
+        let tasks = [self.spawn_account_feed(account_events, tx.clone()), self.spawn_market_feed(tx)];
+
+        let result = self.drive(strategy, &mut ctx, &mut rx).await;
+        for task in &tasks {
+            task.abort();
+        }
+        if result.is_ok() {
+            // A panicking strategy may be in any state, so on_stop is skipped.
+            guarded("on_stop", strategy.on_stop(&mut ctx)).await?;
+        }
+        let cancelled = self.cancel_open_orders(&ctx).await;
+        result.and(cancelled)
+    }
+
+    async fn drive<S: Strategy>(
+        &self,
+        strategy: &mut S,
+        ctx: &mut StrategyContext,
+        rx: &mut mpsc::Receiver<EngineEvent>,
+    ) -> Result<(), StrategyError> {
+        let mut shutdown = self.shutdown.subscribe();
+        let mut timer = tokio::time::interval(self.config.timer_interval);
+        timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        timer.reset();
+
+        guarded("on_start", strategy.on_start(ctx)).await?;
+        while !ctx.stop_requested && !*shutdown.borrow() {
+            tokio::select! {
+                _ = shutdown.changed() => {}
+                _ = timer.tick() => guarded("on_timer", strategy.on_timer(ctx)).await?,
+                event = rx.recv() => match event {
+                    Some(EngineEvent::Market(msg)) => on_market_message(strategy, ctx, &msg).await?,
+                    Some(EngineEvent::Account(AccountEvent::Order(order))) => {
+                        ctx.apply_order(&order);
+                        guarded("on_order_update", strategy.on_order_update(ctx, &order)).await?;
+                    }
+                    Some(EngineEvent::Account(AccountEvent::Fill(fill))) => {
+                        guarded("on_fill", strategy.on_fill(ctx, &fill)).await?;
+                    }
+                    Some(EngineEvent::Resync(open_orders)) => ctx.resync(&open_orders),
+                    Some(EngineEvent::MarketEnded) | None => break,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn spawn_account_feed(&self, mut events: BoxStream<'static, AccountEvent>, tx: mpsc::Sender<EngineEvent>) -> JoinHandle<()> {
+        let (api, account_id, delay) = (self.api.clone(), self.account_id, self.config.reconnect_delay);
+        tokio::spawn(async move {
+            loop {
+                while let Some(event) = events.next().await {
+                    if tx.send(EngineEvent::Account(event)).await.is_err() {
+                        return;
+                    }
+                }
+                // The stream dropped; keep trying until it comes back.
+                loop {
+                    tokio::time::sleep(delay).await;
+                    if let Ok(stream) = api.account_events(account_id).await {
+                        events = stream;
+                        break;
+                    }
+                }
+                // Updates sent while it was down are gone, so refetch. The new
+                // stream is subscribed first and buffers anything that follows.
+                let open_orders = loop {
+                    match api.get_open_orders(account_id).await {
+                        Ok(open_orders) => break open_orders,
+                        Err(_) => tokio::time::sleep(delay).await,
+                    }
+                };
+                if tx.send(EngineEvent::Resync(open_orders)).await.is_err() {
+                    return;
+                }
+            }
+        })
+    }
+
+    fn spawn_market_feed(&mut self, tx: mpsc::Sender<EngineEvent>) -> JoinHandle<()> {
+        if let Some(mut messages) = self.market_data.take() {
+            return tokio::spawn(async move {
+                while let Some(msg) = messages.next().await {
+                    if tx.send(EngineEvent::Market(msg)).await.is_err() {
+                        return;
+                    }
+                }
+                let _ = tx.send(EngineEvent::MarketEnded).await;
+            });
+        }
+        let channels: Vec<String> = self.contracts.iter()
+            .flat_map(|id| [format!("depth.{}.{}", id, self.config.depth_level), format!("trades.{}", id)])
+            .collect();
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            if channels.is_empty() {
+                return;
+            }
+            loop {
+                if let Ok(stream) = connect_public(&config, &channels).await {
+                    let mut messages = std::pin::pin!(EdgeXWebSocket::messages(stream));
+                    // A new connection starts with fresh depth snapshots.
+                    while let Some(Ok(msg)) = messages.next().await {
+                        if tx.send(EngineEvent::Market(msg)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                tokio::time::sleep(config.reconnect_delay).await;
+            }
+        })
+    }
+
+    /// Cancels whatever the exchange still reports open, falling back to the
+    /// orders the context knows about if that query fails.
+    async fn cancel_open_orders(&self, ctx: &StrategyContext) -> Result<(), StrategyError> {
+        if !self.config.cancel_on_stop {
+            return Ok(());
+        }
+        let open: Vec<(u64, u64)> = match self.api.get_open_orders(self.account_id).await {
+            Ok(orders) => orders.iter().map(|o| (o.contract_id, o.order_id)).collect(),
+            Err(_) => ctx.open_orders().map(|o| (o.contract_id, o.order_id)).collect(),
+        };
+        let mut first_error = None;
+        for (contract_id, order_id) in open {
+            if let Err(e) = self.api.cancel_order(&CancelOrderRequest::by_order_id(self.account_id, contract_id, order_id)).await {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), |e| Err(e.into()))
+    }
+}
+
+async fn on_market_message<S: Strategy>(strategy: &mut S, ctx: &mut StrategyContext, msg: &WsMessage) -> Result<(), StrategyError> {
+    for update in parse_depth_updates(msg) {
+        // Only subscribed contracts have a book.
+        let Some(book) = ctx.books.get_mut(&update.contract_id) else {
+            continue;
+        };
+        if book.apply(&update).is_ok() {
+            let book = book.clone();
+            guarded("on_book", strategy.on_book(ctx, &book)).await?;
+        }
+    }
+    for trade in parse_public_trades(msg) {
+        guarded("on_trade", strategy.on_trade(ctx, &trade)).await?;
+    }
+    Ok(())
+}
+
+async fn connect_public(config: &StrategyConfig, channels: &[String]) -> Result<crate::websocket::WsStream, ClientError> {
+    let mut stream = match &config.ws_url {
+        Some(url) => EdgeXWebSocket::connect_url(url).await?,
+        None => EdgeXWebSocket::connect().await?,
+    };
+    for channel in channels {
+        EdgeXWebSocket::subscribe(&mut stream, channel).await?;
+    }
+    Ok(stream)
+}
+
+/// Runs a hook, turning a panic into an error.
+async fn guarded(hook: &'static str, fut: impl Future<Output = ()>) -> Result<(), StrategyError> {
+    AssertUnwindSafe(fut).catch_unwind().await
+        .map_err(|payload| StrategyError::Panicked { hook, message: panic_message(payload.as_ref()) })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::default_metadata;
+    use crate::model::{OrderSide, Position};
+    use crate::order_manager::strategy_tag;
+    use crate::paper::PaperExchange;
+    use futures_util::stream;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const CONTRACT: u64 = 10000001;
+
+    fn order(side: OrderSide, price: &str) -> CreateOrderRequest {
+        CreateOrderRequest::unsigned(1, CONTRACT, side, price, "1")
+    }
+
+    fn messages() -> Vec<WsMessage> {
+        [
+            json!({
+                "type": "quote-event",
+                "channel": "depth.10000001.15",
+                "content": { "data": [{
+                    "contractId": "10000001",
+                    "depthType": "SNAPSHOT",
+                    "bids": [{ "price": "49990", "size": "1" }],
+                    "asks": [{ "price": "50010", "size": "1" }],
+                }] },
+            }),
+            json!({
+                "type": "quote-event",
+                "channel": "trades.10000001",
+                "content": { "data": [{
+                    "ticketId": "1", "contractId": "10000001", "price": "49000",
+                    "size": "5", "isBuyerMaker": true, "time": "1000",
+                }] },
+            }),
+        ]
+        .into_iter()
+        .map(WsMessage::from_value)
+        .collect()
+    }
+
+    /// Bids at 49500 and asks far away on start; stops after the bid fills.
+    #[derive(Default)]
+    struct Quoter {
+        books: usize,
+        fills: Vec<Fill>,
+        panic_on_book: bool,
+        stopped: bool,
+    }
+
+    #[async_trait]
+    impl Strategy for Quoter {
+        async fn on_start(&mut self, ctx: &mut StrategyContext) {
+            ctx.place_order(order(OrderSide::Buy, "49500")).await.unwrap();
+            ctx.place_order(order(OrderSide::Sell, "60000")).await.unwrap();
+        }
+
+        async fn on_book(&mut self, _ctx: &mut StrategyContext, book: &OrderBook) {
+            if self.panic_on_book {
+                panic!("bad book");
+            }
+            assert!(book.best_bid().is_some());
+            self.books += 1;
+        }
+
+        async fn on_fill(&mut self, ctx: &mut StrategyContext, fill: &Fill) {
+            self.fills.push(fill.clone());
+            ctx.stop();
+        }
+
+        async fn on_stop(&mut self, ctx: &mut StrategyContext) {
+            // The resting ask is still open at this point, carrying the engine's tag.
+            assert_eq!(ctx.open_orders().count(), 1);
+            assert!(ctx.open_orders().all(|o| o.client_order_id.as_deref().and_then(strategy_tag) == Some("mm")));
+            self.stopped = true;
+        }
+    }
+
+    fn engine(paper: &Arc<PaperExchange>) -> StrategyEngine {
+        let feed = paper.clone();
+        // Feed the paper exchange first so fills follow from the same data;
+        // never ending, so only the strategy stops the engine.
+        let market = stream::iter(messages())
+            .then(|msg| async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                msg
+            })
+            .inspect(move |msg| feed.apply_message(msg))
+            .chain(stream::pending());
+        StrategyEngine::new(paper.clone(), default_metadata(), 1)
+            .with_config(StrategyConfig { tag: Some("mm".to_string()), ..Default::default() })
+            .with_contracts([CONTRACT])
+            .with_market_data(market.boxed())
+    }
+
+    /// Its first account stream drops everything and ends after 100ms.
+    struct DroppingFeed {
+        paper: Arc<PaperExchange>,
+        dropped: AtomicBool,
+    }
+
+    #[async_trait]
+    impl TradingApi for DroppingFeed {
+        async fn place_order(&self, req: CreateOrderRequest, meta: &MetaData) -> Result<Value, ClientError> {
+            self.paper.place_order(req, meta).await
+        }
+
+        async fn cancel_order(&self, req: &CancelOrderRequest) -> Result<Value, ClientError> {
+            self.paper.cancel_order(req).await
+        }
+
+        async fn get_open_orders(&self, account_id: u64) -> Result<Vec<OpenOrder>, ClientError> {
+            self.paper.get_open_orders(account_id).await
+        }
+
+        async fn get_fills(&self, account_id: u64) -> Result<Vec<Fill>, ClientError> {
+            self.paper.get_fills(account_id).await
+        }
+
+        async fn get_positions(&self, account_id: u64) -> Result<Vec<Position>, ClientError> {
+            self.paper.get_positions(account_id).await
+        }
+
+        async fn account_events(&self, account_id: u64) -> Result<BoxStream<'static, AccountEvent>, ClientError> {
+            if self.dropped.swap(true, Ordering::SeqCst) {
+                return self.paper.account_events(account_id).await;
+            }
+            let outage = stream::once(tokio::time::sleep(Duration::from_millis(100)));
+            Ok(outage.filter_map(|_| async { None }).boxed())
+        }
+    }
+
+    /// Places two orders, then stops once the context shows both.
+    struct Resting;
+
+    #[async_trait]
+    impl Strategy for Resting {
+        async fn on_start(&mut self, ctx: &mut StrategyContext) {
+            ctx.place_order(order(OrderSide::Buy, "49500")).await.unwrap();
+            ctx.place_order(order(OrderSide::Sell, "60000")).await.unwrap();
+        }
+
+        async fn on_timer(&mut self, ctx: &mut StrategyContext) {
+            if ctx.open_orders().count() == 2 {
+                ctx.stop();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_orders_resync_after_account_stream_reconnects() {
+        let paper = Arc::new(PaperExchange::new(1, default_metadata()));
+        let api = Arc::new(DroppingFeed { paper: paper.clone(), dropped: AtomicBool::new(false) });
+        let config = StrategyConfig {
+            timer_interval: Duration::from_millis(20),
+            reconnect_delay: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let engine = StrategyEngine::new(api, default_metadata(), 1)
+            .with_config(config)
+            .with_market_data(stream::pending().boxed());
+        tokio::time::timeout(Duration::from_secs(5), engine.run(&mut Resting)).await.unwrap().unwrap();
+        assert!(paper.get_open_orders(1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_engine_runs_strategy_and_cancels_on_stop() {
+        let paper = Arc::new(PaperExchange::new(1, default_metadata()));
+        let mut strategy = Quoter::default();
+        tokio::time::timeout(Duration::from_secs(5), engine(&paper).run(&mut strategy)).await.unwrap().unwrap();
+
+        assert_eq!(strategy.books, 1);
+        assert_eq!(strategy.fills.len(), 1);
+        assert!(strategy.stopped);
+        assert!(paper.get_open_orders(1).await.unwrap().is_empty());
+
+        // A panicking hook ends the run with an error, still cancelling orders.
+        let paper = Arc::new(PaperExchange::new(1, default_metadata()));
+        let mut strategy = Quoter { panic_on_book: true, ..Default::default() };
+        let result = tokio::time::timeout(Duration::from_secs(5), engine(&paper).run(&mut strategy)).await.unwrap();
+        assert!(matches!(result, Err(StrategyError::Panicked { hook: "on_book", .. })));
+        assert!(!strategy.stopped);
+        assert!(paper.get_open_orders(1).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::client::{parse_data, read_json, ClientError};
+use reqwest::Client;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+const SERVER_TIME_PATH: &str = "/api/v1/public/meta/getServerTime";
+
+#[derive(Debug, Clone)]
+pub struct TimeSyncConfig {
+    /// How often the background task re-samples server time.
+    pub refresh_interval: Duration,
+    /// Samples per sync; the one with the lowest round trip wins.
+    pub samples: usize,
+    /// Offsets larger than this are flagged as skewed.
+    pub max_skew: Duration,
+    /// Added to server time to get the default `l2ExpireTime`.
+    pub default_expire_window: Duration,
+}
+
+impl Default for TimeSyncConfig {
+    fn default() -> Self {
+        Self {
+            refresh_interval: Duration::from_secs(60),
+            samples: 5,
+            max_skew: Duration::from_secs(1),
+            default_expire_window: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// One request/response exchange with the server clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSample {
+    /// `server - local`, in milliseconds, assuming symmetric network latency.
+    pub offset_ms: i64,
+    pub rtt_ms: u64,
+}
+
+impl TimeSample {
+    /// `sent_ms` / `received_ms` are local clock readings around the request.
+    pub fn new(sent_ms: u64, server_ms: u64, received_ms: u64) -> Self {
+        let rtt_ms = received_ms.saturating_sub(sent_ms);
+        let midpoint = sent_ms + rtt_ms / 2;
+        Self {
+            offset_ms: server_ms as i64 - midpoint as i64,
+            rtt_ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeSyncStatus {
+    pub offset_ms: i64,
+    pub rtt_ms: u64,
+    /// Local time of the last successful sync; `None` until the first one.
+    pub last_sync_ms: Option<u64>,
+    /// `|offset_ms|` exceeds `TimeSyncConfig::max_skew`.
+    pub skewed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerTime {
+    #[serde(deserialize_with = "crate::model::de_u64_from_str_or_num")]
+    time_millis: u64,
+}
+
+/// Tracks the offset between the local clock and the exchange clock.
+///
+/// Before the first sync the offset is zero, so `now_millis` is just the local clock.
+pub struct TimeSync {
+    http: Client,
+    base_url: String,
+    config: TimeSyncConfig,
+    status: watch::Sender<TimeSyncStatus>,
+}
+
+impl TimeSync {
+    pub fn new(http: Client, base_url: &str, config: TimeSyncConfig) -> Self {
+        let (status, _) = watch::channel(TimeSyncStatus::default());
+        Self {
+            http,
+            base_url: base_url.to_string(),
+            config,
+            status,
+        }
+    }
+
+    pub fn config(&self) -> &TimeSyncConfig {
+        &self.config
+    }
+
+    pub fn status(&self) -> TimeSyncStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Receives every status update; check `skewed` to be told about clock drift.
+    pub fn subscribe(&self) -> watch::Receiver<TimeSyncStatus> {
+        self.status.subscribe()
+    }
+
+    /// Current time on the exchange clock, in milliseconds.
+    pub fn now_millis(&self) -> u64 {
+        let offset = self.status.borrow().offset_ms;
+        (local_millis() as i64 + offset).max(0) as u64
+    }
+
+    /// Default `l2ExpireTime`: server time plus the configured window.
+    pub fn default_l2_expire_time(&self) -> u64 {
+        self.now_millis() + self.config.default_expire_window.as_millis() as u64
+    }
+
+    pub async fn sample(&self) -> Result<TimeSample, ClientError> {
+        let url = format!("{}{}", self.base_url, SERVER_TIME_PATH);
+        let sent_ms = local_millis();
+        let res = self.http.get(&url).send().await?;
+        let received_ms = local_millis();
+        let server: ServerTime = parse_data(read_json(res).await?)?;
+        Ok(TimeSample::new(sent_ms, server.time_millis, received_ms))
+    }
+
+    /// Takes `config.samples` samples and adopts the lowest-latency one.
+    pub async fn sync(&self) -> Result<TimeSyncStatus, ClientError> {
+        let mut best: Option<TimeSample> = None;
+        let mut last_err = None;
+        for _ in 0..self.config.samples.max(1) {
+            match self.sample().await {
+                Ok(sample) if best.is_none_or(|b| sample.rtt_ms < b.rtt_ms) => best = Some(sample),
+                Ok(_) => {}
+                Err(e) => last_err = Some(e),
+            }
+        }
+        let best = match (best, last_err) {
+            (Some(best), _) => best,
+            (None, Some(e)) => return Err(e),
+            (None, None) => unreachable!(),
+        };
+
+        let status = TimeSyncStatus {
+            offset_ms: best.offset_ms,
+            rtt_ms: best.rtt_ms,
+            last_sync_ms: Some(local_millis()),
+            skewed: best.offset_ms.unsigned_abs() > self.config.max_skew.as_millis() as u64,
+        };
+        self.status.send_replace(status.clone());
+        Ok(status)
+    }
+
+    /// Re-syncs every `refresh_interval` until the handle is aborted.
+    /// Failed syncs keep the previous offset.
+    pub fn spawn_refresh(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.refresh_interval);
+            loop {
+                interval.tick().await;
+                let _ = self.sync().await;
+            }
+        })
+    }
+}
+
+pub(crate) fn local_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_sample_offset() {
+        // Local clock 500ms behind, 100ms round trip.
+        let sample = TimeSample::new(1_000, 1_550, 1_100);
+        assert_eq!(sample, TimeSample { offset_ms: 500, rtt_ms: 100 });
+
+        // Local clock ahead.
+        let sample = TimeSample::new(10_000, 9_000, 10_020);
+        assert_eq!(sample.offset_ms, -1_010);
+
+        let sync = TimeSync::new(Client::new(), "http://localhost", TimeSyncConfig::default());
+        sync.status.send_replace(TimeSyncStatus { offset_ms: 60_000, ..Default::default() });
+        let drift = sync.now_millis() as i64 - local_millis() as i64;
+        assert!((59_000..=61_000).contains(&drift));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::client::{ClientError, EdgeXClient};
+use crate::model::{CancelOrderRequest, CreateOrderRequest, Fill, MetaData, OpenOrder, Position};
+use crate::websocket::{parse_account_events, AccountEvent, EdgeXWebSocket};
+use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use serde_json::Value;
+
+/// The order-entry surface shared by the live client and simulated backends
+/// such as `PaperExchange`. Write strategies against `Arc<dyn TradingApi>` to
+/// switch between them without code changes.
+#[async_trait]
+pub trait TradingApi: Send + Sync {
+    /// Signs (where applicable) and submits an order; see `EdgeXClient::place_order`.
+    async fn place_order(&self, req: CreateOrderRequest, meta: &MetaData) -> Result<Value, ClientError>;
+
+    async fn cancel_order(&self, req: &CancelOrderRequest) -> Result<Value, ClientError>;
+
+    async fn get_open_orders(&self, account_id: u64) -> Result<Vec<OpenOrder>, ClientError>;
+
+    async fn get_fills(&self, account_id: u64) -> Result<Vec<Fill>, ClientError>;
+
+    async fn get_positions(&self, account_id: u64) -> Result<Vec<Position>, ClientError>;
+
+    /// Order and fill updates for the account, as pushed by the private stream.
+    async fn account_events(&self, account_id: u64) -> Result<BoxStream<'static, AccountEvent>, ClientError>;
+}
+
+/// Requests that reach the exchange but are refused come back as `Ok` with a
+/// non-success `code`; this turns those into errors.
+pub fn check_response(response: &Value) -> Result<(), ClientError> {
+    match response["code"].as_str() {
+        None | Some("SUCCESS") => Ok(()),
+        Some(code) => Err(ClientError::ApiError(format!("{}: {}", code, response["msg"].as_str().unwrap_or_default()))),
+    }
+}
+
+/// The `data.orderId` of a `createOrder` response, sent as a number or a string.
+pub fn response_order_id(response: &Value) -> Option<u64> {
+    let id = &response["data"]["orderId"];
+    id.as_u64().or_else(|| id.as_str().and_then(|s| s.parse().ok()))
+}
+
+#[async_trait]
+impl TradingApi for EdgeXClient {
+    async fn place_order(&self, req: CreateOrderRequest, meta: &MetaData) -> Result<Value, ClientError> {
+        EdgeXClient::place_order(self, req, meta).await
+    }
+
+    async fn cancel_order(&self, req: &CancelOrderRequest) -> Result<Value, ClientError> {
+        EdgeXClient::cancel_order(self, req).await
+    }
+
+    async fn get_open_orders(&self, account_id: u64) -> Result<Vec<OpenOrder>, ClientError> {
+        EdgeXClient::get_open_orders(self, account_id).await
+    }
+
+    async fn get_fills(&self, account_id: u64) -> Result<Vec<Fill>, ClientError> {
+        EdgeXClient::get_fills(self, account_id).await
+    }
+
+    async fn get_positions(&self, account_id: u64) -> Result<Vec<Position>, ClientError> {
+        EdgeXClient::get_positions(self, account_id).await
+    }
+
+    /// Opens a private websocket connection; transport errors end the stream.
+    async fn account_events(&self, account_id: u64) -> Result<BoxStream<'static, AccountEvent>, ClientError> {
+        let ws = EdgeXWebSocket::connect_private(self, account_id, self.ws_url()).await?;
+        let events = EdgeXWebSocket::messages(ws)
+            .take_while(|msg| std::future::ready(msg.is_ok()))
+            .flat_map(|msg| stream::iter(msg.map(|m| parse_account_events(&m)).unwrap_or_default()));
+        Ok(events.boxed())
+    }
+}
@@ -1,14 +1,18 @@
-use futures_util::{StreamExt, SinkExt};
+use futures_util::{SinkExt, Stream, StreamExt};
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use url::Url;
 use serde::{Serialize, Deserialize};
 use serde_json::Value;
-use crate::client::ClientError;
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::client::{ClientError, EdgeXClient};
+use crate::model::{DepthUpdate, Fill, OpenOrder, PublicTrade};
 
 const WS_URL: &str = "wss://quote.edgex.exchange";
+const PRIVATE_WS_PATH: &str = "/api/v1/private/ws";
 
-#[derive(Debug, Serialize, Deserialize)]
+pub type WsStream = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WsMessage {
     pub r#type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -19,6 +23,78 @@ pub struct WsMessage {
     pub payload: Value,
 }
 
+impl WsMessage {
+    /// Lenient parse: `time` may come as a number or a string, and anything
+    /// else is kept in `payload`.
+    pub fn from_value(mut value: Value) -> Self {
+        let r#type = value["type"].as_str().unwrap_or_default().to_string();
+        let channel = value["channel"].as_str().map(str::to_string);
+        let time = value["time"].as_u64().or_else(|| value["time"].as_str().and_then(|s| s.parse().ok()));
+        if let Value::Object(map) = &mut value {
+            map.remove("type");
+            map.remove("channel");
+            map.remove("time");
+        }
+        Self { r#type, channel, time, payload: value }
+    }
+}
+
+/// Parses one text frame the way `EdgeXWebSocket::messages` does.
+pub fn parse_message(text: &str) -> Result<WsMessage, ClientError> {
+    serde_json::from_str::<Value>(text)
+        .map(WsMessage::from_value)
+        .map_err(|e| ClientError::ApiError(e.to_string()))
+}
+
+/// Private account events pushed on the private websocket (`trade-event` messages).
+#[derive(Debug, Clone)]
+pub enum AccountEvent {
+    Order(OpenOrder),
+    Fill(Fill),
+}
+
+/// Extracts order and fill updates from a private `trade-event` message.
+/// Entries that don't deserialize are skipped; other message types yield nothing.
+pub fn parse_account_events(msg: &WsMessage) -> Vec<AccountEvent> {
+    let mut events = Vec::new();
+    if msg.r#type != "trade-event" {
+        return events;
+    }
+    let data = &msg.payload["content"]["data"];
+    if let Some(orders) = data["order"].as_array() {
+        events.extend(orders.iter()
+            .filter_map(|o| serde_json::from_value(o.clone()).ok())
+            .map(AccountEvent::Order));
+    }
+    if let Some(fills) = data["orderFillTransaction"].as_array() {
+        events.extend(fills.iter()
+            .filter_map(|f| serde_json::from_value(f.clone()).ok())
+            .map(AccountEvent::Fill));
+    }
+    events
+}
+
+/// Extracts trades from a public `trades.{contractId}` message.
+/// Entries that don't deserialize are skipped; other channels yield nothing.
+pub fn parse_public_trades(msg: &WsMessage) -> Vec<PublicTrade> {
+    if !msg.channel.as_deref().is_some_and(|c| c.starts_with("trades.")) {
+        return Vec::new();
+    }
+    msg.payload["content"]["data"].as_array()
+        .map(|trades| trades.iter().filter_map(|t| serde_json::from_value(t.clone()).ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Extracts order book updates from a public `depth.*` message.
+pub fn parse_depth_updates(msg: &WsMessage) -> Vec<DepthUpdate> {
+    if !msg.channel.as_deref().is_some_and(|c| c.starts_with("depth.")) {
+        return Vec::new();
+    }
+    msg.payload["content"]["data"].as_array()
+        .map(|updates| updates.iter().filter_map(|u| serde_json::from_value(u.clone()).ok()).collect())
+        .unwrap_or_default()
+}
+
 pub struct EdgeXWebSocket {
     // For now, expose basic stream handling or a loop.
     // In SDKs, usually we provide a callback or channel.
@@ -27,13 +103,60 @@ pub struct EdgeXWebSocket {
 }
 
 impl EdgeXWebSocket {
-    pub async fn connect() -> Result<tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>, ClientError> {
-        let (ws_stream, _) = connect_async(Url::parse(WS_URL).unwrap()).await
+    pub async fn connect() -> Result<WsStream, ClientError> {
+        Self::connect_url(WS_URL).await
+    }
+
+    pub async fn connect_url(url: &str) -> Result<WsStream, ClientError> {
+        let url = Url::parse(url).map_err(|e| ClientError::ApiError(e.to_string()))?;
+        let (ws_stream, _) = connect_async(url).await
+            .map_err(|e| ClientError::ApiError(e.to_string()))?;
+        Ok(ws_stream)
+    }
+
+    /// Connects to the private account stream, authenticated with the same
+    /// `X-edgeX-Api-*` headers as REST. `ws_url` defaults to the public quote host.
+    pub async fn connect_private(client: &EdgeXClient, account_id: u64, ws_url: Option<&str>) -> Result<WsStream, ClientError> {
+        let query = format!("accountId={}", account_id);
+        let url = format!("{}{}?{}", ws_url.unwrap_or(WS_URL), PRIVATE_WS_PATH, query);
+        let mut request = url.into_client_request()
+            .map_err(|e| ClientError::ApiError(e.to_string()))?;
+        let headers = client.auth_headers("GET", PRIVATE_WS_PATH, &query).await?;
+        request.headers_mut().extend(headers);
+        let (ws_stream, _) = connect_async(request).await
             .map_err(|e| ClientError::ApiError(e.to_string()))?;
         Ok(ws_stream)
     }
 
-    pub async fn subscribe(stream: &mut tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>, channel: &str) -> Result<(), ClientError> {
+    /// Turns a connection into a stream of parsed messages, answering server
+    /// pings along the way. Ends when the connection closes.
+    pub fn messages(stream: WsStream) -> impl Stream<Item = Result<WsMessage, ClientError>> {
+        Self::text_messages(stream).map(|text| text.and_then(|text| parse_message(&text)))
+    }
+
+    /// Like `messages`, but yields the raw JSON text of each frame.
+    pub fn text_messages(stream: WsStream) -> impl Stream<Item = Result<String, ClientError>> {
+        futures_util::stream::unfold(stream, |mut stream| async move {
+            loop {
+                let msg = match stream.next().await? {
+                    Ok(msg) => msg,
+                    Err(e) => return Some((Err(ClientError::ApiError(e.to_string())), stream)),
+                };
+                match Self::handle_ping(&mut stream, &msg).await {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => return Some((Err(e), stream)),
+                }
+                match msg {
+                    Message::Text(text) => return Some((Ok(text), stream)),
+                    Message::Close(_) => return None,
+                    _ => continue,
+                }
+            }
+        })
+    }
+
+    pub async fn subscribe(stream: &mut WsStream, channel: &str) -> Result<(), ClientError> {
         let msg = serde_json::json!({
             "type": "subscribe",
             "channel": channel
@@ -46,21 +169,20 @@ impl EdgeXWebSocket {
     // Helper to handle ping/pong automatically if wrapped in a loop.
     // User of SDK will likely consume the stream.
     // We can provide a helper "handle_ping"
-    pub async fn handle_ping(stream: &mut tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>, msg: &Message) -> Result<bool, ClientError> {
-        if let Message::Text(text) = msg {
-            if let Ok(v) = serde_json::from_str::<Value>(text) {
-                if v["type"] == "ping" {
-                    // Send Pong
-                    let time = v["time"].as_u64().or_else(|| v["time"].as_str().and_then(|s| s.parse().ok())).unwrap_or(0);
-                    let pong = serde_json::json!({
-                        "type": "pong",
-                        "time": time
-                    });
-                    stream.send(Message::Text(pong.to_string())).await
-                        .map_err(|e| ClientError::ApiError(e.to_string()))?;
-                    return Ok(true);
-                }
-            }
+    pub async fn handle_ping(stream: &mut WsStream, msg: &Message) -> Result<bool, ClientError> {
+        if let Message::Text(text) = msg
+            && let Ok(v) = serde_json::from_str::<Value>(text)
+            && v["type"] == "ping"
+        {
+            // Send Pong
+            let time = v["time"].as_u64().or_else(|| v["time"].as_str().and_then(|s| s.parse().ok())).unwrap_or(0);
+            let pong = serde_json::json!({
+                "type": "pong",
+                "time": time
+            });
+            stream.send(Message::Text(pong.to_string())).await
+                .map_err(|e| ClientError::ApiError(e.to_string()))?;
+            return Ok(true);
         }
         Ok(false)
     }